@@ -128,13 +128,14 @@ pub struct IgvmParamBlock {
     /// The number of bytes for the stage1 bootloader
     pub stage1_size: u32,
 
-    #[doc(hidden)]
-    pub _reserved2: u32,
+    /// The size, in bytes, of the memory map which begins at
+    /// `memory_map_offset`.  The memory map may span multiple pages.
+    pub memory_map_size: u32,
 
     /// The guest physical address of the base of the stage1 bootloader
     pub stage1_base: u64,
 
-    /// The amount of space that must be reserved at the base of the kernel
+    /// The amount of space that must be reserved at the top of the kernel
     /// memory region (e.g. for VMSA contents).
     pub kernel_reserved_size: u32,
 
@@ -146,6 +147,13 @@ pub struct IgvmParamBlock {
 
     /// The value of vTOM used by the guest, or zero if not used.
     pub vtom: u64,
+
+    /// The offset, in bytes, from the base of the parameter block to the
+    /// ACPI SRAT describing the NUMA topology, or zero if no SRAT is present.
+    pub srat_offset: u32,
+
+    /// The size, in bytes, of the ACPI SRAT.
+    pub srat_size: u32,
 }
 
 /// The IGVM context page is a measured page that is used to specify the start
@@ -112,6 +112,46 @@ pub struct VMSASegment {
     pub base: u64,
 }
 
+impl VMSASegment {
+    /// Attributes of a present, accessed 64-bit code segment.
+    pub const CODE64_FLAGS: u16 = 0xa9b;
+    /// Attributes of a present, accessed 32-bit code segment with 4K
+    /// granularity.
+    pub const CODE32_FLAGS: u16 = 0xc9b;
+    /// Attributes of a present, accessed, writable 32-bit data segment with
+    /// 4K granularity.
+    pub const DATA_FLAGS: u16 = 0xc93;
+    /// Attributes of a present LDT.
+    pub const LDT_FLAGS: u16 = 0x82;
+    /// Attributes of a present, busy 64-bit TSS.
+    pub const TSS_BUSY_FLAGS: u16 = 0x8b;
+
+    const fn flat(selector: u16, flags: u16) -> Self {
+        Self {
+            selector,
+            flags,
+            limit: 0xffff_ffff,
+            base: 0,
+        }
+    }
+}
+
+/// Selects one of the segment or descriptor-table registers held in a
+/// [`VMSA`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SegmentRegister {
+    ES,
+    CS,
+    SS,
+    DS,
+    FS,
+    GS,
+    GDTR,
+    LDTR,
+    IDTR,
+    TR,
+}
+
 #[repr(C, packed)]
 #[derive(Debug)]
 pub struct VMSA {
@@ -339,3 +379,133 @@ impl Default for VMSA {
         }
     }
 }
+
+/// Errors reported when validating the contents of a [`VMSA`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VmsaError {
+    /// The named reserved field contains non-zero data.
+    NonZeroReserved(&'static str),
+}
+
+impl VMSA {
+    /// Code segment selector used by [`VMSA::init_long_mode()`].
+    pub const LONG_MODE_CS: u16 = 0x8;
+    /// Data segment selector used by [`VMSA::init_long_mode()`].
+    pub const LONG_MODE_DS: u16 = 0x10;
+
+    /// Sets up the VMSA for a clean 64-bit entry at `entry`, with paging
+    /// rooted at `cr3` and the stack pointer set to `stack`.  Segment
+    /// registers are flat, EFER has LME, LMA and NXE set, and the remaining
+    /// architectural state is at its reset values.  Fields not covered here,
+    /// such as the VMPL or SEV features, are left untouched for the caller
+    /// to fill in.
+    pub fn init_long_mode(&mut self, entry: u64, stack: u64, cr3: u64) {
+        // PE | MP | ET | NE | WP | PG
+        const CR0: u64 = 0x8001_0033;
+        // PAE | OSFXSR | OSXMMEXCPT
+        const CR4: u64 = 0x0000_0620;
+        // LME | LMA | NXE
+        const EFER: u64 = 0x0000_0d00;
+
+        let data = VMSASegment::flat(Self::LONG_MODE_DS, VMSASegment::DATA_FLAGS);
+        self.cs = VMSASegment::flat(Self::LONG_MODE_CS, VMSASegment::CODE64_FLAGS);
+        self.ds = data;
+        self.es = data;
+        self.fs = data;
+        self.gs = data;
+        self.ss = data;
+        self.ldt = VMSASegment {
+            flags: VMSASegment::LDT_FLAGS,
+            limit: 0xffff,
+            ..Default::default()
+        };
+        self.tr = VMSASegment {
+            flags: VMSASegment::TSS_BUSY_FLAGS,
+            limit: 0xffff,
+            ..Default::default()
+        };
+
+        self.cr0 = CR0;
+        self.cr3 = cr3;
+        self.cr4 = CR4;
+        self.efer = EFER;
+
+        self.rip = entry;
+        self.rsp = stack;
+        self.rflags = 0x2;
+        self.dr6 = 0xffff_0ff0;
+        self.dr7 = 0x0400;
+        self.g_pat = 0x0007_0406_0007_0406;
+        self.xcr0 = 1;
+        self.mxcsr = 0x1f80;
+        self.x87_ftw = 0x5555;
+        self.x87_fcw = 0x0040;
+    }
+
+    /// Returns a copy of the state of segment register `reg`.
+    pub fn get_segment(&self, reg: SegmentRegister) -> VMSASegment {
+        match reg {
+            SegmentRegister::ES => self.es,
+            SegmentRegister::CS => self.cs,
+            SegmentRegister::SS => self.ss,
+            SegmentRegister::DS => self.ds,
+            SegmentRegister::FS => self.fs,
+            SegmentRegister::GS => self.gs,
+            SegmentRegister::GDTR => self.gdt,
+            SegmentRegister::LDTR => self.ldt,
+            SegmentRegister::IDTR => self.idt,
+            SegmentRegister::TR => self.tr,
+        }
+    }
+
+    /// Replaces the state of segment register `reg` with `seg`.
+    pub fn set_segment(&mut self, reg: SegmentRegister, seg: VMSASegment) {
+        match reg {
+            SegmentRegister::ES => self.es = seg,
+            SegmentRegister::CS => self.cs = seg,
+            SegmentRegister::SS => self.ss = seg,
+            SegmentRegister::DS => self.ds = seg,
+            SegmentRegister::FS => self.fs = seg,
+            SegmentRegister::GS => self.gs = seg,
+            SegmentRegister::GDTR => self.gdt = seg,
+            SegmentRegister::LDTR => self.ldt = seg,
+            SegmentRegister::IDTR => self.idt = seg,
+            SegmentRegister::TR => self.tr = seg,
+        }
+    }
+
+    /// Checks that all reserved fields of the VMSA are zero, returning the
+    /// name of the first field that is not.  This is a cheap integrity check
+    /// for a VMSA that may have been modified by the host or a lower VMPL.
+    pub fn validate_reserved(&self) -> Result<(), VmsaError> {
+        let scalars: [(&'static str, u64); 5] = [
+            ("reserved_0c8", self.reserved_0c8.into()),
+            ("reserved_0cc", self.reserved_0cc.into()),
+            ("reserved_2e0", self.reserved_2e0),
+            ("reserved_2ec", self.reserved_2ec.into()),
+            ("reserved_320", self.reserved_320),
+        ];
+        let arrays: [(&'static str, &[u8]); 7] = [
+            ("reserved_0d8", &self.reserved_0d8),
+            ("reserved_1c0", &self.reserved_1c0),
+            ("reserved_248", &self.reserved_248),
+            ("reserved_298", &self.reserved_298),
+            ("reserved_380", &self.reserved_380),
+            ("reserved_3f0", &self.reserved_3f0),
+            ("reserved_670", &self.reserved_670),
+        ];
+
+        for (name, value) in scalars {
+            if value != 0 {
+                return Err(VmsaError::NonZeroReserved(name));
+            }
+        }
+        for (name, bytes) in arrays {
+            if bytes.iter().any(|&b| b != 0) {
+                return Err(VmsaError::NonZeroReserved(name));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -69,6 +69,37 @@ impl Elf64DynamicSymtab {
     }
 }
 
+/// Represents an ELF64 dynamic string table
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Elf64DynamicStrtab {
+    /// Virtual address of the string table (DT_STRTAB)
+    pub base_vaddr: Elf64Addr,
+    /// Size of the string table (DT_STRSZ)
+    pub size: Elf64Xword,
+}
+
+impl Elf64DynamicStrtab {
+    /// Verifies the integrity and validity of the dynamic string table.
+    ///
+    /// # Returns
+    ///
+    /// Returns [`Ok`] if the dynamic string table is valid; otherwise, returns an
+    /// [`ElfError`] indicating the issue.
+    fn verify(&self) -> Result<(), ElfError> {
+        Elf64AddrRange::try_from((self.base_vaddr, self.size))?;
+        Ok(())
+    }
+
+    /// Calculates and returns the virtual address range covered by the dynamic string table.
+    ///
+    /// # Returns
+    ///
+    /// An [`Elf64AddrRange`] representing the virtual address range of the dynamic string table.
+    pub fn vaddr_range(&self) -> Elf64AddrRange {
+        Elf64AddrRange::try_from((self.base_vaddr, self.size)).unwrap()
+    }
+}
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Elf64Dynamic {
     // No DT_REL representation: "The AMD64 ABI architectures uses only
@@ -77,6 +108,10 @@ pub struct Elf64Dynamic {
     pub rela: Option<Elf64DynamicRelocTable>,
     /// Optional representation of the dynamic symbol table (DT_SYMTAB)
     pub symtab: Option<Elf64DynamicSymtab>,
+    /// Optional representation of the dynamic string table (DT_STRTAB)
+    pub strtab: Option<Elf64DynamicStrtab>,
+    /// Optional virtual address of the GNU hash table (DT_GNU_HASH)
+    pub gnu_hash: Option<Elf64Addr>,
     /// Flags related to dynamic linking (DT_FLAGS_1)
     pub flags_1: Elf64Xword,
 }
@@ -136,6 +171,11 @@ impl Elf64Dynamic {
         let mut syment: Option<Elf64Xword> = None;
         let mut symtab_shndx: Option<Elf64Addr> = None;
 
+        let mut strtab: Option<Elf64Addr> = None;
+        let mut strsz: Option<Elf64Xword> = None;
+
+        let mut gnu_hash: Option<Elf64Addr> = None;
+
         let mut flags_1: Option<Elf64Xword> = None;
 
         let mut fields = [
@@ -145,16 +185,16 @@ impl Elf64Dynamic {
             (Self::DT_SYMTAB, &mut symtab),
             (Self::DT_SYMENT, &mut syment),
             (Self::DT_SYMTAB_SHNDX, &mut symtab_shndx),
+            (Self::DT_STRTAB, &mut strtab),
+            (Self::DT_STRSZ, &mut strsz),
+            (Self::DT_GNU_HASH, &mut gnu_hash),
             (Self::DT_FLAGS_1, &mut flags_1),
         ];
         let ignored_fields = [
             Self::DT_HASH,
-            Self::DT_STRTAB,
-            Self::DT_STRSZ,
             Self::DT_DEBUG,
             Self::DT_TEXTREL,
             Self::DT_FLAGS,
-            Self::DT_GNU_HASH,
             Self::DT_RELACOUNT,
         ];
         let mut null_seen = false;
@@ -210,11 +250,24 @@ impl Elf64Dynamic {
             None
         };
 
+        let strtab = if strtab.is_some() || strsz.is_some() {
+            let strtab = strtab.ok_or(ElfError::MissingDynamicField)?;
+            let strsz = strsz.ok_or(ElfError::MissingDynamicField)?;
+            Some(Elf64DynamicStrtab {
+                base_vaddr: strtab,
+                size: strsz,
+            })
+        } else {
+            None
+        };
+
         let flags_1 = flags_1.unwrap_or(0);
 
         Ok(Elf64Dynamic {
             rela,
             symtab,
+            strtab,
+            gnu_hash,
             flags_1,
         })
     }
@@ -232,6 +285,9 @@ impl Elf64Dynamic {
         if let Some(symtab) = &self.symtab {
             symtab.verify()?;
         }
+        if let Some(strtab) = &self.strtab {
+            strtab.verify()?;
+        }
         Ok(())
     }
 
@@ -49,6 +49,7 @@ pub enum ElfError {
     UnalignedSegmentAddress,
     LoadSegmentConflict,
     DynamicPhdrConflict,
+    InvalidInterpString,
 
     UnterminatedDynamicSection,
     DynamicFieldConflict,
@@ -62,11 +63,13 @@ pub enum ElfError {
 
     InvalidSymbolEntrySize,
     InvalidSymbolIndex,
+    InvalidGnuHashTable,
 
     InvalidRelocationEntrySize,
     UnrecognizedRelocationType,
     InvalidRelocationOffset,
     RelocationAgainstUndefSymbol,
+    RelocationBufferTooSmall,
 }
 
 impl fmt::Display for ElfError {
@@ -132,6 +135,9 @@ impl fmt::Display for ElfError {
             Self::DynamicPhdrConflict => {
                 write!(f, "multiple ELF PT_DYNAMIC program headers")
             }
+            Self::InvalidInterpString => {
+                write!(f, "invalid ELF PT_INTERP string")
+            }
 
             Self::UnterminatedDynamicSection => {
                 write!(f, "unterminated ELF dynamic section")
@@ -163,6 +169,9 @@ impl fmt::Display for ElfError {
             Self::InvalidSymbolIndex => {
                 write!(f, "invalid ELF symbol index")
             }
+            Self::InvalidGnuHashTable => {
+                write!(f, "invalid ELF GNU hash table")
+            }
 
             Self::InvalidRelocationEntrySize => {
                 write!(f, "invalid ELF relocation entry size")
@@ -176,6 +185,9 @@ impl fmt::Display for ElfError {
             Self::RelocationAgainstUndefSymbol => {
                 write!(f, "ELF relocation against undefined symbol")
             }
+            Self::RelocationBufferTooSmall => {
+                write!(f, "buffer too small for applied ELF relocations")
+            }
         }
     }
 }
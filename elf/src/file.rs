@@ -8,12 +8,15 @@
 
 use super::types::*;
 use super::{
-    Elf64AddrRange, Elf64AppliedRelaIterator, Elf64Dynamic, Elf64FileRange, Elf64Hdr,
-    Elf64ImageLoadSegmentIterator, Elf64ImageLoadVaddrAllocInfo, Elf64LoadSegments, Elf64Phdr,
-    Elf64Relas, Elf64RelocProcessor, Elf64Shdr, Elf64ShdrFlags, Elf64ShdrIterator, Elf64Strtab,
-    Elf64Symtab, ElfError,
+    Elf64AddrRange, Elf64AppliedRelaIterator, Elf64Dynamic, Elf64FileRange, Elf64GnuHash, Elf64Hdr,
+    Elf64ImageLoadSegmentIterator, Elf64ImageLoadVaddrAllocInfo, Elf64LoadSegments, Elf64Machine,
+    Elf64MachineRelocProcessor, Elf64Phdr, Elf64PhdrFlags, Elf64Relas, Elf64RelocProcessor,
+    Elf64Shdr, Elf64ShdrFlags, Elf64ShdrIterator, Elf64Strtab, Elf64Sym, Elf64Symtab,
+    Elf64SymtabShndx, Elf64TlsTemplate, ElfError,
 };
 
+use core::ffi;
+
 /// This struct represents a parsed 64-bit ELF file. It contains information
 /// about the ELF file's header, load segments, dynamic section, and more.
 #[derive(Default, Debug, PartialEq)]
@@ -30,9 +33,14 @@ pub struct Elf64File<'a> {
     #[allow(unused)]
     pub sh_strtab: Option<Elf64Strtab<'a>>,
     pub dynamic: Option<Elf64Dynamic>,
+    /// The stack permissions requested via PT_GNU_STACK
+    stack_flags: Elf64PhdrFlags,
 }
 
 impl<'a> Elf64File<'a> {
+    /// The page size images get loaded with
+    const PAGE_SIZE: Elf64Xword = 0x1000;
+
     /// This method takes a byte buffer containing the ELF file data and parses
     /// it into an [`Elf64File`] struct, providing access to the ELF file's information.
     ///
@@ -82,6 +90,8 @@ impl<'a> Elf64File<'a> {
         let mut load_segments = Elf64LoadSegments::new();
         let mut max_load_segment_align = 0;
         let mut dynamic_file_range: Option<Elf64FileRange> = None;
+        // Without a PT_GNU_STACK header, the stack is non-executable.
+        let mut stack_flags = Elf64PhdrFlags::READ | Elf64PhdrFlags::WRITE;
         for i in 0..elf_hdr.e_phnum {
             let phdr = Self::read_phdr_from_file(elf_file_buf, &elf_hdr, i);
             Self::verify_phdr(&phdr, elf_file_buf.len())?;
@@ -99,6 +109,8 @@ impl<'a> Elf64File<'a> {
                     return Err(ElfError::DynamicPhdrConflict);
                 }
                 dynamic_file_range = Some(phdr.file_range());
+            } else if phdr.p_type == Elf64Phdr::PT_GNU_STACK {
+                stack_flags = phdr.p_flags;
             }
         }
 
@@ -149,9 +161,24 @@ impl<'a> Elf64File<'a> {
             max_load_segment_align,
             sh_strtab,
             dynamic,
+            stack_flags,
         })
     }
 
+    /// Validates an ELF file without retaining a reference to its buffer.
+    ///
+    /// This performs the same header, program header, section header and
+    /// dynamic section checks as [`Self::read()`]. It allows verifying an
+    /// untrusted image and then moving the buffer before parsing it for real.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ElfError`] if there are issues parsing the ELF file.
+    pub fn validate(elf_file_buf: &[u8]) -> Result<(), ElfError> {
+        Elf64File::read(elf_file_buf)?;
+        Ok(())
+    }
+
     /// Reads an ELF Program Header (Phdr) from the ELF file buffer.
     ///
     /// This function reads an ELF Program Header (Phdr) from the provided ELF file buffer
@@ -222,6 +249,84 @@ impl<'a> Elf64File<'a> {
         Self::read_phdr_from_file(self.elf_file_buf, &self.elf_hdr, i)
     }
 
+    /// Returns the stack permissions requested by the ELF image.
+    ///
+    /// These are the flags of the `PT_GNU_STACK` program header, or
+    /// readable and writable, but not executable, if there is none.
+    pub fn stack_flags(&self) -> Elf64PhdrFlags {
+        self.stack_flags
+    }
+
+    /// Retrieves the Thread-Local Storage (TLS) template of the ELF image.
+    ///
+    /// This method locates the `PT_TLS` program header and maps its
+    /// initialization image to the file contents of the containing `PT_LOAD`
+    /// segment. Images with a `PT_TLS` `->p_filesz` exceeding its `->p_memsz`
+    /// have already been rejected by [`Self::read()`].
+    ///
+    /// # Returns
+    ///
+    /// - [`Ok<Some>`]: The [`Elf64TlsTemplate`], if the image uses TLS.
+    /// - [`Ok<None>`]: If the image has no `PT_TLS` program header.
+    /// - [`Err<ElfError>`]: If the initialization image is not backed by the
+    ///   file contents of a `PT_LOAD` segment.
+    pub fn tls_template(&self) -> Result<Option<Elf64TlsTemplate<'a>>, ElfError> {
+        let phdr = match (0..self.elf_hdr.e_phnum)
+            .map(|i| self.read_phdr(i))
+            .find(|phdr| phdr.p_type == Elf64Phdr::PT_TLS)
+        {
+            Some(phdr) => phdr,
+            None => return Ok(None),
+        };
+
+        let init_data = if phdr.p_filesz != 0 {
+            let vaddr_range = phdr.vaddr_range();
+            let vaddr_end = vaddr_range.vaddr_begin + phdr.p_filesz;
+            self.map_vaddr_to_file_buf(vaddr_range.vaddr_begin, Some(vaddr_end))?
+        } else {
+            &[]
+        };
+
+        Ok(Some(Elf64TlsTemplate {
+            init_data,
+            mem_size: phdr.p_memsz,
+            align: phdr.p_align,
+        }))
+    }
+
+    /// Retrieves the program interpreter requested by the ELF image.
+    ///
+    /// This method locates the `PT_INTERP` program header and returns the
+    /// NUL-terminated interpreter path it refers to.
+    ///
+    /// # Returns
+    ///
+    /// - [`Ok<Some>`]: The interpreter path, for dynamically linked images.
+    /// - [`Ok<None>`]: If the image has no `PT_INTERP` program header, i.e. is
+    ///   statically linked.
+    /// - [`Err<ElfError>`]: If the interpreter path is not NUL-terminated within
+    ///   the segment's file contents.
+    pub fn interpreter(&self) -> Result<Option<&'a ffi::CStr>, ElfError> {
+        let phdr = match (0..self.elf_hdr.e_phnum)
+            .map(|i| self.read_phdr(i))
+            .find(|phdr| phdr.p_type == Elf64Phdr::PT_INTERP)
+        {
+            Some(phdr) => phdr,
+            None => return Ok(None),
+        };
+
+        // The file range has been checked against the file bounds at load
+        // time, unless ->p_filesz is zero, in which case there's no path.
+        if phdr.p_filesz == 0 {
+            return Err(ElfError::InvalidInterpString);
+        }
+        let file_range = phdr.file_range();
+        let interp_buf = &self.elf_file_buf[file_range.offset_begin..file_range.offset_end];
+        ffi::CStr::from_bytes_until_nul(interp_buf)
+            .map(Some)
+            .map_err(|_| ElfError::InvalidInterpString)
+    }
+
     /// Checks if the section header table is within the ELF file bounds.
     ///
     /// This function verifies that the section header table is within the bounds of
@@ -348,6 +453,44 @@ impl<'a> Elf64File<'a> {
         Elf64ShdrIterator::new(self)
     }
 
+    /// Returns the contents of the section described by `shdr`.
+    fn section_buf(&self, shdr: &Elf64Shdr) -> &'a [u8] {
+        let file_range = shdr.file_range();
+        &self.elf_file_buf[file_range.offset_begin..file_range.offset_end]
+    }
+
+    /// Reads the symbol table stored in the section with index `i`.
+    ///
+    /// If a `SHT_SYMTAB_SHNDX` section is linked to the symbol table, it is
+    /// attached to the returned [`Elf64Symtab`] so that
+    /// [`Elf64Symtab::sym_shndx()`] resolves symbols with a `st_shndx` of
+    /// `SHN_XINDEX`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err<ElfError>`] if `i` is out of bounds or the section is
+    /// not of type `SHT_SYMTAB` or `SHT_DYNSYM`.
+    pub fn section_symtab(&self, i: Elf64Word) -> Result<Elf64Symtab<'a>, ElfError> {
+        if i >= self.elf_hdr.e_shnum {
+            return Err(ElfError::InvalidSectionIndex);
+        }
+        let shdr = self.read_shdr(i);
+        if shdr.sh_type != Elf64Shdr::SHT_SYMTAB && shdr.sh_type != Elf64Shdr::SHT_DYNSYM {
+            return Err(ElfError::IncompatibleSectionType);
+        }
+        let symtab = Elf64Symtab::new(self.section_buf(&shdr), shdr.sh_entsize)?;
+
+        let shndx_shdr = self
+            .shdrs_iter()
+            .find(|s| s.sh_type == Elf64Shdr::SHT_SYMTAB_SHNDX && s.sh_link == i);
+        Ok(match shndx_shdr {
+            Some(shndx_shdr) => {
+                symtab.with_shndx(Elf64SymtabShndx::new(self.section_buf(&shndx_shdr)))
+            }
+            None => symtab,
+        })
+    }
+
     /// Verifies the integrity of the ELF Dynamic section.
     ///
     /// This function verifies the integrity of the ELF Dynamic section.
@@ -470,7 +613,7 @@ impl<'a> Elf64File<'a> {
         &self,
         vaddr_begin: Elf64Addr,
         vaddr_end: Option<Elf64Addr>,
-    ) -> Result<&[u8], ElfError> {
+    ) -> Result<&'a [u8], ElfError> {
         let file_range = self.map_vaddr_to_file_off(vaddr_begin, vaddr_end)?;
         Ok(&self.elf_file_buf[file_range.offset_begin..file_range.offset_end])
     }
@@ -544,6 +687,26 @@ impl<'a> Elf64File<'a> {
         Elf64ImageLoadVaddrAllocInfo { range, align }
     }
 
+    /// Calculates the size of the memory to allocate for the loaded image.
+    ///
+    /// This covers all `PT_LOAD` segments, starting from the aligned image
+    /// beginning as returned by [`Self::image_load_vaddr_alloc_info()`], and
+    /// is rounded up to a multiple of the page size.
+    ///
+    /// # Returns
+    ///
+    /// - [`Ok<usize>`]: The page-aligned size of the memory image.
+    /// - [`Err<ElfError>`]: [`ElfError::InvalidAddressRange`] if the size
+    ///   overflows.
+    pub fn total_mem_image_size(&self) -> Result<usize, ElfError> {
+        let len = self.image_load_vaddr_alloc_info().range.len();
+        let len = len
+            .checked_add(Self::PAGE_SIZE - 1)
+            .ok_or(ElfError::InvalidAddressRange)?
+            & !(Self::PAGE_SIZE - 1);
+        usize::try_from(len).map_err(|_| ElfError::InvalidAddressRange)
+    }
+
     /// Creates an iterator over the ELF segments that are part of the loaded image.
     ///
     /// This function returns an iterator that allows iterating over the ELF segments
@@ -570,6 +733,57 @@ impl<'a> Elf64File<'a> {
         }
     }
 
+    /// Copies the loaded image into a destination buffer.
+    ///
+    /// This function copies the file contents of each `PT_LOAD` segment to its
+    /// offset within `dest`, which represents the virtual address range returned
+    /// by [`Self::image_load_vaddr_alloc_info()`]. Any part of the image not
+    /// backed by file contents, i.e. the `->p_memsz - ->p_filesz` tail of each
+    /// segment and any gaps between segments, is zero-filled.
+    ///
+    /// # Arguments
+    ///
+    /// * `dest` - The buffer to load the image into. It must be at least as
+    ///   large as the image's virtual address range.
+    /// * `image_load_addr` - The virtual address where the ELF image is loaded in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ElfError::InvalidAddressRange`] if `dest` is too small to hold
+    /// the image.
+    pub fn load_into(
+        &'a self,
+        dest: &mut [u8],
+        image_load_addr: Elf64Addr,
+    ) -> Result<(), ElfError> {
+        let alloc_range = self.image_load_vaddr_alloc_info().range;
+        let image_len =
+            usize::try_from(alloc_range.len()).map_err(|_| ElfError::InvalidAddressRange)?;
+        let dest = dest
+            .get_mut(..image_len)
+            .ok_or(ElfError::InvalidAddressRange)?;
+
+        // Zero the whole image first, which takes care of both the
+        // segments' unbacked tails and the gaps in between.
+        dest.fill(0);
+
+        let dest_vaddr = alloc_range
+            .vaddr_begin
+            .wrapping_add(self.load_base(image_load_addr));
+        for segment in self.image_load_segment_iter(image_load_addr) {
+            let offset = segment.vaddr_range.vaddr_begin.wrapping_sub(dest_vaddr);
+            let offset = usize::try_from(offset).map_err(|_| ElfError::InvalidAddressRange)?;
+            let end = offset
+                .checked_add(segment.file_contents.len())
+                .ok_or(ElfError::InvalidAddressRange)?;
+            dest.get_mut(offset..end)
+                .ok_or(ElfError::InvalidAddressRange)?
+                .copy_from_slice(segment.file_contents);
+        }
+
+        Ok(())
+    }
+
     ///
     /// This function processes dynamic relocations (relas) in the ELF file and applies them
     /// to the loaded image. It takes a generic `rela_proc` parameter that should implement the
@@ -632,6 +846,79 @@ impl<'a> Elf64File<'a> {
         )))
     }
 
+    /// Looks up a dynamic symbol by name.
+    ///
+    /// If the image provides a GNU hash table (`DT_GNU_HASH`), it is used
+    /// to find the symbol without scanning the whole dynamic symbol table.
+    /// Otherwise, the dynamic symbol table is searched linearly.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the symbol to look up.
+    ///
+    /// # Returns
+    ///
+    /// The matching defined [`Elf64Sym`], or [`None`] if the image has no
+    /// dynamic symbol table, the symbol is not found or the tables are malformed.
+    pub fn gnu_hash_lookup(&self, name: &str) -> Option<Elf64Sym> {
+        self.lookup_dynamic_sym(name).ok().flatten()
+    }
+
+    fn lookup_dynamic_sym(&self, name: &str) -> Result<Option<Elf64Sym>, ElfError> {
+        let dynamic = match &self.dynamic {
+            Some(dynamic) => dynamic,
+            None => return Ok(None),
+        };
+        let (dynamic_symtab, dynamic_strtab) = match (&dynamic.symtab, &dynamic.strtab) {
+            (Some(dynamic_symtab), Some(dynamic_strtab)) => (dynamic_symtab, dynamic_strtab),
+            _ => return Ok(None),
+        };
+
+        let syms_buf = self.map_vaddr_to_file_buf(dynamic_symtab.base_vaddr, None)?;
+        let symtab = Elf64Symtab::new(syms_buf, dynamic_symtab.entsize)?;
+        let strtab_vaddr_range = dynamic_strtab.vaddr_range();
+        let strtab_buf = self.map_vaddr_to_file_buf(
+            strtab_vaddr_range.vaddr_begin,
+            Some(strtab_vaddr_range.vaddr_end),
+        )?;
+        let strtab = Elf64Strtab::new(strtab_buf);
+
+        if let Some(gnu_hash_vaddr) = dynamic.gnu_hash {
+            let gnu_hash_buf = self.map_vaddr_to_file_buf(gnu_hash_vaddr, None)?;
+            let gnu_hash = Elf64GnuHash::new(gnu_hash_buf)?;
+            return gnu_hash.lookup(name, &symtab, &strtab);
+        }
+
+        // The size of the dynamic symbol table is not known, the buffer
+        // extends to the end of the containing segment. Skip over anything
+        // that doesn't resolve to a valid name rather than failing.
+        for i in (Elf64Symtab::STN_UNDEF + 1)..symtab.syms_num() {
+            let sym = symtab.read_sym(i)?;
+            if Elf64Word::from(sym.st_shndx) == Elf64Shdr::SHN_UNDEF {
+                continue;
+            }
+            if strtab
+                .get_str(sym.st_name)
+                .is_ok_and(|sym_name| sym_name.to_bytes() == name.as_bytes())
+            {
+                return Ok(Some(sym));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the target architecture of the ELF image.
+    pub fn machine(&self) -> Elf64Machine {
+        self.elf_hdr.machine()
+    }
+
+    /// Returns the relocation processor matching the ELF image's target
+    /// architecture, to be passed to [`Self::apply_dyn_relas()`].
+    pub fn reloc_processor(&self) -> Elf64MachineRelocProcessor {
+        Elf64MachineRelocProcessor::new(self.machine())
+    }
+
     /// Retrieves the entry point virtual address of the ELF image.
     ///
     /// This function returns the virtual address of the entry point of the ELF image.
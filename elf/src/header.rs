@@ -10,6 +10,15 @@ use super::types::*;
 use super::ElfError;
 use core::mem;
 
+/// Target architecture of an ELF64 file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Elf64Machine {
+    /// AMD x86-64 (EM_X86_64)
+    X86_64,
+    /// ARM AArch64 (EM_AARCH64)
+    Aarch64,
+}
+
 /// Header of the ELF64 file, including fields describing properties such
 /// as type, machine architecture, entry point, etc.
 
@@ -73,6 +82,7 @@ impl Elf64Hdr {
     const ET_EXEC: Elf64Half = 2;
 
     const EM_X86_64: Elf64Half = 62;
+    const EM_AARCH64: Elf64Half = 183;
 
     const EV_CURRENT: Elf64Word = 1;
 
@@ -145,7 +155,7 @@ impl Elf64Hdr {
         if e_type != Self::ET_EXEC {
             return Err(ElfError::UnsupportedType);
         }
-        if e_machine != Self::EM_X86_64 {
+        if e_machine != Self::EM_X86_64 && e_machine != Self::EM_AARCH64 {
             return Err(ElfError::UnsupportedMachine);
         }
         if e_version != Self::EV_CURRENT {
@@ -169,4 +179,13 @@ impl Elf64Hdr {
             e_shstrndx,
         })
     }
+
+    /// Returns the target architecture of the ELF file.
+    pub fn machine(&self) -> Elf64Machine {
+        // Only the machines listed here are accepted by read().
+        match self.e_machine {
+            Self::EM_AARCH64 => Elf64Machine::Aarch64,
+            _ => Elf64Machine::X86_64,
+        }
+    }
 }
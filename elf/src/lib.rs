@@ -27,17 +27,19 @@ pub use error::ElfError;
 pub use file::Elf64File;
 pub use file_range::Elf64FileRange;
 use header::Elf64Hdr;
+pub use header::Elf64Machine;
 pub use load_segments::{
     Elf64ImageLoadSegment, Elf64ImageLoadSegmentIterator, Elf64ImageLoadVaddrAllocInfo,
-    Elf64LoadSegments,
+    Elf64LoadSegments, Elf64TlsTemplate,
 };
 pub use program_header::{Elf64Phdr, Elf64PhdrFlags};
 pub use relocation::{
-    Elf64AppliedRelaIterator, Elf64Rela, Elf64Relas, Elf64RelocOp, Elf64RelocProcessor,
-    Elf64X86RelocProcessor,
+    Elf64Aarch64RelocProcessor, Elf64AppliedRelaIterator, Elf64MachineRelocProcessor,
+    Elf64RecordingRelocProcessor, Elf64Rela, Elf64Relas, Elf64RelocOp, Elf64RelocProcessor,
+    Elf64RelocRecord, Elf64RelocTypeSet, Elf64X86RelocProcessor,
 };
 pub use section_header::{Elf64Shdr, Elf64ShdrFlags, Elf64ShdrIterator};
-pub use syms::{Elf64Strtab, Elf64Sym, Elf64Symtab};
+pub use syms::{Elf64GnuHash, Elf64Strtab, Elf64Sym, Elf64Symtab, Elf64SymtabShndx};
 pub use types::*;
 
 #[cfg(test)]
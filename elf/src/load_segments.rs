@@ -155,6 +155,18 @@ pub struct Elf64ImageLoadSegment<'a> {
     pub flags: Elf64PhdrFlags,
 }
 
+/// Represents an ELF64 Thread-Local Storage (TLS) template, as described by
+/// the `PT_TLS` program header
+#[derive(Debug, Clone, Copy)]
+pub struct Elf64TlsTemplate<'a> {
+    /// The TLS initialization image in the ELF file
+    pub init_data: &'a [u8],
+    /// The total size of the TLS block, including the zero-initialized part
+    pub mem_size: Elf64Xword,
+    /// Alignment of the TLS block
+    pub align: Elf64Xword,
+}
+
 /// An iterator over ELF64 image load segments within an ELF file
 #[derive(Debug)]
 pub struct Elf64ImageLoadSegmentIterator<'a> {
@@ -15,7 +15,7 @@ use bitflags::bitflags;
 bitflags! {
 /// Attributes of an ELF64 program header, to specify whether
 /// the segment is readable, writable, and/or executable
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
     pub struct Elf64PhdrFlags : Elf64Word {
         const EXECUTE = 0x01;
         const WRITE   = 0x02;
@@ -24,7 +24,7 @@ bitflags! {
 }
 
 /// Program header entry in an ELF64 file
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct Elf64Phdr {
     /// Type of the program header entry
     pub p_type: Elf64Word,
@@ -59,6 +59,10 @@ impl Elf64Phdr {
     pub const PT_SHLIB: Elf64Word = 5;
     /// Represents the Program Header Table itself
     pub const PT_PHDR: Elf64Word = 6;
+    /// Represents the Thread-Local Storage template program header type
+    pub const PT_TLS: Elf64Word = 7;
+    /// Represents the GNU stack permissions program header type
+    pub const PT_GNU_STACK: Elf64Word = 0x6474e551;
     /// Processor-specific entries lower bound
     pub const PT_LOPROC: Elf64Word = 0x70000000;
     /// Processor-specific entries upper bound
@@ -108,7 +112,9 @@ impl Elf64Phdr {
             return Ok(());
         }
 
-        if self.p_type == Self::PT_LOAD && self.p_memsz < self.p_filesz {
+        if (self.p_type == Self::PT_LOAD || self.p_type == Self::PT_TLS)
+            && self.p_memsz < self.p_filesz
+        {
             return Err(ElfError::InvalidSegmentSize);
         }
 
@@ -7,7 +7,9 @@
 // vim: ts=4 sw=4 et
 
 use super::types::*;
-use super::{Elf64AddrRange, Elf64LoadSegments, Elf64Shdr, Elf64Symtab, ElfError};
+use super::{Elf64AddrRange, Elf64LoadSegments, Elf64Machine, Elf64Shdr, Elf64Symtab, ElfError};
+
+use core::cell::Cell;
 
 /// Represents a relocation entry in an ELF64 file ([`Elf64Rela`])
 #[derive(Debug, Clone, Copy)]
@@ -161,6 +163,65 @@ impl Elf64X86RelocProcessor {
     pub fn new() -> Self {
         Self
     }
+
+    /// Returns the name of an x86_64 relocation type, for diagnostics.
+    ///
+    /// # Arguments
+    ///
+    /// - `r_type`: The relocation type.
+    ///
+    /// # Returns
+    ///
+    /// - [`Option<&'static str>`]: The relocation type's name, or [`None`] if
+    ///   the type is not known.
+    pub fn type_name(r_type: Elf64Word) -> Option<&'static str> {
+        const NAMES: [&str; 43] = [
+            "R_X86_64_NONE",
+            "R_X86_64_64",
+            "R_X86_64_PC32",
+            "R_X86_64_GOT32",
+            "R_X86_64_PLT32",
+            "R_X86_64_COPY",
+            "R_X86_64_GLOB_DAT",
+            "R_X86_64_JUMP_SLOT",
+            "R_X86_64_RELATIVE",
+            "R_X86_64_GOTPCREL",
+            "R_X86_64_32",
+            "R_X86_64_32S",
+            "R_X86_64_16",
+            "R_X86_64_PC16",
+            "R_X86_64_8",
+            "R_X86_64_PC8",
+            "R_X86_64_DTPMOD64",
+            "R_X86_64_DTPOFF64",
+            "R_X86_64_TPOFF64",
+            "R_X86_64_TLSGD",
+            "R_X86_64_TLSLD",
+            "R_X86_64_DTPOFF32",
+            "R_X86_64_GOTTPOFF",
+            "R_X86_64_TPOFF32",
+            "R_X86_64_PC64",
+            "R_X86_64_GOTOFF64",
+            "R_X86_64_GOTPC32",
+            "R_X86_64_GOT64",
+            "R_X86_64_GOTPCREL64",
+            "R_X86_64_GOTPC64",
+            "R_X86_64_GOTPLT64",
+            "R_X86_64_PLTOFF64",
+            "R_X86_64_SIZE32",
+            "R_X86_64_SIZE64",
+            "R_X86_64_GOTPC32_TLSDESC",
+            "R_X86_64_TLSDESC_CALL",
+            "R_X86_64_TLSDESC",
+            "R_X86_64_IRELATIVE",
+            "R_X86_64_RELATIVE64",
+            "R_X86_64_PC32_BND",
+            "R_X86_64_PLT32_BND",
+            "R_X86_64_GOTPCRELX",
+            "R_X86_64_REX_GOTPCRELX",
+        ];
+        NAMES.get(usize::try_from(r_type).ok()?).copied()
+    }
 }
 
 impl Default for Elf64X86RelocProcessor {
@@ -234,6 +295,240 @@ impl Elf64RelocProcessor for Elf64X86RelocProcessor {
     }
 }
 
+/// Relocation processor specifically for AArch64 ELF files.
+#[derive(Clone, Copy, Debug)]
+pub struct Elf64Aarch64RelocProcessor;
+
+impl Elf64Aarch64RelocProcessor {
+    /// Relocation type value for a 64-bit absolute relocation
+    const R_AARCH64_ABS64: Elf64Word = 257;
+    /// Relocation type value for a GOT entry relocation
+    const R_AARCH64_GLOB_DAT: Elf64Word = 1025;
+    /// Relocation type value for a PLT entry relocation
+    const R_AARCH64_JUMP_SLOT: Elf64Word = 1026;
+    /// Relocation type value for a relative relocation
+    const R_AARCH64_RELATIVE: Elf64Word = 1027;
+
+    /// Creates a new [`Elf64Aarch64RelocProcessor`] instance
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for Elf64Aarch64RelocProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Elf64RelocProcessor for Elf64Aarch64RelocProcessor {
+    /// Applies a relocation operation for AArch64 ELF files.
+    ///
+    /// # Arguments
+    ///
+    /// - `rela`: The relocation entry specifying the operation.
+    /// - `load_base`: The base address for loading ELF sections.
+    /// - `sym_value`: The value associated with the symbol being relocated.
+    ///
+    /// # Returns
+    ///
+    /// - [`Result<Elf64RelocOp, ElfError>`]: A [`Result`] containing the relocation
+    ///   operation ([`Elf64RelocOp`]) if successful, or an [`ElfError`] if there was an
+    ///   issue applying the relocation.
+    fn apply_relocation(
+        &self,
+        rela: &Elf64Rela,
+        load_base: Elf64Xword,
+        sym_value: Elf64Addr,
+    ) -> Result<Elf64RelocOp, ElfError> {
+        // load_base is the difference between the actual mapping addresses
+        // and the ELF vaddrs. This signed difference is represented in
+        // two's complement.
+        let p = rela.r_offset.wrapping_add(load_base);
+        // Use two's complement arithmethic for the addend.
+        let a = rela.r_addend as u64;
+        let value = match rela.get_type() {
+            Self::R_AARCH64_ABS64 | Self::R_AARCH64_GLOB_DAT | Self::R_AARCH64_JUMP_SLOT => {
+                sym_value.wrapping_add(a)
+            }
+            Self::R_AARCH64_RELATIVE => load_base.wrapping_add(a),
+            _ => return Err(ElfError::UnrecognizedRelocationType),
+        };
+
+        Ok(Elf64RelocOp {
+            dst: p,
+            value: value.to_le_bytes(),
+            value_len: 8,
+        })
+    }
+}
+
+/// Relocation processor for the target architecture of an ELF file
+#[derive(Clone, Copy, Debug)]
+pub enum Elf64MachineRelocProcessor {
+    /// Processor for x86_64 ELF files
+    X86(Elf64X86RelocProcessor),
+    /// Processor for AArch64 ELF files
+    Aarch64(Elf64Aarch64RelocProcessor),
+}
+
+impl Elf64MachineRelocProcessor {
+    /// Creates the relocation processor matching an ELF file's machine type.
+    ///
+    /// # Arguments
+    ///
+    /// - `machine`: The ELF file's target architecture.
+    pub fn new(machine: Elf64Machine) -> Self {
+        match machine {
+            Elf64Machine::X86_64 => Self::X86(Elf64X86RelocProcessor::new()),
+            Elf64Machine::Aarch64 => Self::Aarch64(Elf64Aarch64RelocProcessor::new()),
+        }
+    }
+}
+
+impl Elf64RelocProcessor for Elf64MachineRelocProcessor {
+    fn apply_relocation(
+        &self,
+        rela: &Elf64Rela,
+        load_base: Elf64Xword,
+        sym_value: Elf64Addr,
+    ) -> Result<Elf64RelocOp, ElfError> {
+        match self {
+            Self::X86(rela_proc) => rela_proc.apply_relocation(rela, load_base, sym_value),
+            Self::Aarch64(rela_proc) => rela_proc.apply_relocation(rela, load_base, sym_value),
+        }
+    }
+}
+
+/// A set of relocation types ([`Elf64RelocTypeSet`])
+///
+/// Types below [`Self::MAX_TYPE`] are tracked individually, any larger ones
+/// are only noted as being present.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Elf64RelocTypeSet {
+    /// Bitmap of the contained types below [`Self::MAX_TYPE`]
+    types: u64,
+    /// Whether any type at or above [`Self::MAX_TYPE`] is contained
+    other: bool,
+}
+
+impl Elf64RelocTypeSet {
+    /// Upper bound of the relocation types tracked individually
+    pub const MAX_TYPE: Elf64Word = 64;
+
+    /// Adds a relocation type to the set.
+    fn insert(&mut self, r_type: Elf64Word) {
+        if r_type < Self::MAX_TYPE {
+            self.types |= 1u64 << r_type;
+        } else {
+            self.other = true;
+        }
+    }
+
+    /// Checks whether a relocation type below [`Self::MAX_TYPE`] is contained
+    /// in the set.
+    pub fn contains(&self, r_type: Elf64Word) -> bool {
+        r_type < Self::MAX_TYPE && self.types & (1u64 << r_type) != 0
+    }
+
+    /// Checks whether any relocation type at or above [`Self::MAX_TYPE`] is
+    /// contained in the set.
+    pub fn contains_other(&self) -> bool {
+        self.other
+    }
+
+    /// Checks whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.types == 0 && !self.other
+    }
+
+    /// Returns an iterator over the contained types below [`Self::MAX_TYPE`],
+    /// in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = Elf64Word> + '_ {
+        (0..Self::MAX_TYPE).filter(|r_type| self.contains(*r_type))
+    }
+}
+
+/// Relocation types seen by an [`Elf64RecordingRelocProcessor`]
+#[derive(Debug, Default)]
+pub struct Elf64RelocRecord {
+    /// All relocation types encountered
+    encountered: Cell<Elf64RelocTypeSet>,
+    /// The relocation types the wrapped processor could not handle
+    unhandled: Cell<Elf64RelocTypeSet>,
+    /// The first relocation type the wrapped processor could not handle
+    first_unhandled: Cell<Option<Elf64Word>>,
+}
+
+impl Elf64RelocRecord {
+    /// Returns the set of relocation types encountered.
+    pub fn encountered(&self) -> Elf64RelocTypeSet {
+        self.encountered.get()
+    }
+
+    /// Returns the set of relocation types that could not be handled.
+    pub fn unhandled(&self) -> Elf64RelocTypeSet {
+        self.unhandled.get()
+    }
+
+    /// Returns the first relocation type that could not be handled, if any.
+    pub fn first_unhandled(&self) -> Option<Elf64Word> {
+        self.first_unhandled.get()
+    }
+}
+
+/// Relocation processor wrapper recording the relocation types encountered.
+///
+/// All relocations are passed on to the wrapped processor. The types seen,
+/// as well as those the wrapped processor failed on with
+/// [`ElfError::UnrecognizedRelocationType`], are recorded in an
+/// [`Elf64RelocRecord`], which remains accessible to the caller once the
+/// relocations have been applied.
+#[derive(Debug)]
+pub struct Elf64RecordingRelocProcessor<'r, RP: Elf64RelocProcessor> {
+    /// The wrapped relocation processor
+    rela_proc: RP,
+    /// Where to record the relocation types
+    record: &'r Elf64RelocRecord,
+}
+
+impl<'r, RP: Elf64RelocProcessor> Elf64RecordingRelocProcessor<'r, RP> {
+    /// Creates a new [`Elf64RecordingRelocProcessor`] instance.
+    ///
+    /// # Arguments
+    ///
+    /// - `rela_proc`: The relocation processor to wrap.
+    /// - `record`: Where to record the relocation types.
+    pub fn new(rela_proc: RP, record: &'r Elf64RelocRecord) -> Self {
+        Self { rela_proc, record }
+    }
+}
+
+impl<RP: Elf64RelocProcessor> Elf64RelocProcessor for Elf64RecordingRelocProcessor<'_, RP> {
+    fn apply_relocation(
+        &self,
+        rela: &Elf64Rela,
+        load_base: Elf64Xword,
+        sym_value: Elf64Addr,
+    ) -> Result<Elf64RelocOp, ElfError> {
+        let r_type = rela.get_type();
+        let mut encountered = self.record.encountered.get();
+        encountered.insert(r_type);
+        self.record.encountered.set(encountered);
+
+        let result = self.rela_proc.apply_relocation(rela, load_base, sym_value);
+        if let Err(ElfError::UnrecognizedRelocationType) = result {
+            let mut unhandled = self.record.unhandled.get();
+            unhandled.insert(r_type);
+            self.record.unhandled.set(unhandled);
+            if self.record.first_unhandled.get().is_none() {
+                self.record.first_unhandled.set(Some(r_type));
+            }
+        }
+        result
+    }
+}
+
 /// An iterator that applies relocation operations to ELF64 relocations
 #[derive(Debug)]
 pub struct Elf64AppliedRelaIterator<'a, RP: Elf64RelocProcessor> {
@@ -281,6 +576,39 @@ impl<'a, RP: Elf64RelocProcessor> Elf64AppliedRelaIterator<'a, RP> {
             next: 0,
         }
     }
+
+    /// Applies all remaining relocations and records each resulting
+    /// operation as a `(destination, value)` pair in `buf`, without
+    /// requiring an allocator.
+    ///
+    /// Relocations of type zero produce no operation and are not recorded.
+    /// The recorded values are zero-extended to 64 bits.
+    ///
+    /// # Arguments
+    ///
+    /// - `buf`: The buffer to store the applied relocations in.
+    ///
+    /// # Returns
+    ///
+    /// - [`Result<usize, ElfError>`]: A [`Result`] containing the number of
+    ///   entries written to `buf`, or an [`ElfError`] if a relocation fails or
+    ///   `buf` cannot hold all of them.
+    pub fn write_applied(self, buf: &mut [(Elf64Addr, Elf64Xword)]) -> Result<usize, ElfError> {
+        let mut count = 0;
+        for reloc_op in self {
+            let Some(reloc_op) = reloc_op? else {
+                continue;
+            };
+            let entry = buf
+                .get_mut(count)
+                .ok_or(ElfError::RelocationBufferTooSmall)?;
+            let mut value = [0u8; 8];
+            value[..reloc_op.value_len].copy_from_slice(&reloc_op.value[..reloc_op.value_len]);
+            *entry = (reloc_op.dst, Elf64Xword::from_le_bytes(value));
+            count += 1;
+        }
+        Ok(count)
+    }
 }
 
 impl<RP: Elf64RelocProcessor> Iterator for Elf64AppliedRelaIterator<'_, RP> {
@@ -66,12 +66,21 @@ impl Elf64Shdr {
     /// Represents a null section type
     pub const SHT_NULL: Elf64Word = 0;
 
+    /// Represents a symbol table section type
+    pub const SHT_SYMTAB: Elf64Word = 2;
+
     /// Represents a string table section type
     pub const SHT_STRTAB: Elf64Word = 3;
 
     /// Represents a section with no associated data in the ELF file
     pub const SHT_NOBITS: Elf64Word = 8;
 
+    /// Represents a dynamic symbol table section type
+    pub const SHT_DYNSYM: Elf64Word = 11;
+
+    /// Represents an extended section index table section type
+    pub const SHT_SYMTAB_SHNDX: Elf64Word = 18;
+
     /// Reads a section header from a byte buffer and returns an [`Elf64Shdr`] instance.
     ///
     /// # Arguments
@@ -7,7 +7,7 @@
 // vim: ts=4 sw=4 et
 
 use super::types::*;
-use super::ElfError;
+use super::{Elf64Shdr, ElfError};
 
 use core::ffi;
 
@@ -95,6 +95,43 @@ impl Elf64Sym {
     }
 }
 
+/// Represents an ELF64 extended section index table ([`Elf64SymtabShndx`]),
+/// i.e. the contents of a `SHT_SYMTAB_SHNDX` section.
+///
+/// It holds one entry per symbol of the associated symbol table. An entry
+/// provides the symbol's section index if the symbol's `st_shndx` is
+/// `SHN_XINDEX`, and is zero otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct Elf64SymtabShndx<'a> {
+    /// The underlying buffer containing the section indices
+    shndx_buf: &'a [u8],
+}
+
+impl<'a> Elf64SymtabShndx<'a> {
+    /// Creates a new [`Elf64SymtabShndx`] instance from the provided buffer.
+    pub fn new(shndx_buf: &'a [u8]) -> Self {
+        Self { shndx_buf }
+    }
+
+    /// Retrieves the extended section index of the symbol at index `i`.
+    ///
+    /// # Returns
+    ///
+    /// - [`Result<Elf64Word, ElfError>`]: A [`Result`] containing the section index,
+    ///   or an [`ElfError`] if the table has no entry for `i`.
+    pub fn get(&self, i: Elf64Word) -> Result<Elf64Word, ElfError> {
+        let off = usize::try_from(i)
+            .ok()
+            .and_then(|i| i.checked_mul(4))
+            .ok_or(ElfError::InvalidSectionIndex)?;
+        let entry = self
+            .shndx_buf
+            .get(off..off + 4)
+            .ok_or(ElfError::InvalidSectionIndex)?;
+        Ok(Elf64Word::from_le_bytes(entry.try_into().unwrap()))
+    }
+}
+
 /// Represents an ELF64 symbol table ([`Elf64Symtab`]) containing
 /// symbols used within the ELF file.
 #[derive(Debug)]
@@ -105,6 +142,8 @@ pub struct Elf64Symtab<'a> {
     entsize: usize,
     /// Number of symbols in the symbol table
     syms_num: Elf64Word,
+    /// The associated extended section index table, if any
+    shndx: Option<Elf64SymtabShndx<'a>>,
 }
 
 impl<'a> Elf64Symtab<'a> {
@@ -133,9 +172,24 @@ impl<'a> Elf64Symtab<'a> {
             syms_buf,
             entsize,
             syms_num,
+            shndx: None,
         })
     }
 
+    /// Associates the extended section index table `shndx` with this symbol
+    /// table, for resolving symbols whose `st_shndx` is `SHN_XINDEX`.
+    pub fn with_shndx(self, shndx: Elf64SymtabShndx<'a>) -> Self {
+        Self {
+            shndx: Some(shndx),
+            ..self
+        }
+    }
+
+    /// Returns the number of symbols in the symbol table.
+    pub fn syms_num(&self) -> Elf64Word {
+        self.syms_num
+    }
+
     /// Reads a symbol from the symbol table by its index.
     ///
     /// # Arguments
@@ -147,7 +201,7 @@ impl<'a> Elf64Symtab<'a> {
     /// - [`Result<Elf64Sym, ElfError>`]: A [`Result`] containing the [`Elf64Sym`] if found,
     ///   or an [`ElfError`] if the index is out of bounds or the symbol is invalid.
     pub fn read_sym(&self, i: Elf64Word) -> Result<Elf64Sym, ElfError> {
-        if i > self.syms_num {
+        if i >= self.syms_num {
             return Err(ElfError::InvalidSymbolIndex);
         }
         let i = usize::try_from(i).map_err(|_| ElfError::InvalidSymbolIndex)?;
@@ -155,4 +209,187 @@ impl<'a> Elf64Symtab<'a> {
         let sym_buf = &self.syms_buf[sym_off..(sym_off + self.entsize)];
         Ok(Elf64Sym::read(sym_buf))
     }
+
+    /// Retrieves the index of the section a symbol is associated with.
+    ///
+    /// Unlike the symbol's `st_shndx`, this resolves `SHN_XINDEX` through the
+    /// extended section index table.
+    ///
+    /// # Arguments
+    ///
+    /// - `i`: The index of the symbol.
+    ///
+    /// # Returns
+    ///
+    /// - [`Result<Elf64Word, ElfError>`]: A [`Result`] containing the section index,
+    ///   or an [`ElfError`] if the symbol index is out of bounds or the symbol uses
+    ///   `SHN_XINDEX` without an extended section index table entry.
+    pub fn sym_shndx(&self, i: Elf64Word) -> Result<Elf64Word, ElfError> {
+        let sym = self.read_sym(i)?;
+        let shndx = Elf64Word::from(sym.st_shndx);
+        if shndx != Elf64Shdr::SHN_XINDEX {
+            return Ok(shndx);
+        }
+        self.shndx
+            .as_ref()
+            .ok_or(ElfError::InvalidSectionIndex)?
+            .get(i)
+    }
+}
+
+/// Represents an ELF64 GNU hash table ([`Elf64GnuHash`]) as referenced from
+/// `DT_GNU_HASH`, used for looking up dynamic symbols by name.
+#[derive(Debug)]
+pub struct Elf64GnuHash<'a> {
+    /// Number of hash buckets
+    nbuckets: Elf64Word,
+    /// Index of the first symbol covered by the hash table
+    symoffset: Elf64Word,
+    /// Shift count for the bloom filter's second hash bit
+    bloom_shift: Elf64Word,
+    /// The bloom filter words
+    bloom_buf: &'a [u8],
+    /// The hash buckets
+    buckets_buf: &'a [u8],
+    /// The hash chains, running up to the end of the containing segment
+    chain_buf: &'a [u8],
+}
+
+impl<'a> Elf64GnuHash<'a> {
+    /// Creates a new [`Elf64GnuHash`] instance from the provided hash table buffer.
+    ///
+    /// # Arguments
+    ///
+    /// - `buf`: The buffer containing the hash table data. The size of the
+    ///   chain array is not recorded anywhere, so the buffer may extend
+    ///   beyond the end of the table.
+    ///
+    /// # Returns
+    ///
+    /// - [`Result<Self, ElfError>`]: A [`Result`] containing the [`Elf64GnuHash`] instance if
+    ///   valid, or an [`ElfError`] if the table header is malformed or truncated.
+    pub fn new(buf: &'a [u8]) -> Result<Self, ElfError> {
+        if buf.len() < 16 {
+            return Err(ElfError::InvalidGnuHashTable);
+        }
+        let nbuckets = Elf64Word::from_le_bytes(buf[0..4].try_into().unwrap());
+        let symoffset = Elf64Word::from_le_bytes(buf[4..8].try_into().unwrap());
+        let bloom_size = Elf64Word::from_le_bytes(buf[8..12].try_into().unwrap());
+        let bloom_shift = Elf64Word::from_le_bytes(buf[12..16].try_into().unwrap());
+        if nbuckets == 0 || bloom_size == 0 {
+            return Err(ElfError::InvalidGnuHashTable);
+        }
+
+        let bloom_len = usize::try_from(bloom_size)
+            .ok()
+            .and_then(|n| n.checked_mul(8))
+            .ok_or(ElfError::InvalidGnuHashTable)?;
+        let buckets_len = usize::try_from(nbuckets)
+            .ok()
+            .and_then(|n| n.checked_mul(4))
+            .ok_or(ElfError::InvalidGnuHashTable)?;
+        let (bloom_buf, rest) = buf[16..]
+            .split_at_checked(bloom_len)
+            .ok_or(ElfError::InvalidGnuHashTable)?;
+        let (buckets_buf, chain_buf) = rest
+            .split_at_checked(buckets_len)
+            .ok_or(ElfError::InvalidGnuHashTable)?;
+
+        Ok(Self {
+            nbuckets,
+            symoffset,
+            bloom_shift,
+            bloom_buf,
+            buckets_buf,
+            chain_buf,
+        })
+    }
+
+    /// Computes the GNU hash of a symbol name.
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: The symbol name, without the terminating NUL.
+    ///
+    /// # Returns
+    ///
+    /// - [`Elf64Word`]: The hash value.
+    pub fn hash(name: &[u8]) -> Elf64Word {
+        name.iter().fold(5381, |h: Elf64Word, c| {
+            h.wrapping_mul(33).wrapping_add(Elf64Word::from(*c))
+        })
+    }
+
+    fn read_word(buf: &[u8], i: usize) -> Option<Elf64Word> {
+        let off = i.checked_mul(4)?;
+        let word_buf = buf.get(off..off.checked_add(4)?)?;
+        Some(Elf64Word::from_le_bytes(word_buf.try_into().unwrap()))
+    }
+
+    /// Looks up a symbol by name.
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: The name of the symbol to look up.
+    /// - `symtab`: The dynamic symbol table the hash table refers to.
+    /// - `strtab`: The string table the symbols' names refer to.
+    ///
+    /// # Returns
+    ///
+    /// - [`Result<Option<Elf64Sym>, ElfError>`]: A [`Result`] containing the matching
+    ///   [`Elf64Sym`], [`None`] if there is no such symbol, or an [`ElfError`] if the
+    ///   hash table references data out of bounds.
+    pub fn lookup(
+        &self,
+        name: &str,
+        symtab: &Elf64Symtab<'_>,
+        strtab: &Elf64Strtab<'_>,
+    ) -> Result<Option<Elf64Sym>, ElfError> {
+        let name = name.as_bytes();
+        let h = Self::hash(name);
+
+        // Check the bloom filter first: if either of the two bits derived
+        // from the hash is clear, the symbol is definitely not present.
+        let bloom_words = self.bloom_buf.len() / 8;
+        let bloom_index = usize::try_from(h / 64).unwrap() % bloom_words;
+        let bloom_off = bloom_index * 8;
+        let bloom_word =
+            u64::from_le_bytes(self.bloom_buf[bloom_off..bloom_off + 8].try_into().unwrap());
+        let mask = (1u64 << (h % 64)) | (1u64 << (h.wrapping_shr(self.bloom_shift) % 64));
+        if bloom_word & mask != mask {
+            return Ok(None);
+        }
+
+        let bucket = usize::try_from(h % self.nbuckets).unwrap();
+        let mut sym_index = Self::read_word(self.buckets_buf, bucket).unwrap();
+        if sym_index == Elf64Symtab::STN_UNDEF {
+            return Ok(None);
+        }
+        if sym_index < self.symoffset {
+            return Err(ElfError::InvalidGnuHashTable);
+        }
+
+        loop {
+            let chain_index = usize::try_from(sym_index - self.symoffset).unwrap();
+            let chain_hash = Self::read_word(self.chain_buf, chain_index)
+                .ok_or(ElfError::InvalidGnuHashTable)?;
+            // The lowest bit of each chain entry marks the end of the chain
+            // and is not part of the hash.
+            if (chain_hash | 1) == (h | 1) {
+                if sym_index >= symtab.syms_num() {
+                    return Err(ElfError::InvalidSymbolIndex);
+                }
+                let sym = symtab.read_sym(sym_index)?;
+                if strtab.get_str(sym.st_name)?.to_bytes() == name {
+                    return Ok(Some(sym));
+                }
+            }
+            if chain_hash & 1 != 0 {
+                return Ok(None);
+            }
+            sym_index = sym_index
+                .checked_add(1)
+                .ok_or(ElfError::InvalidGnuHashTable)?;
+        }
+    }
 }
@@ -8,6 +8,80 @@
 
 use super::*;
 
+extern crate alloc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A program header to be emitted by [`build_elf()`].
+#[derive(Clone, Copy, Debug)]
+struct TestPhdr {
+    p_type: Elf64Word,
+    p_flags: Elf64Word,
+    p_offset: Elf64Off,
+    p_vaddr: Elf64Addr,
+    p_filesz: Elf64Xword,
+    p_memsz: Elf64Xword,
+}
+
+/// Builds a minimal x86-64 executable consisting of an ELF header, the
+/// given program headers and `data` placed at `data_offset`.  No section
+/// headers are emitted.
+fn build_elf(phdrs: &[TestPhdr], data_offset: usize, data: &[u8]) -> Vec<u8> {
+    build_elf_for_machine(62, phdrs, data_offset, data)
+}
+
+/// Like [`build_elf()`], but for the given `e_machine`.
+fn build_elf_for_machine(
+    e_machine: Elf64Half,
+    phdrs: &[TestPhdr],
+    data_offset: usize,
+    data: &[u8],
+) -> Vec<u8> {
+    let phnum = Elf64Half::try_from(phdrs.len()).unwrap();
+    let mut buf = vec![0u8; 64];
+    buf[..8].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    buf[16..18].copy_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    buf[18..20].copy_from_slice(&e_machine.to_le_bytes());
+    buf[20..24].copy_from_slice(&1u32.to_le_bytes()); // EV_CURRENT
+    buf[32..40].copy_from_slice(&64u64.to_le_bytes()); // e_phoff
+    buf[52..54].copy_from_slice(&64u16.to_le_bytes()); // e_ehsize
+    buf[54..56].copy_from_slice(&56u16.to_le_bytes()); // e_phentsize
+    buf[56..58].copy_from_slice(&phnum.to_le_bytes());
+    buf[58..60].copy_from_slice(&64u16.to_le_bytes()); // e_shentsize
+
+    for phdr in phdrs {
+        buf.extend_from_slice(&phdr.p_type.to_le_bytes());
+        buf.extend_from_slice(&phdr.p_flags.to_le_bytes());
+        buf.extend_from_slice(&phdr.p_offset.to_le_bytes());
+        buf.extend_from_slice(&phdr.p_vaddr.to_le_bytes());
+        buf.extend_from_slice(&phdr.p_vaddr.to_le_bytes());
+        buf.extend_from_slice(&phdr.p_filesz.to_le_bytes());
+        buf.extend_from_slice(&phdr.p_memsz.to_le_bytes());
+        buf.extend_from_slice(&0u64.to_le_bytes()); // p_align
+    }
+
+    assert!(data_offset >= buf.len());
+    buf.resize(data_offset, 0);
+    buf.extend_from_slice(data);
+    buf
+}
+
+fn load_phdr(
+    p_offset: Elf64Off,
+    p_vaddr: Elf64Addr,
+    p_filesz: Elf64Xword,
+    p_memsz: Elf64Xword,
+) -> TestPhdr {
+    TestPhdr {
+        p_type: Elf64Phdr::PT_LOAD,
+        p_flags: (Elf64PhdrFlags::READ | Elf64PhdrFlags::WRITE).bits(),
+        p_offset,
+        p_vaddr,
+        p_filesz,
+        p_memsz,
+    }
+}
+
 #[test]
 fn test_elf64_shdr_verify_methods() {
     // Create a valid Elf64Shdr instance for testing.
@@ -195,3 +269,582 @@ fn test_elf64_load_segments() {
     assert_eq!(total_range.vaddr_begin, 0x1000);
     assert_eq!(total_range.vaddr_end, 0x4000);
 }
+
+#[test]
+fn test_elf64_load_into() {
+    let phdrs = [
+        load_phdr(0x100, 0x1000, 4, 8),
+        load_phdr(0x104, 0x3000, 2, 2),
+    ];
+    let elf_buf = build_elf(&phdrs, 0x100, &[1, 2, 3, 4, 5, 6]);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    let image_len = usize::try_from(elf_file.image_load_vaddr_alloc_info().range.len()).unwrap();
+    assert_eq!(image_len, 0x2002);
+
+    let mut image = vec![0xffu8; image_len];
+    elf_file.load_into(&mut image, 0x1000).unwrap();
+
+    // The BSS tail of the first segment and the gap up to the second
+    // segment must be zeroed.
+    assert_eq!(image[..4], [1, 2, 3, 4]);
+    assert!(image[4..0x2000].iter().all(|b| *b == 0));
+    assert_eq!(image[0x2000..], [5, 6]);
+
+    let mut short = vec![0u8; image_len - 1];
+    assert_eq!(
+        elf_file.load_into(&mut short, 0x1000),
+        Err(ElfError::InvalidAddressRange)
+    );
+}
+
+#[test]
+fn test_elf64_gnu_hash() {
+    assert_eq!(Elf64GnuHash::hash(b""), 5381);
+    assert_eq!(Elf64GnuHash::hash(b"printf"), 0x156b2bb8);
+}
+
+/// Builds an executable with a dynamic symbol table defining `foo` and
+/// `bar`, optionally with a GNU hash table covering both.
+fn build_dynsym_elf(with_gnu_hash: bool) -> Vec<u8> {
+    const DT_NULL: u64 = 0;
+    const DT_STRTAB: u64 = 5;
+    const DT_SYMTAB: u64 = 6;
+    const DT_STRSZ: u64 = 10;
+    const DT_SYMENT: u64 = 11;
+    const DT_GNU_HASH: u64 = 0x6ffffef5;
+
+    // Everything is placed in a single segment at file offset 0x100 and
+    // vaddr 0x1000, laid out as .dynamic, .dynsym, .dynstr, .gnu.hash.
+    let mut data = vec![0u8; 0x300];
+
+    let mut dynamic: Vec<(u64, u64)> = vec![
+        (DT_SYMTAB, 0x1100),
+        (DT_SYMENT, 24),
+        (DT_STRTAB, 0x1200),
+        (DT_STRSZ, 9),
+    ];
+    if with_gnu_hash {
+        dynamic.push((DT_GNU_HASH, 0x1280));
+    }
+    dynamic.push((DT_NULL, 0));
+    for (i, (d_tag, d_val)) in dynamic.iter().enumerate() {
+        data[i * 16..i * 16 + 8].copy_from_slice(&d_tag.to_le_bytes());
+        data[i * 16 + 8..i * 16 + 16].copy_from_slice(&d_val.to_le_bytes());
+    }
+    let dynamic_size = dynamic.len() * 16;
+
+    let syms: [(Elf64Word, Elf64Addr); 3] = [(0, 0), (1, 0x1111), (5, 0x2222)];
+    for (i, (st_name, st_value)) in syms.iter().enumerate() {
+        let off = 0x100 + i * 24;
+        data[off..off + 4].copy_from_slice(&st_name.to_le_bytes());
+        if i != 0 {
+            data[off + 6..off + 8].copy_from_slice(&1u16.to_le_bytes());
+        }
+        data[off + 8..off + 16].copy_from_slice(&st_value.to_le_bytes());
+    }
+
+    data[0x200..0x209].copy_from_slice(b"\0foo\0bar\0");
+
+    if with_gnu_hash {
+        let bloom_shift = 6;
+        let hashes = [Elf64GnuHash::hash(b"foo"), Elf64GnuHash::hash(b"bar")];
+        let bloom = hashes.iter().fold(0u64, |bloom, h| {
+            bloom | (1u64 << (h % 64)) | (1u64 << ((h >> bloom_shift) % 64))
+        });
+        let words: [Elf64Word; 4] = [1, 1, 1, bloom_shift];
+        for (i, word) in words.iter().enumerate() {
+            data[0x280 + i * 4..0x284 + i * 4].copy_from_slice(&word.to_le_bytes());
+        }
+        data[0x290..0x298].copy_from_slice(&bloom.to_le_bytes());
+        // Single bucket, starting at symbol 1.
+        data[0x298..0x29c].copy_from_slice(&1u32.to_le_bytes());
+        data[0x29c..0x2a0].copy_from_slice(&(hashes[0] & !1).to_le_bytes());
+        data[0x2a0..0x2a4].copy_from_slice(&(hashes[1] | 1).to_le_bytes());
+    }
+
+    let phdrs = [
+        load_phdr(0x100, 0x1000, 0x300, 0x300),
+        TestPhdr {
+            p_type: Elf64Phdr::PT_DYNAMIC,
+            p_flags: (Elf64PhdrFlags::READ | Elf64PhdrFlags::WRITE).bits(),
+            p_offset: 0x100,
+            p_vaddr: 0x1000,
+            p_filesz: Elf64Xword::try_from(dynamic_size).unwrap(),
+            p_memsz: Elf64Xword::try_from(dynamic_size).unwrap(),
+        },
+    ];
+    build_elf(&phdrs, 0x100, &data)
+}
+
+#[test]
+fn test_elf64_gnu_hash_lookup() {
+    let elf_buf = build_dynsym_elf(true);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert!(elf_file.dynamic.as_ref().unwrap().gnu_hash.is_some());
+
+    assert_eq!(elf_file.gnu_hash_lookup("foo").unwrap().st_value, 0x1111);
+    assert_eq!(elf_file.gnu_hash_lookup("bar").unwrap().st_value, 0x2222);
+    assert!(elf_file.gnu_hash_lookup("baz").is_none());
+    assert!(elf_file.gnu_hash_lookup("").is_none());
+}
+
+#[test]
+fn test_elf64_gnu_hash_lookup_linear_fallback() {
+    let elf_buf = build_dynsym_elf(false);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert!(elf_file.dynamic.as_ref().unwrap().gnu_hash.is_none());
+
+    assert_eq!(elf_file.gnu_hash_lookup("foo").unwrap().st_value, 0x1111);
+    assert_eq!(elf_file.gnu_hash_lookup("bar").unwrap().st_value, 0x2222);
+    assert!(elf_file.gnu_hash_lookup("baz").is_none());
+}
+
+#[test]
+fn test_elf64_symtab_xindex() {
+    // Three symbols: the null symbol, one in section 5 and one whose section
+    // index does not fit into st_shndx.
+    let mut syms_buf = vec![0u8; 3 * 24];
+    syms_buf[24 + 6..24 + 8].copy_from_slice(&5u16.to_le_bytes());
+    syms_buf[48 + 6..48 + 8].copy_from_slice(&0xffffu16.to_le_bytes());
+
+    let symtab = Elf64Symtab::new(&syms_buf, 24).unwrap();
+    assert_eq!(symtab.sym_shndx(1), Ok(5));
+    assert_eq!(symtab.sym_shndx(2), Err(ElfError::InvalidSectionIndex));
+    assert_eq!(symtab.sym_shndx(3), Err(ElfError::InvalidSymbolIndex));
+
+    let mut shndx_buf = vec![0u8; 3 * 4];
+    shndx_buf[8..12].copy_from_slice(&0x12345u32.to_le_bytes());
+    let symtab = symtab.with_shndx(Elf64SymtabShndx::new(&shndx_buf));
+    assert_eq!(symtab.sym_shndx(1), Ok(5));
+    assert_eq!(symtab.sym_shndx(2), Ok(0x12345));
+
+    // A truncated extended section index table is rejected.
+    let symtab = Elf64Symtab::new(&syms_buf, 24)
+        .unwrap()
+        .with_shndx(Elf64SymtabShndx::new(&shndx_buf[..8]));
+    assert_eq!(symtab.sym_shndx(2), Err(ElfError::InvalidSectionIndex));
+}
+
+fn interp_phdr(p_offset: Elf64Off, p_filesz: Elf64Xword) -> TestPhdr {
+    TestPhdr {
+        p_type: Elf64Phdr::PT_INTERP,
+        p_flags: Elf64PhdrFlags::READ.bits(),
+        p_offset,
+        p_vaddr: 0x1000 + p_offset,
+        p_filesz,
+        p_memsz: p_filesz,
+    }
+}
+
+#[test]
+fn test_elf64_interpreter() {
+    let interp = b"/lib64/ld-linux-x86-64.so.2\0";
+    let interp_len = Elf64Xword::try_from(interp.len()).unwrap();
+    let phdrs = [interp_phdr(0x100, interp_len)];
+    let elf_buf = build_elf(&phdrs, 0x100, interp);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert_eq!(
+        elf_file.interpreter().unwrap().unwrap().to_bytes(),
+        b"/lib64/ld-linux-x86-64.so.2"
+    );
+}
+
+#[test]
+fn test_elf64_interpreter_static() {
+    let phdrs = [load_phdr(0x100, 0x1000, 4, 4)];
+    let elf_buf = build_elf(&phdrs, 0x100, &[1, 2, 3, 4]);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert_eq!(elf_file.interpreter(), Ok(None));
+}
+
+#[test]
+fn test_elf64_interpreter_unterminated() {
+    // The segment ends right before the terminating NUL.
+    let interp = b"/lib/ld.so\0";
+    let interp_len = Elf64Xword::try_from(interp.len()).unwrap();
+    let phdrs = [interp_phdr(0x100, interp_len - 1)];
+    let elf_buf = build_elf(&phdrs, 0x100, interp);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert_eq!(elf_file.interpreter(), Err(ElfError::InvalidInterpString));
+
+    let phdrs = [interp_phdr(0x100, 0)];
+    let elf_buf = build_elf(&phdrs, 0x100, interp);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert_eq!(elf_file.interpreter(), Err(ElfError::InvalidInterpString));
+}
+
+#[test]
+fn test_elf64_stack_flags() {
+    let phdrs = [load_phdr(0x100, 0x1000, 4, 4)];
+    let elf_buf = build_elf(&phdrs, 0x100, &[1, 2, 3, 4]);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert_eq!(
+        elf_file.stack_flags(),
+        Elf64PhdrFlags::READ | Elf64PhdrFlags::WRITE
+    );
+
+    let gnu_stack = TestPhdr {
+        p_type: Elf64Phdr::PT_GNU_STACK,
+        p_flags: (Elf64PhdrFlags::READ | Elf64PhdrFlags::WRITE | Elf64PhdrFlags::EXECUTE).bits(),
+        p_offset: 0,
+        p_vaddr: 0,
+        p_filesz: 0,
+        p_memsz: 0,
+    };
+    let phdrs = [load_phdr(0x100, 0x1000, 4, 4), gnu_stack];
+    let elf_buf = build_elf(&phdrs, 0x100, &[1, 2, 3, 4]);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert!(elf_file.stack_flags().contains(Elf64PhdrFlags::EXECUTE));
+}
+
+#[test]
+fn test_elf64_total_mem_image_size() {
+    let phdrs = [
+        load_phdr(0x100, 0x1000, 4, 8),
+        load_phdr(0x104, 0x3000, 2, 2),
+    ];
+    let elf_buf = build_elf(&phdrs, 0x100, &[1, 2, 3, 4, 5, 6]);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert_eq!(elf_file.total_mem_image_size(), Ok(0x3000));
+
+    // A partial last page is rounded up.
+    let phdrs = [load_phdr(0x100, 0xffff_ffff_ffff_f000, 4, 0xfff)];
+    let elf_buf = build_elf(&phdrs, 0x100, &[1, 2, 3, 4]);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert_eq!(elf_file.total_mem_image_size(), Ok(0x1000));
+
+    // Rounding up a size spanning almost the whole address space overflows.
+    let phdrs = [load_phdr(0x100, 0, 4, 0xffff_ffff_ffff_f001)];
+    let elf_buf = build_elf(&phdrs, 0x100, &[1, 2, 3, 4]);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert_eq!(
+        elf_file.total_mem_image_size(),
+        Err(ElfError::InvalidAddressRange)
+    );
+}
+
+#[test]
+fn test_elf64_validate() {
+    let phdrs = [load_phdr(0x100, 0x1000, 4, 4)];
+    let mut elf_buf = build_elf(&phdrs, 0x100, &[1, 2, 3, 4]);
+    assert_eq!(Elf64File::validate(&elf_buf), Ok(()));
+
+    // The buffer is not borrowed past validation and may be modified.
+    elf_buf[0] = 0;
+    assert_eq!(
+        Elf64File::validate(&elf_buf),
+        Err(ElfError::UnrecognizedMagic)
+    );
+
+    let phdrs = [load_phdr(0x100, 0x1000, 4, 4)];
+    let elf_buf = build_elf(&phdrs, 0x100, &[1, 2]);
+    assert_eq!(Elf64File::validate(&elf_buf), Err(ElfError::FileTooShort));
+}
+
+#[test]
+fn test_elf64_x86_reloc_type_name() {
+    assert_eq!(
+        Elf64X86RelocProcessor::type_name(8),
+        Some("R_X86_64_RELATIVE")
+    );
+    assert_eq!(
+        Elf64X86RelocProcessor::type_name(18),
+        Some("R_X86_64_TPOFF64")
+    );
+    assert_eq!(Elf64X86RelocProcessor::type_name(0x1000), None);
+}
+
+#[test]
+fn test_elf64_recording_reloc_processor() {
+    const DT_NULL: u64 = 0;
+    const DT_RELA: u64 = 7;
+    const DT_RELASZ: u64 = 8;
+    const DT_RELAENT: u64 = 9;
+    const R_X86_64_RELATIVE: u64 = 8;
+    const R_X86_64_TPOFF64: u64 = 18;
+
+    // .dynamic at vaddr 0x1000, the relocations at 0x1100 and their
+    // targets at 0x1200.
+    let mut data = vec![0u8; 0x300];
+    let dynamic: [(u64, u64); 4] = [
+        (DT_RELA, 0x1100),
+        (DT_RELASZ, 3 * 24),
+        (DT_RELAENT, 24),
+        (DT_NULL, 0),
+    ];
+    for (i, (d_tag, d_val)) in dynamic.iter().enumerate() {
+        data[i * 16..i * 16 + 8].copy_from_slice(&d_tag.to_le_bytes());
+        data[i * 16 + 8..i * 16 + 16].copy_from_slice(&d_val.to_le_bytes());
+    }
+    let relas: [(u64, u64); 3] = [
+        (0x1200, R_X86_64_RELATIVE),
+        (0x1208, R_X86_64_TPOFF64),
+        (0x1210, 0x100),
+    ];
+    for (i, (r_offset, r_info)) in relas.iter().enumerate() {
+        let off = 0x100 + i * 24;
+        data[off..off + 8].copy_from_slice(&r_offset.to_le_bytes());
+        data[off + 8..off + 16].copy_from_slice(&r_info.to_le_bytes());
+    }
+
+    let phdrs = [
+        load_phdr(0x100, 0x1000, 0x300, 0x300),
+        TestPhdr {
+            p_type: Elf64Phdr::PT_DYNAMIC,
+            p_flags: (Elf64PhdrFlags::READ | Elf64PhdrFlags::WRITE).bits(),
+            p_offset: 0x100,
+            p_vaddr: 0x1000,
+            p_filesz: 4 * 16,
+            p_memsz: 4 * 16,
+        },
+    ];
+    let elf_buf = build_elf(&phdrs, 0x100, &data);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+
+    let record = Elf64RelocRecord::default();
+    let rela_proc = Elf64RecordingRelocProcessor::new(Elf64X86RelocProcessor::new(), &record);
+    let results: Vec<_> = elf_file
+        .apply_dyn_relas(rela_proc, 0x1000)
+        .unwrap()
+        .unwrap()
+        .collect();
+    assert!(matches!(results[0], Ok(Some(_))));
+    assert!(matches!(
+        results[1],
+        Err(ElfError::UnrecognizedRelocationType)
+    ));
+    assert!(matches!(
+        results[2],
+        Err(ElfError::UnrecognizedRelocationType)
+    ));
+
+    let encountered = record.encountered();
+    assert!(encountered.contains(8));
+    assert!(encountered.contains(18));
+    assert!(encountered.contains_other());
+    assert_eq!(record.unhandled().iter().collect::<Vec<_>>(), [18]);
+    assert!(record.unhandled().contains_other());
+    assert_eq!(record.first_unhandled(), Some(18));
+}
+
+#[test]
+fn test_elf64_write_applied_relas() {
+    const DT_NULL: u64 = 0;
+    const DT_RELA: u64 = 7;
+    const DT_RELASZ: u64 = 8;
+    const DT_RELAENT: u64 = 9;
+    const R_X86_64_RELATIVE: u64 = 8;
+
+    // .dynamic at vaddr 0x1000, the relocations at 0x1100 and their
+    // targets at 0x1200.
+    let mut data = vec![0u8; 0x300];
+    let dynamic: [(u64, u64); 4] = [
+        (DT_RELA, 0x1100),
+        (DT_RELASZ, 3 * 24),
+        (DT_RELAENT, 24),
+        (DT_NULL, 0),
+    ];
+    for (i, (d_tag, d_val)) in dynamic.iter().enumerate() {
+        data[i * 16..i * 16 + 8].copy_from_slice(&d_tag.to_le_bytes());
+        data[i * 16 + 8..i * 16 + 16].copy_from_slice(&d_val.to_le_bytes());
+    }
+    // Two relative relocations with a no-op relocation in between.
+    let relas: [(u64, u64, u64); 3] = [
+        (0x1200, R_X86_64_RELATIVE, 0x10),
+        (0, 0, 0),
+        (0x1208, R_X86_64_RELATIVE, 0x20),
+    ];
+    for (i, (r_offset, r_info, r_addend)) in relas.iter().enumerate() {
+        let off = 0x100 + i * 24;
+        data[off..off + 8].copy_from_slice(&r_offset.to_le_bytes());
+        data[off + 8..off + 16].copy_from_slice(&r_info.to_le_bytes());
+        data[off + 16..off + 24].copy_from_slice(&r_addend.to_le_bytes());
+    }
+
+    let phdrs = [
+        load_phdr(0x100, 0x1000, 0x300, 0x300),
+        TestPhdr {
+            p_type: Elf64Phdr::PT_DYNAMIC,
+            p_flags: (Elf64PhdrFlags::READ | Elf64PhdrFlags::WRITE).bits(),
+            p_offset: 0x100,
+            p_vaddr: 0x1000,
+            p_filesz: 4 * 16,
+            p_memsz: 4 * 16,
+        },
+    ];
+    let elf_buf = build_elf(&phdrs, 0x100, &data);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+
+    let mut buf = [(0, 0); 3];
+    let count = elf_file
+        .apply_dyn_relas(Elf64X86RelocProcessor::new(), 0x5000)
+        .unwrap()
+        .unwrap()
+        .write_applied(&mut buf)
+        .unwrap();
+    assert_eq!(count, 2);
+    assert_eq!(buf[..count], [(0x5200, 0x4010), (0x5208, 0x4020)]);
+
+    let mut buf = [(0, 0); 1];
+    let result = elf_file
+        .apply_dyn_relas(Elf64X86RelocProcessor::new(), 0x5000)
+        .unwrap()
+        .unwrap()
+        .write_applied(&mut buf);
+    assert_eq!(result, Err(ElfError::RelocationBufferTooSmall));
+}
+
+fn tls_phdr(
+    p_offset: Elf64Off,
+    p_vaddr: Elf64Addr,
+    p_filesz: Elf64Xword,
+    p_memsz: Elf64Xword,
+) -> TestPhdr {
+    TestPhdr {
+        p_type: Elf64Phdr::PT_TLS,
+        p_flags: Elf64PhdrFlags::READ.bits(),
+        p_offset,
+        p_vaddr,
+        p_filesz,
+        p_memsz,
+    }
+}
+
+#[test]
+fn test_elf64_tls_template() {
+    let data = [1, 2, 3, 4, 5, 6, 7, 8];
+    let phdrs = [
+        load_phdr(0x100, 0x1000, 8, 8),
+        tls_phdr(0x104, 0x1004, 4, 0x10),
+    ];
+    let elf_buf = build_elf(&phdrs, 0x100, &data);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    let tls = elf_file.tls_template().unwrap().unwrap();
+    assert_eq!(tls.init_data, [5, 6, 7, 8]);
+    assert_eq!(tls.mem_size, 0x10);
+
+    let phdrs = [load_phdr(0x100, 0x1000, 8, 8)];
+    let elf_buf = build_elf(&phdrs, 0x100, &data);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert!(elf_file.tls_template().unwrap().is_none());
+}
+
+#[test]
+fn test_elf64_tls_template_malformed() {
+    let data = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    // ->p_filesz exceeding ->p_memsz is rejected.
+    let phdrs = [
+        load_phdr(0x100, 0x1000, 8, 8),
+        tls_phdr(0x104, 0x1004, 4, 2),
+    ];
+    let elf_buf = build_elf(&phdrs, 0x100, &data);
+    assert_eq!(
+        Elf64File::read(&elf_buf).err(),
+        Some(ElfError::InvalidSegmentSize)
+    );
+
+    // The initialization image must be backed by a PT_LOAD segment.
+    let phdrs = [
+        load_phdr(0x100, 0x1000, 8, 8),
+        tls_phdr(0x104, 0x2004, 4, 4),
+    ];
+    let elf_buf = build_elf(&phdrs, 0x100, &data);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert!(matches!(
+        elf_file.tls_template(),
+        Err(ElfError::UnmappedVaddrRange)
+    ));
+}
+
+#[test]
+fn test_elf64_aarch64_relocs() {
+    const DT_NULL: u64 = 0;
+    const DT_SYMTAB: u64 = 6;
+    const DT_RELA: u64 = 7;
+    const DT_RELASZ: u64 = 8;
+    const DT_RELAENT: u64 = 9;
+    const DT_SYMENT: u64 = 11;
+    const R_AARCH64_ABS64: u64 = 257;
+    const R_AARCH64_GLOB_DAT: u64 = 1025;
+    const R_AARCH64_JUMP_SLOT: u64 = 1026;
+    const R_AARCH64_RELATIVE: u64 = 1027;
+
+    // .dynamic at vaddr 0x1000, the relocations at 0x1100, the symbol
+    // table at 0x1200 and the relocation targets at 0x1280.
+    let mut data = vec![0u8; 0x300];
+    let dynamic: [(u64, u64); 6] = [
+        (DT_RELA, 0x1100),
+        (DT_RELASZ, 4 * 24),
+        (DT_RELAENT, 24),
+        (DT_SYMTAB, 0x1200),
+        (DT_SYMENT, 24),
+        (DT_NULL, 0),
+    ];
+    for (i, (d_tag, d_val)) in dynamic.iter().enumerate() {
+        data[i * 16..i * 16 + 8].copy_from_slice(&d_tag.to_le_bytes());
+        data[i * 16 + 8..i * 16 + 16].copy_from_slice(&d_val.to_le_bytes());
+    }
+    let relas: [(u64, u64, i64); 4] = [
+        (0x1280, R_AARCH64_RELATIVE, 0x10),
+        (0x1288, (1 << 32) | R_AARCH64_GLOB_DAT, 0),
+        (0x1290, (1 << 32) | R_AARCH64_JUMP_SLOT, 0),
+        (0x1298, (1 << 32) | R_AARCH64_ABS64, 4),
+    ];
+    for (i, (r_offset, r_info, r_addend)) in relas.iter().enumerate() {
+        let off = 0x100 + i * 24;
+        data[off..off + 8].copy_from_slice(&r_offset.to_le_bytes());
+        data[off + 8..off + 16].copy_from_slice(&r_info.to_le_bytes());
+        data[off + 16..off + 24].copy_from_slice(&r_addend.to_le_bytes());
+    }
+    // Symbol 1 is defined in section 1 at 0x1234.
+    data[0x218 + 6..0x218 + 8].copy_from_slice(&1u16.to_le_bytes());
+    data[0x218 + 8..0x218 + 16].copy_from_slice(&0x1234u64.to_le_bytes());
+
+    let phdrs = [
+        load_phdr(0x100, 0x1000, 0x300, 0x300),
+        TestPhdr {
+            p_type: Elf64Phdr::PT_DYNAMIC,
+            p_flags: (Elf64PhdrFlags::READ | Elf64PhdrFlags::WRITE).bits(),
+            p_offset: 0x100,
+            p_vaddr: 0x1000,
+            p_filesz: 6 * 16,
+            p_memsz: 6 * 16,
+        },
+    ];
+    let elf_buf = build_elf_for_machine(183, &phdrs, 0x100, &data);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert_eq!(elf_file.machine(), Elf64Machine::Aarch64);
+    let rela_proc = elf_file.reloc_processor();
+    assert!(matches!(rela_proc, Elf64MachineRelocProcessor::Aarch64(_)));
+
+    // Load at 0x10000, i.e. with a load base of 0xf000.
+    let ops: Vec<_> = elf_file
+        .apply_dyn_relas(rela_proc, 0x10000)
+        .unwrap()
+        .unwrap()
+        .map(|op| op.unwrap().unwrap())
+        .collect();
+    let expected: [(Elf64Addr, u64); 4] = [
+        (0x10280, 0xf010),
+        (0x10288, 0x10234),
+        (0x10290, 0x10234),
+        (0x10298, 0x10238),
+    ];
+    assert_eq!(ops.len(), expected.len());
+    for (op, (dst, value)) in ops.iter().zip(expected) {
+        assert_eq!(op.dst, dst);
+        assert_eq!(op.value_len, 8);
+        assert_eq!(op.value, value.to_le_bytes());
+    }
+
+    // The x86_64 processor doesn't know about any of these.
+    let elf_buf = build_elf(&phdrs, 0x100, &data);
+    let elf_file = Elf64File::read(&elf_buf).unwrap();
+    assert_eq!(elf_file.machine(), Elf64Machine::X86_64);
+    assert!(elf_file
+        .apply_dyn_relas(elf_file.reloc_processor(), 0x10000)
+        .unwrap()
+        .unwrap()
+        .all(|op| matches!(op, Err(ElfError::UnrecognizedRelocationType))));
+}
@@ -4,6 +4,8 @@
 //
 // Author: Roy Hopkins <roy.hopkins@suse.com>
 
+use std::num::ParseIntError;
+
 use clap::{Parser, ValueEnum};
 
 #[derive(Parser, Debug)]
@@ -24,6 +26,10 @@ pub struct CmdOptions {
     #[arg(long)]
     pub filesystem: Option<String>,
 
+    /// Optional ACPI SRAT binary describing the guest NUMA topology
+    #[arg(long)]
+    pub srat: Option<String>,
+
     /// Optional firmware file, e.g. OVMF.fd
     #[arg(short, long)]
     pub firmware: Option<String>,
@@ -48,6 +54,15 @@ pub struct CmdOptions {
     #[arg(long, default_value_t = false)]
     pub sort: bool,
 
+    /// Print a summary of all IGVM directives, sorted by GPA, and report
+    /// overlapping page data
+    #[arg(long, default_value_t = false)]
+    pub dump_directives: bool,
+
+    /// Print the expected SEV-SNP launch measurement of the generated file
+    #[arg(long, default_value_t = false)]
+    pub print_measurement: bool,
+
     /// A hex value containing the guest policy to apply. For example: 0x30000
     #[arg(long)]
     pub policy: Option<String>,
@@ -75,6 +90,33 @@ pub struct CmdOptions {
     /// Use Alternate Injection if available
     #[arg(long, default_value_t = false)]
     pub alt_injection: bool,
+
+    /// Override the guest physical base address of the kernel memory region.
+    /// For example: 0x8000000000
+    #[arg(long, value_parser = parse_u64)]
+    pub kernel_base: Option<u64>,
+
+    /// Override the size in bytes of the kernel memory region. For example:
+    /// 0x2000000
+    #[arg(long, value_parser = parse_u64)]
+    pub kernel_max_size: Option<u64>,
+
+    /// Override the guest physical address of the initial VMSA page. For
+    /// example: 0xFFFFFFFFF000
+    #[arg(long, value_parser = parse_u64)]
+    pub vmsa_gpa: Option<u64>,
+
+    /// The number of vCPUs for which a measured SNP VMSA is generated
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub cpu_count: u32,
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal integer argument.
+fn parse_u64(arg: &str) -> Result<u64, ParseIntError> {
+    match arg.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => arg.parse(),
+    }
 }
 
 impl CmdOptions {
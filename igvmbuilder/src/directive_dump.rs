@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) Microsoft Corporation
+//
+// Author: agent <agent@local>
+
+use igvm::IgvmDirectiveHeader;
+use igvm_defs::PAGE_SIZE_4K;
+
+/// A human-readable summary of a single IGVM directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DirectiveSummary {
+    gpa: Option<u64>,
+    compatibility_mask: Option<u32>,
+    kind: &'static str,
+    size: u64,
+}
+
+impl DirectiveSummary {
+    fn new(directive: &IgvmDirectiveHeader) -> Self {
+        let (gpa, compatibility_mask, size) = match directive {
+            IgvmDirectiveHeader::PageData {
+                gpa,
+                compatibility_mask,
+                ..
+            } => (Some(*gpa), Some(*compatibility_mask), PAGE_SIZE_4K),
+            IgvmDirectiveHeader::SnpVpContext {
+                gpa,
+                compatibility_mask,
+                ..
+            } => (Some(*gpa), Some(*compatibility_mask), PAGE_SIZE_4K),
+            IgvmDirectiveHeader::RequiredMemory {
+                gpa,
+                compatibility_mask,
+                number_of_bytes,
+                ..
+            } => (
+                Some(*gpa),
+                Some(*compatibility_mask),
+                *number_of_bytes as u64,
+            ),
+            IgvmDirectiveHeader::ParameterArea {
+                number_of_bytes, ..
+            } => (None, None, *number_of_bytes),
+            IgvmDirectiveHeader::ParameterInsert(insert) => {
+                (Some(insert.gpa), Some(insert.compatibility_mask), 0)
+            }
+            IgvmDirectiveHeader::X64NativeVpContext {
+                compatibility_mask, ..
+            } => (None, Some(*compatibility_mask), 0),
+            _ => (None, None, 0),
+        };
+        Self {
+            gpa,
+            compatibility_mask,
+            kind: directive_kind(directive),
+            size,
+        }
+    }
+
+    fn is_page_data(&self) -> bool {
+        self.kind == "PageData"
+    }
+}
+
+/// Returns the name of the directive variant.
+fn directive_kind(directive: &IgvmDirectiveHeader) -> &'static str {
+    match directive {
+        IgvmDirectiveHeader::PageData { .. } => "PageData",
+        IgvmDirectiveHeader::ParameterArea { .. } => "ParameterArea",
+        IgvmDirectiveHeader::VpCount(_) => "VpCount",
+        IgvmDirectiveHeader::EnvironmentInfo(_) => "EnvironmentInfo",
+        IgvmDirectiveHeader::Srat(_) => "Srat",
+        IgvmDirectiveHeader::Madt(_) => "Madt",
+        IgvmDirectiveHeader::MemoryMap(_) => "MemoryMap",
+        IgvmDirectiveHeader::CommandLine(_) => "CommandLine",
+        IgvmDirectiveHeader::RequiredMemory { .. } => "RequiredMemory",
+        IgvmDirectiveHeader::SnpVpContext { .. } => "SnpVpContext",
+        IgvmDirectiveHeader::X64NativeVpContext { .. } => "X64NativeVpContext",
+        IgvmDirectiveHeader::ParameterInsert(_) => "ParameterInsert",
+        IgvmDirectiveHeader::SnpIdBlock { .. } => "SnpIdBlock",
+        _ => "Other",
+    }
+}
+
+/// Returns the index pairs of page data directives that populate the same
+/// page for at least one common platform.  The summaries must be sorted by
+/// GPA.
+fn find_page_overlaps(summaries: &[DirectiveSummary]) -> Vec<(usize, usize)> {
+    let mut overlaps = Vec::new();
+    for (i, a) in summaries.iter().enumerate() {
+        if !a.is_page_data() {
+            continue;
+        }
+        for (j, b) in summaries.iter().enumerate().skip(i + 1) {
+            if b.gpa != a.gpa {
+                break;
+            }
+            let common_mask = a.compatibility_mask.unwrap_or(0) & b.compatibility_mask.unwrap_or(0);
+            if b.is_page_data() && common_mask != 0 {
+                overlaps.push((i, j));
+            }
+        }
+    }
+    overlaps
+}
+
+/// Prints a summary of each directive, sorted by GPA, followed by a list of
+/// any page data directives which populate the same page more than once.
+pub fn dump_directives(directives: &[IgvmDirectiveHeader]) {
+    let mut summaries: Vec<DirectiveSummary> =
+        directives.iter().map(DirectiveSummary::new).collect();
+    // Directives without a GPA are listed first.
+    summaries.sort_by_key(|summary| summary.gpa);
+
+    println!("IGVM directives ({}):", summaries.len());
+    for summary in &summaries {
+        let gpa = summary
+            .gpa
+            .map_or(String::from("-"), |gpa| format!("{gpa:#018x}"));
+        let mask = summary
+            .compatibility_mask
+            .map_or(String::from("-"), |mask| format!("{mask:#x}"));
+        println!(
+            "  {gpa:>18} mask {mask:>4} {:<20} size {:#x}",
+            summary.kind, summary.size
+        );
+    }
+
+    for (i, j) in find_page_overlaps(&summaries) {
+        println!(
+            "WARNING: overlapping page data at {:#018x} (masks {:#x} and {:#x})",
+            summaries[i].gpa.unwrap(),
+            summaries[i].compatibility_mask.unwrap(),
+            summaries[j].compatibility_mask.unwrap()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use igvm_defs::{IgvmPageDataFlags, IgvmPageDataType};
+
+    fn page(gpa: u64, compatibility_mask: u32) -> IgvmDirectiveHeader {
+        IgvmDirectiveHeader::PageData {
+            gpa,
+            compatibility_mask,
+            flags: IgvmPageDataFlags::new(),
+            data_type: IgvmPageDataType::NORMAL,
+            data: vec![],
+        }
+    }
+
+    #[test]
+    fn test_find_page_overlaps() {
+        let directives = [
+            page(0x1000, 1),
+            page(0x1000, 2),
+            page(0x2000, 3),
+            page(0x2000, 1),
+        ];
+        let summaries: Vec<DirectiveSummary> =
+            directives.iter().map(DirectiveSummary::new).collect();
+        assert_eq!(summaries[0].kind, "PageData");
+        assert_eq!(summaries[0].size, PAGE_SIZE_4K);
+
+        // Pages at the same GPA for disjoint platforms do not overlap.
+        assert_eq!(find_page_overlaps(&summaries), vec![(2, 3)]);
+    }
+}
@@ -7,12 +7,17 @@
 use std::error::Error;
 use std::fs::metadata;
 
+use bootlib::igvm_params::IgvmParamBlock;
 use igvm_defs::PAGE_SIZE_4K;
 
 use crate::cmd_options::{CmdOptions, Hypervisor};
 use crate::firmware::Firmware;
 use crate::igvm_builder::{COMPATIBILITY_MASK, TDP_COMPATIBILITY_MASK};
 
+/// The number of pages reserved for the IGVM memory map supplied by the
+/// host loader.
+const IGVM_MEMORY_MAP_PAGES: u64 = 4;
+
 #[derive(Debug, Copy, Clone)]
 pub struct GpaRange {
     start: u64,
@@ -47,6 +52,16 @@ impl GpaRange {
     pub fn get_size(&self) -> u64 {
         self.size
     }
+
+    /// Returns true if the two ranges share at least one page.
+    fn overlaps(&self, other: &GpaRange) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    /// Returns true if `other` lies entirely within this range.
+    fn contains(&self, other: &GpaRange) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
 }
 
 #[derive(Debug)]
@@ -64,8 +79,9 @@ pub struct GpaMap {
     pub general_params: GpaRange,
     pub memory_map: GpaRange,
     pub guest_context: GpaRange,
+    pub srat: GpaRange,
     pub kernel: GpaRange,
-    pub vmsa: GpaRange,
+    pub vmsa: Vec<GpaRange>,
 }
 
 impl GpaMap {
@@ -106,6 +122,11 @@ impl GpaMap {
         } else {
             0
         };
+        let srat_len = if let Some(srat) = &options.srat {
+            Self::get_metadata(srat)?.len()
+        } else {
+            0
+        };
 
         let stage2_image = GpaRange::new(0x10000, stage2_len as u64)?;
 
@@ -140,23 +161,40 @@ impl GpaMap {
         let kernel_fs = GpaRange::new(kernel_elf.get_end(), kernel_fs_len as u64)?;
 
         // Calculate the kernel size and base.
-        let kernel = match options.hypervisor {
+        let (default_kernel_base, default_kernel_size) = match options.hypervisor {
             Hypervisor::Qemu => {
                 // Place the kernel area at 512 GB with a size of 16 MB.
-                GpaRange::new(0x0000008000000000, 0x01000000)?
+                (0x0000008000000000, 0x01000000)
             }
             Hypervisor::HyperV => {
                 // Place the kernel area at 64 MB with a size of 16 MB.
-                GpaRange::new(0x04000000, 0x01000000)?
+                (0x04000000, 0x01000000)
             }
         };
+        let kernel_size = options.kernel_max_size.unwrap_or(default_kernel_size);
+        if (kernel_size & (PAGE_SIZE_4K - 1)) != 0 || kernel_size == 0 {
+            return Err("Kernel size must be a non-zero multiple of the page size".into());
+        }
+        if kernel_size > u32::MAX as u64 {
+            return Err("Kernel size must be less than 4 GB".into());
+        }
+        let kernel = GpaRange::new(
+            options.kernel_base.unwrap_or(default_kernel_base),
+            kernel_size,
+        )?;
+        if firmware_range.get_size() != 0 && kernel.overlaps(&firmware_range) {
+            return Err("Kernel range overlaps the firmware range".into());
+        }
 
         let igvm_param_block = GpaRange::new_page(kernel_fs.get_end())?;
         let general_params = GpaRange::new_page(igvm_param_block.get_end())?;
-        let memory_map = GpaRange::new_page(general_params.get_end())?;
+        let memory_map = GpaRange::new(
+            general_params.get_end(),
+            IGVM_MEMORY_MAP_PAGES * PAGE_SIZE_4K,
+        )?;
         let guest_context = if let Some(firmware) = firmware {
             if firmware.get_guest_context().is_some() {
-                // Locate the guest context after the memory map parameter page
+                // Locate the guest context after the memory map parameter pages
                 GpaRange::new_page(memory_map.get_end())?
             } else {
                 GpaRange::new(0, 0)?
@@ -164,14 +202,39 @@ impl GpaMap {
         } else {
             GpaRange::new(0, 0)?
         };
+        let srat = if srat_len != 0 {
+            // Locate the SRAT after the last parameter page
+            let srat_start = if guest_context.get_size() != 0 {
+                guest_context.get_end()
+            } else {
+                memory_map.get_end()
+            };
+            GpaRange::new(srat_start, srat_len)?
+        } else {
+            GpaRange::new(0, 0)?
+        };
 
-        let vmsa = match options.hypervisor {
-            Hypervisor::Qemu => {
+        let vmsa_start = match (options.vmsa_gpa, options.hypervisor) {
+            (Some(vmsa_gpa), _) => {
+                GpaRange::new_page(vmsa_gpa).map_err(|_| "VMSA GPA must be page aligned")?
+            }
+            (None, Hypervisor::Qemu) => {
                 // VMSA address is currently hardcoded in kvm
                 GpaRange::new_page(0xFFFFFFFFF000)?
             }
-            Hypervisor::HyperV => GpaRange::new_page(kernel.end - PAGE_SIZE_4K)?,
+            (None, Hypervisor::HyperV) => GpaRange::new_page(kernel.end - PAGE_SIZE_4K)?,
         };
+        // The VMSAs of additional vCPUs occupy the pages immediately below
+        // the VMSA of the boot vCPU.
+        let vmsa = (0..u64::from(options.cpu_count))
+            .map(|index| {
+                let gpa = vmsa_start
+                    .get_start()
+                    .checked_sub(index * PAGE_SIZE_4K)
+                    .ok_or("VMSA range underflows guest physical address space")?;
+                GpaRange::new_page(gpa)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
         let gpa_map = Self {
             stage1_image,
@@ -187,15 +250,121 @@ impl GpaMap {
             general_params,
             memory_map,
             guest_context,
+            srat,
             kernel,
             vmsa,
         };
         if options.verbose {
             println!("GPA Map: {gpa_map:#X?}");
         }
+        gpa_map.check_overlaps()?;
         Ok(gpa_map)
     }
 
+    /// Verifies that no two ranges in the map overlap, returning an error
+    /// that names the first overlapping pair.  VMSA pages are permitted to
+    /// lie within the kernel range, which reserves space for them.
+    fn check_overlaps(&self) -> Result<(), Box<dyn Error>> {
+        let mut ranges: Vec<(String, &GpaRange)> = vec![
+            ("stage1_image".into(), &self.stage1_image),
+            ("low_memory".into(), &self.low_memory),
+            ("stage2_stack".into(), &self.stage2_stack),
+            ("stage2_image".into(), &self.stage2_image),
+            ("stage2_free".into(), &self.stage2_free),
+            ("secrets_page".into(), &self.secrets_page),
+            ("cpuid_page".into(), &self.cpuid_page),
+            ("kernel_elf".into(), &self.kernel_elf),
+            ("kernel_fs".into(), &self.kernel_fs),
+            ("igvm_param_block".into(), &self.igvm_param_block),
+            ("general_params".into(), &self.general_params),
+            ("memory_map".into(), &self.memory_map),
+            ("guest_context".into(), &self.guest_context),
+            ("srat".into(), &self.srat),
+            ("kernel".into(), &self.kernel),
+        ];
+        for (index, vmsa) in self.vmsa.iter().enumerate() {
+            if !self.kernel.contains(vmsa) {
+                ranges.push((format!("vmsa[{index}]"), vmsa));
+            }
+        }
+        ranges.retain(|(_, range)| range.get_size() != 0);
+        ranges.sort_by_key(|(_, range)| range.get_start());
+
+        // Compare each range against the preceding range which extends the
+        // furthest, so that a range nested within a large range is caught
+        // even if it does not directly follow it.
+        let mut furthest: Option<&(String, &GpaRange)> = None;
+        for entry in ranges.iter() {
+            if let Some(prev) = furthest {
+                if prev.1.overlaps(entry.1) {
+                    return Err(format!(
+                        "GPA range {} [{:#x}-{:#x}) overlaps {} [{:#x}-{:#x})",
+                        prev.0,
+                        prev.1.get_start(),
+                        prev.1.get_end(),
+                        entry.0,
+                        entry.1.get_start(),
+                        entry.1.get_end()
+                    )
+                    .into());
+                }
+                if entry.1.get_end() <= prev.1.get_end() {
+                    continue;
+                }
+            }
+            furthest = Some(entry);
+        }
+        Ok(())
+    }
+
+    /// Fills the fields of the IGVM parameter block that describe the guest
+    /// physical address layout, so that the layout seen by the kernel always
+    /// matches the layout used to build the IGVM file.
+    pub fn populate_param_block(&self, block: &mut IgvmParamBlock) {
+        let param_page_offset = PAGE_SIZE_4K as u32;
+        let memory_map_offset = param_page_offset + PAGE_SIZE_4K as u32;
+        let memory_map_size = self.memory_map.get_size() as u32;
+        let memory_map_end = memory_map_offset + memory_map_size;
+        let (guest_context_offset, guest_context_end) = if self.guest_context.get_size() == 0 {
+            (0, memory_map_end)
+        } else {
+            (
+                memory_map_end,
+                memory_map_end + self.guest_context.get_size() as u32,
+            )
+        };
+        let (srat_offset, param_area_size) = if self.srat.get_size() == 0 {
+            (0, guest_context_end)
+        } else {
+            let srat_pages = self.srat.get_end() - self.srat.get_start();
+            (guest_context_end, guest_context_end + srat_pages as u32)
+        };
+
+        block.param_area_size = param_area_size;
+        block.param_page_offset = param_page_offset;
+        block.memory_map_offset = memory_map_offset;
+        block.memory_map_size = memory_map_size;
+        block.guest_context_offset = guest_context_offset;
+        block.srat_offset = srat_offset;
+        block.srat_size = self.srat.get_size() as u32;
+        block.cpuid_page = self.cpuid_page.get_start() as u32;
+        block.secrets_page = self.secrets_page.get_start() as u32;
+        block.stage1_size = self.stage1_image.get_size() as u32;
+        block.stage1_base = self.stage1_image.get_start();
+        // Reserve the top of the kernel range down to the lowest VMSA placed
+        // within it, so the kernel does not allocate over the VMSAs of any
+        // vCPU.  At least one page is always reserved.
+        let kernel_reserved_size = self
+            .vmsa
+            .iter()
+            .filter(|vmsa| self.kernel.contains(vmsa))
+            .map(|vmsa| self.kernel.get_end() - vmsa.get_start())
+            .fold(PAGE_SIZE_4K, u64::max);
+        block.kernel_reserved_size = kernel_reserved_size as u32;
+        block.kernel_size = self.kernel.get_size() as u32;
+        block.kernel_base = self.kernel.get_start();
+    }
+
     pub fn get_metadata(path: &String) -> Result<std::fs::Metadata, Box<dyn Error>> {
         let meta = metadata(path).map_err(|e| {
             eprintln!("Failed to access {}", path);
@@ -204,3 +373,145 @@ impl GpaMap {
         Ok(meta)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_gpa_map(guest_context: GpaRange, srat: GpaRange) -> GpaMap {
+        GpaMap {
+            stage1_image: GpaRange::new(0xFFFF_0000, 0x10000).unwrap(),
+            low_memory: GpaRange::new(0, 0xf000).unwrap(),
+            stage2_stack: GpaRange::new_page(0xf000).unwrap(),
+            stage2_image: GpaRange::new(0x10000, 0x20000).unwrap(),
+            stage2_free: GpaRange::new(0x30000, 0x6e000).unwrap(),
+            secrets_page: GpaRange::new_page(0x9e000).unwrap(),
+            cpuid_page: GpaRange::new_page(0x9f000).unwrap(),
+            kernel_elf: GpaRange::new(0x100000, 0x80000).unwrap(),
+            kernel_fs: GpaRange::new(0x180000, 0).unwrap(),
+            igvm_param_block: GpaRange::new_page(0x180000).unwrap(),
+            general_params: GpaRange::new_page(0x181000).unwrap(),
+            memory_map: GpaRange::new(0x182000, 2 * PAGE_SIZE_4K).unwrap(),
+            guest_context,
+            srat,
+            kernel: GpaRange::new(0x04000000, 0x01000000).unwrap(),
+            vmsa: vec![GpaRange::new_page(0x04FFF000).unwrap()],
+        }
+    }
+
+    #[test]
+    fn test_populate_param_block() {
+        let gpa_map = test_gpa_map(GpaRange::new(0, 0).unwrap(), GpaRange::new(0, 0).unwrap());
+        let mut block = IgvmParamBlock::default();
+        gpa_map.populate_param_block(&mut block);
+
+        let (kernel_base, kernel_size) = (block.kernel_base, block.kernel_size);
+        assert_eq!(kernel_base, gpa_map.kernel.get_start());
+        assert_eq!(kernel_size as u64, gpa_map.kernel.get_size());
+        let (stage1_base, stage1_size) = (block.stage1_base, block.stage1_size);
+        assert_eq!(stage1_base, gpa_map.stage1_image.get_start());
+        assert_eq!(stage1_size as u64, gpa_map.stage1_image.get_size());
+        let (cpuid_page, secrets_page) = (block.cpuid_page, block.secrets_page);
+        assert_eq!(cpuid_page as u64, gpa_map.cpuid_page.get_start());
+        assert_eq!(secrets_page as u64, gpa_map.secrets_page.get_start());
+        let (guest_context_offset, param_area_size) =
+            (block.guest_context_offset, block.param_area_size);
+        assert_eq!(guest_context_offset, 0);
+        assert_eq!(param_area_size as u64, 4 * PAGE_SIZE_4K);
+        let (memory_map_offset, memory_map_size) = (block.memory_map_offset, block.memory_map_size);
+        assert_eq!(memory_map_offset as u64, 2 * PAGE_SIZE_4K);
+        assert_eq!(memory_map_size as u64, 2 * PAGE_SIZE_4K);
+    }
+
+    #[test]
+    fn test_populate_param_block_guest_context() {
+        let gpa_map = test_gpa_map(
+            GpaRange::new_page(0x184000).unwrap(),
+            GpaRange::new(0, 0).unwrap(),
+        );
+        let mut block = IgvmParamBlock::default();
+        gpa_map.populate_param_block(&mut block);
+
+        let (guest_context_offset, param_area_size) =
+            (block.guest_context_offset, block.param_area_size);
+        assert_eq!(guest_context_offset as u64, 4 * PAGE_SIZE_4K);
+        assert_eq!(param_area_size as u64, 5 * PAGE_SIZE_4K);
+    }
+
+    #[test]
+    fn test_populate_param_block_srat() {
+        let gpa_map = test_gpa_map(
+            GpaRange::new(0, 0).unwrap(),
+            GpaRange::new(0x184000, 0x1800).unwrap(),
+        );
+        let mut block = IgvmParamBlock::default();
+        gpa_map.populate_param_block(&mut block);
+
+        let (srat_offset, srat_size, param_area_size) =
+            (block.srat_offset, block.srat_size, block.param_area_size);
+        assert_eq!(srat_offset as u64, 4 * PAGE_SIZE_4K);
+        assert_eq!(srat_size, 0x1800);
+        assert_eq!(param_area_size as u64, 6 * PAGE_SIZE_4K);
+    }
+
+    #[test]
+    fn test_populate_param_block_multiple_vmsas() {
+        let none = GpaRange::new(0, 0).unwrap();
+        let mut gpa_map = test_gpa_map(none, none);
+        let mut block = IgvmParamBlock::default();
+        gpa_map.populate_param_block(&mut block);
+        let kernel_reserved_size = block.kernel_reserved_size;
+        assert_eq!(kernel_reserved_size as u64, PAGE_SIZE_4K);
+
+        // Every VMSA within the kernel range must be reserved.
+        gpa_map.vmsa = (0..4)
+            .map(|index| GpaRange::new_page(0x04FFF000 - index * PAGE_SIZE_4K).unwrap())
+            .collect();
+        assert!(gpa_map.check_overlaps().is_ok());
+        gpa_map.populate_param_block(&mut block);
+        let kernel_reserved_size = block.kernel_reserved_size;
+        assert_eq!(kernel_reserved_size as u64, 4 * PAGE_SIZE_4K);
+
+        // VMSAs outside the kernel range need no reservation.
+        gpa_map.vmsa = (0..4)
+            .map(|index| GpaRange::new_page(0xFFFFFFFFF000 - index * PAGE_SIZE_4K).unwrap())
+            .collect();
+        gpa_map.populate_param_block(&mut block);
+        let kernel_reserved_size = block.kernel_reserved_size;
+        assert_eq!(kernel_reserved_size as u64, PAGE_SIZE_4K);
+    }
+
+    #[test]
+    fn test_gpa_range_overlaps() {
+        let kernel = GpaRange::new(0x04000000, 0x01000000).unwrap();
+        let below = GpaRange::new(0x03000000, 0x01000000).unwrap();
+        let inside = GpaRange::new(0x04FFF000, 0x1000).unwrap();
+        let partial = GpaRange::new(0x03FFF000, 0x2000).unwrap();
+        assert!(!kernel.overlaps(&below));
+        assert!(!below.overlaps(&kernel));
+        assert!(kernel.overlaps(&inside));
+        assert!(kernel.overlaps(&partial));
+        assert!(partial.overlaps(&kernel));
+    }
+
+    #[test]
+    fn test_check_overlaps() {
+        let none = GpaRange::new(0, 0).unwrap();
+        let mut gpa_map = test_gpa_map(none, none);
+        assert!(gpa_map.check_overlaps().is_ok());
+
+        // A VMSA outside the kernel range must not overlap other ranges.
+        gpa_map.vmsa = vec![GpaRange::new_page(0x181000).unwrap()];
+        let err = gpa_map.check_overlaps().unwrap_err().to_string();
+        assert!(err.contains("general_params"));
+        assert!(err.contains("vmsa[0]"));
+
+        // A range nested within a larger one is detected even when other
+        // ranges start in between.
+        let mut gpa_map = test_gpa_map(none, none);
+        gpa_map.cpuid_page = GpaRange::new_page(0x50000).unwrap();
+        let err = gpa_map.check_overlaps().unwrap_err().to_string();
+        assert!(err.contains("stage2_free"));
+        assert!(err.contains("cpuid_page"));
+    }
+}
@@ -20,10 +20,12 @@ use igvm_defs::{
     IgvmPageDataFlags, IgvmPageDataType, IgvmPlatformType, IGVM_VHS_PARAMETER,
     IGVM_VHS_PARAMETER_INSERT, IGVM_VHS_SUPPORTED_PLATFORM, PAGE_SIZE_4K,
 };
+use igvmmeasure::igvm_measure::IgvmMeasure;
 use zerocopy::AsBytes;
 
 use crate::cmd_options::{CmdOptions, Hypervisor};
 use crate::cpuid::SnpCpuidPage;
+use crate::directive_dump::dump_directives;
 use crate::firmware::{parse_firmware, Firmware};
 use crate::platform::PlatformMask;
 use crate::stage2_stack::Stage2Stack;
@@ -139,6 +141,10 @@ impl IgvmBuilder {
             println!("{param_block:#X?}");
         }
 
+        if self.options.dump_directives {
+            dump_directives(&self.directives);
+        }
+
         let igvm_revision = if self.use_igvm_v2 {
             IgvmRevision::V2 {
                 arch: Arch::X64,
@@ -159,6 +165,10 @@ impl IgvmBuilder {
             e
         })?;
 
+        if self.options.print_measurement {
+            Self::print_measurement(&file)?;
+        }
+
         let mut binary_file = Vec::new();
         file.serialize(&mut binary_file)?;
 
@@ -173,21 +183,30 @@ impl IgvmBuilder {
         Ok(())
     }
 
-    fn create_param_block(&self) -> Result<IgvmParamBlock, Box<dyn Error>> {
-        let param_page_offset = PAGE_SIZE_4K as u32;
-        let memory_map_offset = param_page_offset + PAGE_SIZE_4K as u32;
-        let (guest_context_offset, param_area_size) = if self.gpa_map.guest_context.get_size() == 0
-        {
-            (0, memory_map_offset + PAGE_SIZE_4K as u32)
-        } else {
-            (
-                memory_map_offset + PAGE_SIZE_4K as u32,
-                memory_map_offset
-                    + PAGE_SIZE_4K as u32
-                    + self.gpa_map.guest_context.get_size() as u32,
-            )
-        };
+    fn print_measurement(file: &IgvmFile) -> Result<(), Box<dyn Error>> {
+        if !COMPATIBILITY_MASK.contains(SNP_COMPATIBILITY_MASK) {
+            return Err("A launch measurement requires the SEV-SNP platform target".into());
+        }
+        // The launch digest is computed in directive order, as the SNP
+        // firmware would compute it when the file is loaded.
+        let measure = IgvmMeasure::measure(
+            false,
+            false,
+            false,
+            SNP_COMPATIBILITY_MASK,
+            IgvmPlatformType::SEV_SNP,
+            file,
+        )?;
+        let digest: String = measure
+            .digest()
+            .iter()
+            .map(|val| format!("{val:02X}"))
+            .collect();
+        println!("SEV-SNP launch measurement: {digest}");
+        Ok(())
+    }
 
+    fn create_param_block(&self) -> Result<IgvmParamBlock, Box<dyn Error>> {
         // Populate the firmware metadata.
         let (fw_info, vtom) = if let Some(firmware) = &self.firmware {
             (firmware.get_fw_info(), firmware.get_vtom())
@@ -205,24 +224,17 @@ impl IgvmBuilder {
         };
 
         // Most of the parameter block can be initialised with constants.
-        Ok(IgvmParamBlock {
-            param_area_size,
-            param_page_offset,
-            memory_map_offset,
-            guest_context_offset,
-            cpuid_page: self.gpa_map.cpuid_page.get_start() as u32,
-            secrets_page: self.gpa_map.secrets_page.get_start() as u32,
+        let mut param_block = IgvmParamBlock {
             debug_serial_port: self.options.get_port_address(),
             firmware: fw_info,
-            stage1_size: self.gpa_map.stage1_image.get_size() as u32,
-            stage1_base: self.gpa_map.stage1_image.get_start(),
-            kernel_reserved_size: PAGE_SIZE_4K as u32, // Reserved for VMSA
-            kernel_size: self.gpa_map.kernel.get_size() as u32,
-            kernel_base: self.gpa_map.kernel.get_start(),
             vtom,
             use_alternate_injection: u8::from(self.options.alt_injection),
             ..Default::default()
-        })
+        };
+
+        // The memory layout is described by the GPA map.
+        self.gpa_map.populate_param_block(&mut param_block);
+        Ok(param_block)
     }
 
     fn build_platforms(&mut self, param_block: &IgvmParamBlock) {
@@ -297,7 +309,7 @@ impl IgvmBuilder {
 
         // Create the two parameter areas for memory map and general parameters.
         self.directives.push(IgvmDirectiveHeader::ParameterArea {
-            number_of_bytes: PAGE_SIZE_4K,
+            number_of_bytes: self.gpa_map.memory_map.get_size(),
             parameter_area_index: IGVM_MEMORY_MAP_PA,
             initial_data: vec![],
         });
@@ -340,14 +352,17 @@ impl IgvmBuilder {
         let start_context = construct_start_context();
 
         if COMPATIBILITY_MASK.contains(SNP_COMPATIBILITY_MASK) {
-            // Add the VMSA.
-            self.directives.push(construct_vmsa(
-                start_context.as_ref(),
-                self.gpa_map.vmsa.get_start(),
-                param_block.vtom,
-                SNP_COMPATIBILITY_MASK,
-                &self.options.sev_features,
-            ));
+            // Add a VMSA for each vCPU.
+            for (vp_index, vmsa) in self.gpa_map.vmsa.iter().enumerate() {
+                self.directives.push(construct_vmsa(
+                    start_context.as_ref(),
+                    vmsa.get_start(),
+                    vp_index.try_into()?,
+                    param_block.vtom,
+                    SNP_COMPATIBILITY_MASK,
+                    &self.options.sev_features,
+                ));
+            }
         }
 
         if COMPATIBILITY_MASK.contains(NATIVE_COMPATIBILITY_MASK) {
@@ -363,6 +378,15 @@ impl IgvmBuilder {
         // Add the IGVM parameter block
         self.add_param_block(param_block);
 
+        // Add optional SRAT
+        if let Some(srat) = &self.options.srat {
+            self.add_data_pages_from_file(
+                &srat.clone(),
+                self.gpa_map.srat.get_start(),
+                COMPATIBILITY_MASK.get(),
+            )?;
+        }
+
         // Add optional filesystem image
         if let Some(fs) = &self.options.filesystem {
             self.add_data_pages_from_file(
@@ -10,6 +10,7 @@ use std::error::Error;
 
 mod cmd_options;
 mod cpuid;
+mod directive_dump;
 mod firmware;
 mod gpa_map;
 mod igvm_builder;
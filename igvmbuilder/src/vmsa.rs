@@ -48,6 +48,7 @@ fn vmsa_convert_attributes(attributes: u16) -> u16 {
 pub fn construct_vmsa(
     context: &IgvmNativeVpContextX64,
     gpa_start: u64,
+    vp_index: u16,
     vtom: u64,
     compatibility_mask: u32,
     extra_features: &Vec<SevExtraFeatures>,
@@ -137,7 +138,7 @@ pub fn construct_vmsa(
     IgvmDirectiveHeader::SnpVpContext {
         gpa: gpa_start,
         compatibility_mask,
-        vp_index: 0,
+        vp_index,
         vmsa: vmsa_box,
     }
 }
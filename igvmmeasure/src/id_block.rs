@@ -17,8 +17,8 @@ use p384::elliptic_curve::bigint::ArrayEncoding;
 use p384::{EncodedPoint, SecretKey};
 use zerocopy::{AsBytes, FromZeroes};
 
-use crate::igvm_measure::IgvmMeasure;
-use crate::utils::{get_compatibility_mask, get_policy};
+use igvmmeasure::igvm_measure::IgvmMeasure;
+use igvmmeasure::utils::{get_compatibility_mask, get_policy};
 
 #[repr(C, packed)]
 #[derive(AsBytes, Clone, Copy, Debug)]
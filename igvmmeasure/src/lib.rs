@@ -0,0 +1,9 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2024 SUSE LLC
+//
+// Author: Roy Hopkins <roy.hopkins@suse.com>
+
+pub mod igvm_measure;
+mod page_info;
+pub mod utils;
@@ -12,17 +12,14 @@ use clap::Parser;
 use cmd_options::{CmdOptions, Commands, Platform};
 use igvm::IgvmFile;
 use igvm_defs::IgvmPlatformType;
-use igvm_measure::IgvmMeasure;
-use utils::get_compatibility_mask;
+use igvmmeasure::igvm_measure::IgvmMeasure;
+use igvmmeasure::utils::get_compatibility_mask;
 use zerocopy::AsBytes;
 
 use crate::id_block::SevIdBlockBuilder;
 
 mod cmd_options;
 mod id_block;
-mod igvm_measure;
-mod page_info;
-mod utils;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let options = CmdOptions::parse();
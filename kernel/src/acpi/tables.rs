@@ -6,9 +6,11 @@
 
 extern crate alloc;
 
+use crate::address::PhysAddr;
 use crate::error::SvsmError;
 use crate::fw_cfg::FwCfg;
 use crate::string::FixedString;
+use crate::utils::MemoryRegion;
 use alloc::vec::Vec;
 use core::mem;
 
@@ -189,7 +191,6 @@ impl ACPITable {
     /// Get the signature of the ACPI table.
     ///
     /// This method returns the 4-character signature of the ACPI table, such as "APIC."
-    #[allow(dead_code)]
     fn signature(&self) -> FixedString<4> {
         FixedString::from(self.header.sig)
     }
@@ -533,3 +534,224 @@ pub fn load_acpi_cpu_info(fw_cfg: &FwCfg<'_>) -> Result<Vec<ACPICPUInfo>, SvsmEr
 
     Ok(cpus)
 }
+
+/// Size of the SRAT fields between the table header and the first entry
+const SRAT_HEADER_SIZE: usize = 12;
+
+/// Entry for a processor local APIC affinity structure within SRAT
+#[derive(Clone, Copy, Debug)]
+#[allow(dead_code)]
+#[repr(C, packed)]
+struct RawSRATEntryLocalApic {
+    header: RawMADTEntryHeader,
+    proximity_domain_lo: u8,
+    apic_id: u8,
+    flags: u32,
+    local_sapic_eid: u8,
+    proximity_domain_hi: [u8; 3],
+    clock_domain: u32,
+}
+
+/// Entry for a memory affinity structure within SRAT
+#[derive(Clone, Copy, Debug)]
+#[allow(dead_code)]
+#[repr(C, packed)]
+struct RawSRATEntryMemory {
+    header: RawMADTEntryHeader,
+    proximity_domain: u32,
+    reserved1: u16,
+    base: u64,
+    length: u64,
+    reserved2: u32,
+    flags: u32,
+    reserved3: u64,
+}
+
+/// Entry for a processor local x2APIC affinity structure within SRAT
+#[derive(Clone, Copy, Debug)]
+#[allow(dead_code)]
+#[repr(C, packed)]
+struct RawSRATEntryLocalX2Apic {
+    header: RawMADTEntryHeader,
+    reserved1: u16,
+    proximity_domain: u32,
+    apic_id: u32,
+    flags: u32,
+    clock_domain: u32,
+    reserved2: u32,
+}
+
+/// A NUMA node, consisting of the memory and CPUs belonging to a single
+/// proximity domain
+#[derive(Clone, Debug, Default)]
+pub struct NumaNode {
+    /// The proximity domain of the node
+    pub proximity_domain: u32,
+    /// The memory ranges local to the node
+    pub memory: Vec<MemoryRegion<PhysAddr>>,
+    /// The APIC IDs of the CPUs local to the node
+    pub apic_ids: Vec<u32>,
+}
+
+fn numa_node_mut(nodes: &mut Vec<NumaNode>, proximity_domain: u32) -> &mut NumaNode {
+    let index = match nodes
+        .iter()
+        .position(|node| node.proximity_domain == proximity_domain)
+    {
+        Some(index) => index,
+        None => {
+            nodes.push(NumaNode {
+                proximity_domain,
+                ..Default::default()
+            });
+            nodes.len() - 1
+        }
+    };
+    &mut nodes[index]
+}
+
+/// Parses a System Resource Affinity Table (SRAT) into a list of NUMA nodes.
+///
+/// Disabled entries are ignored.  Nodes are returned in the order in which
+/// their proximity domains first appear in the table.
+///
+/// # Arguments
+///
+/// * `srat` - The raw SRAT, including the ACPI table header.
+///
+/// # Returns
+///
+/// A [`Result`] containing a vector of [`NumaNode`] structs, or an error if
+/// the table is malformed.
+pub fn parse_srat(srat: &[u8]) -> Result<Vec<NumaNode>, SvsmError> {
+    let table = ACPITable::new(srat)?;
+    if table.signature() != "SRAT" {
+        return Err(SvsmError::Acpi);
+    }
+    let content = table.content().ok_or(SvsmError::Acpi)?;
+
+    let mut nodes: Vec<NumaNode> = Vec::new();
+
+    let mut offset = SRAT_HEADER_SIZE;
+    while offset < content.len() {
+        let entry_ptr = table
+            .content_ptr::<RawMADTEntryHeader>(offset)
+            .ok_or(SvsmError::Acpi)?;
+        let (srat_type, entry_len) = unsafe { ((*entry_ptr).entry_type, (*entry_ptr).entry_len) };
+        let entry_len = usize::from(entry_len);
+
+        match srat_type {
+            0 if entry_len == mem::size_of::<RawSRATEntryLocalApic>() => {
+                let lapic = unsafe {
+                    *table
+                        .content_ptr::<RawSRATEntryLocalApic>(offset)
+                        .ok_or(SvsmError::Acpi)?
+                };
+                if (lapic.flags & 1) == 1 {
+                    let hi = lapic.proximity_domain_hi;
+                    let domain =
+                        u32::from_le_bytes([lapic.proximity_domain_lo, hi[0], hi[1], hi[2]]);
+                    numa_node_mut(&mut nodes, domain)
+                        .apic_ids
+                        .push(lapic.apic_id.into());
+                }
+            }
+            1 if entry_len == mem::size_of::<RawSRATEntryMemory>() => {
+                let memory = unsafe {
+                    *table
+                        .content_ptr::<RawSRATEntryMemory>(offset)
+                        .ok_or(SvsmError::Acpi)?
+                };
+                if (memory.flags & 1) == 1 {
+                    let base = usize::try_from(memory.base).map_err(|_| SvsmError::Acpi)?;
+                    let length = usize::try_from(memory.length).map_err(|_| SvsmError::Acpi)?;
+                    numa_node_mut(&mut nodes, memory.proximity_domain)
+                        .memory
+                        .push(MemoryRegion::new(PhysAddr::new(base), length));
+                }
+            }
+            2 if entry_len == mem::size_of::<RawSRATEntryLocalX2Apic>() => {
+                let x2apic = unsafe {
+                    *table
+                        .content_ptr::<RawSRATEntryLocalX2Apic>(offset)
+                        .ok_or(SvsmError::Acpi)?
+                };
+                if (x2apic.flags & 1) == 1 {
+                    numa_node_mut(&mut nodes, x2apic.proximity_domain)
+                        .apic_ids
+                        .push(x2apic.apic_id);
+                }
+            }
+            _ if entry_len == 0 => {
+                log::warn!(
+                    "Found zero-length SRAT entry with type {}, stopping",
+                    srat_type
+                );
+                break;
+            }
+            _ => {
+                log::info!("Ignoring SRAT entry with type {}", srat_type);
+            }
+        }
+
+        offset = offset.checked_add(entry_len).ok_or(SvsmError::Acpi)?;
+    }
+
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_entry(table: &mut Vec<u8>, entry_type: u8, body: &[u8]) {
+        table.push(entry_type);
+        table.push((body.len() + 2) as u8);
+        table.extend_from_slice(body);
+    }
+
+    #[test]
+    fn test_parse_srat() {
+        let mut srat = Vec::new();
+        srat.extend_from_slice(b"SRAT");
+        srat.resize(mem::size_of::<RawACPITableHeader>() + SRAT_HEADER_SIZE, 0);
+
+        // Local APIC 1 in domain 0, and a disabled local APIC 2.
+        let mut lapic = [0u8; 14];
+        lapic[1] = 1;
+        lapic[2] = 1;
+        push_entry(&mut srat, 0, &lapic);
+        lapic[1] = 2;
+        lapic[2] = 0;
+        push_entry(&mut srat, 0, &lapic);
+
+        // 1 GB of memory at 4 GB in domain 1.
+        let mut memory = [0u8; 38];
+        memory[0..4].copy_from_slice(&1u32.to_le_bytes());
+        memory[6..14].copy_from_slice(&0x1_0000_0000u64.to_le_bytes());
+        memory[14..22].copy_from_slice(&0x4000_0000u64.to_le_bytes());
+        memory[26] = 1;
+        push_entry(&mut srat, 1, &memory);
+
+        // x2APIC 0x100 in domain 1.
+        let mut x2apic = [0u8; 22];
+        x2apic[2..6].copy_from_slice(&1u32.to_le_bytes());
+        x2apic[6..10].copy_from_slice(&0x100u32.to_le_bytes());
+        x2apic[10] = 1;
+        push_entry(&mut srat, 2, &x2apic);
+
+        let len = srat.len() as u32;
+        srat[4..8].copy_from_slice(&len.to_le_bytes());
+
+        let nodes = parse_srat(&srat).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].proximity_domain, 0);
+        assert_eq!(nodes[0].apic_ids, [1]);
+        assert!(nodes[0].memory.is_empty());
+        assert_eq!(nodes[1].proximity_domain, 1);
+        assert_eq!(nodes[1].apic_ids, [0x100]);
+        assert_eq!(nodes[1].memory.len(), 1);
+        assert_eq!(nodes[1].memory[0].start(), PhysAddr::new(0x1_0000_0000));
+        assert_eq!(nodes[1].memory[0].len(), 0x4000_0000);
+    }
+}
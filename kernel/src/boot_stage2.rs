@@ -79,9 +79,15 @@ global_asm!(
         decl %ecx
         jnz 1b
 
-        /* Enable 64bit PTEs, CR4.PAE. */
+        /*
+         * Enable 64bit PTEs, CR4.PAE.  The static page tables and the
+         * kernel page table code are 4-level, so make sure that CR4.LA57
+         * is clear even if the loader enabled it.  It can only be changed
+         * while long mode is inactive.
+         */
         movl %cr4, %eax
         bts $5, %eax
+        btr $12, %eax
         movl %eax, %cr4
 
         /* Enable long mode, EFER.LME. */
@@ -168,7 +174,10 @@ global_asm!(
         jmp .Lcheck_entry
 
     .Lfound_entry:
-        /* Extract the c-bit location from the cpuid entry. */
+        /*
+         * Extract the c-bit location from the cpuid entry. The mask must
+         * match CPUID_C_BIT_POSITION_MASK used by c_bit_position().
+         */
         movl 28(%ecx), %ebx
         andl $0x3f, %ebx
 
@@ -98,7 +98,9 @@ impl SvsmConfig<'_> {
     pub fn write_guest_memory_map(&self, map: &[MemoryRegion<PhysAddr>]) -> Result<(), SvsmError> {
         match self {
             SvsmConfig::FirmwareConfig(_) => Ok(()),
-            SvsmConfig::IgvmConfig(igvm_params) => igvm_params.write_guest_memory_map(map),
+            SvsmConfig::IgvmConfig(igvm_params) => {
+                igvm_params.write_guest_memory_map(&igvm_params.guest_memory_map(map)?)
+            }
         }
     }
     pub fn reserved_kernel_area_size(&self) -> usize {
@@ -147,19 +149,19 @@ impl SvsmConfig<'_> {
     pub fn get_fw_regions(
         &self,
         kernel_region: &MemoryRegion<PhysAddr>,
-    ) -> Vec<MemoryRegion<PhysAddr>> {
+    ) -> Result<Vec<MemoryRegion<PhysAddr>>, SvsmError> {
         match self {
             SvsmConfig::FirmwareConfig(fw_cfg) => {
                 let flash_regions = fw_cfg.iter_flash_regions().collect::<Vec<_>>();
                 check_ovmf_regions(&flash_regions, kernel_region);
-                flash_regions
+                Ok(flash_regions)
             }
             SvsmConfig::IgvmConfig(igvm_params) => {
-                let flash_regions = igvm_params.get_fw_regions();
+                let flash_regions = igvm_params.get_fw_regions()?;
                 if !igvm_params.fw_in_low_memory() {
                     check_ovmf_regions(&flash_regions, kernel_region);
                 }
-                flash_regions
+                Ok(flash_regions)
             }
         }
     }
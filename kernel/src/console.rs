@@ -4,6 +4,7 @@
 //
 // Author: Joerg Roedel <jroedel@suse.de>
 
+use crate::error::SvsmError;
 use crate::locking::SpinLock;
 use crate::serial::{Terminal, DEFAULT_SERIAL_PORT};
 use crate::utils::immut_after_init::{ImmutAfterInitCell, ImmutAfterInitResult};
@@ -31,6 +32,71 @@ impl fmt::Debug for Console {
     }
 }
 
+/// Maximum number of terminals a [`CompositeTerminal`] can fan out to.
+pub const MAX_COMPOSITE_TERMINALS: usize = 4;
+
+/// A [`Terminal`] multiplexing console output to several terminals, e.g.
+/// to a serial port and an in-memory log buffer at the same time.  Input
+/// is only read from the primary terminal passed at construction time.
+pub struct CompositeTerminal {
+    primary: &'static dyn Terminal,
+    secondary: SpinLock<[Option<&'static dyn Terminal>; MAX_COMPOSITE_TERMINALS - 1]>,
+}
+
+impl CompositeTerminal {
+    pub const fn new(primary: &'static dyn Terminal) -> Self {
+        Self {
+            primary,
+            secondary: SpinLock::new([None; MAX_COMPOSITE_TERMINALS - 1]),
+        }
+    }
+
+    /// Adds a terminal to receive a copy of all output.  Fails with
+    /// [`SvsmError::NotSupported`] once [`MAX_COMPOSITE_TERMINALS`] are
+    /// registered.
+    pub fn add(&self, terminal: &'static dyn Terminal) -> Result<(), SvsmError> {
+        let mut secondary = self.secondary.lock();
+        let slot = secondary
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(SvsmError::NotSupported)?;
+        *slot = Some(terminal);
+        Ok(())
+    }
+}
+
+impl Terminal for CompositeTerminal {
+    fn put_byte(&self, ch: u8) {
+        // Terminals can't report write failures, so every one of them gets
+        // each byte regardless of what happened with the others.
+        self.primary.put_byte(ch);
+        for terminal in self.secondary.lock().iter().flatten() {
+            terminal.put_byte(ch);
+        }
+    }
+
+    fn get_byte(&self) -> u8 {
+        self.primary.get_byte()
+    }
+
+    fn try_get_byte(&self) -> Option<u8> {
+        self.primary.try_get_byte()
+    }
+}
+
+impl fmt::Debug for CompositeTerminal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secondary = self.secondary.lock();
+        f.debug_struct("CompositeTerminal")
+            .field("primary", &format_args!("{:?}", self.primary as *const _))
+            .field(
+                "secondary",
+                &format_args!("{}", secondary.iter().flatten().count()),
+            )
+            .finish()
+    }
+}
+
 static WRITER: SpinLock<Console> = SpinLock::new(Console {
     writer: &DEFAULT_SERIAL_PORT,
 });
@@ -136,3 +202,70 @@ macro_rules! println {
     () => (log::info!(""));
     ($($arg:tt)*) => (log::info!($($arg)*));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct RecordingTerminal {
+        output: SpinLock<([u8; 8], usize)>,
+        input: u8,
+    }
+
+    impl RecordingTerminal {
+        const fn new(input: u8) -> Self {
+            Self {
+                output: SpinLock::new(([0; 8], 0)),
+                input,
+            }
+        }
+
+        fn output(&self) -> ([u8; 8], usize) {
+            *self.output.lock()
+        }
+    }
+
+    impl Terminal for RecordingTerminal {
+        fn put_byte(&self, ch: u8) {
+            let mut output = self.output.lock();
+            let (buf, len) = &mut *output;
+            if let Some(slot) = buf.get_mut(*len) {
+                *slot = ch;
+                *len += 1;
+            }
+        }
+
+        fn get_byte(&self) -> u8 {
+            self.input
+        }
+
+        fn try_get_byte(&self) -> Option<u8> {
+            Some(self.input)
+        }
+    }
+
+    #[test]
+    fn test_composite_terminal() {
+        static PRIMARY: RecordingTerminal = RecordingTerminal::new(b'p');
+        static SECONDARY: RecordingTerminal = RecordingTerminal::new(b's');
+        static COMPOSITE: CompositeTerminal = CompositeTerminal::new(&PRIMARY);
+
+        COMPOSITE.add(&SECONDARY).unwrap();
+        COMPOSITE.put_byte(b'x');
+        COMPOSITE.put_byte(b'y');
+        assert_eq!(PRIMARY.output().1, 2);
+        assert_eq!(&PRIMARY.output().0[..2], b"xy");
+        assert_eq!(SECONDARY.output(), PRIMARY.output());
+
+        // Input only comes from the primary terminal.
+        assert_eq!(COMPOSITE.get_byte(), b'p');
+        assert_eq!(COMPOSITE.try_get_byte(), Some(b'p'));
+
+        // The primary counts towards the limit.
+        for _ in 1..MAX_COMPOSITE_TERMINALS - 1 {
+            COMPOSITE.add(&SECONDARY).unwrap();
+        }
+        assert!(COMPOSITE.add(&SECONDARY).is_err());
+    }
+}
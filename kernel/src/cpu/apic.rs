@@ -4,6 +4,8 @@
 //
 // Author: Jon Lange (jlange@microsoft.com)
 
+extern crate alloc;
+
 use crate::address::VirtAddr;
 use crate::cpu::idt::common::INT_INJ_VECTOR;
 use crate::cpu::percpu::{current_ghcb, this_cpu, PerCpuShared, PERCPU_AREAS};
@@ -11,16 +13,19 @@ use crate::mm::GuestPtr;
 use crate::platform::guest_cpu::GuestCpuState;
 use crate::platform::SVSM_PLATFORM;
 use crate::requests::SvsmCaa;
-use crate::sev::hv_doorbell::HVExtIntStatus;
+use crate::sev::hv_doorbell::{HVExtIntInfo, HVExtIntStatus};
 use crate::types::GUEST_VMPL;
 
+use alloc::vec::Vec;
 use bitfield_struct::bitfield;
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 const APIC_REGISTER_APIC_ID: u64 = 0x802;
 const APIC_REGISTER_TPR: u64 = 0x808;
 const APIC_REGISTER_PPR: u64 = 0x80A;
 const APIC_REGISTER_EOI: u64 = 0x80B;
+const APIC_REGISTER_LDR: u64 = 0x80D;
+const APIC_REGISTER_DFR: u64 = 0x80E;
 const APIC_REGISTER_ISR_0: u64 = 0x810;
 const APIC_REGISTER_ISR_7: u64 = 0x817;
 const APIC_REGISTER_TMR_0: u64 = 0x818;
@@ -30,6 +35,57 @@ const APIC_REGISTER_IRR_7: u64 = 0x827;
 const APIC_REGISTER_ICR: u64 = 0x830;
 const APIC_REGISTER_SELF_IPI: u64 = 0x83F;
 
+/// An x2APIC register handled by the emulated APIC.  The register banks
+/// (ISR, TMR and IRR) carry the index of the 32-bit register within the
+/// bank.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ApicRegister {
+    ApicId,
+    Tpr,
+    Ppr,
+    Eoi,
+    Ldr,
+    Dfr,
+    Isr(usize),
+    Tmr(usize),
+    Irr(usize),
+    Icr,
+    SelfIpi,
+}
+
+impl ApicRegister {
+    fn from_offset(offset: u64) -> Option<Self> {
+        let bank_index = |base: u64| usize::try_from(offset - base).unwrap();
+        match offset {
+            APIC_REGISTER_APIC_ID => Some(Self::ApicId),
+            APIC_REGISTER_TPR => Some(Self::Tpr),
+            APIC_REGISTER_PPR => Some(Self::Ppr),
+            APIC_REGISTER_EOI => Some(Self::Eoi),
+            APIC_REGISTER_LDR => Some(Self::Ldr),
+            APIC_REGISTER_DFR => Some(Self::Dfr),
+            APIC_REGISTER_ISR_0..=APIC_REGISTER_ISR_7 => {
+                Some(Self::Isr(bank_index(APIC_REGISTER_ISR_0)))
+            }
+            APIC_REGISTER_TMR_0..=APIC_REGISTER_TMR_7 => {
+                Some(Self::Tmr(bank_index(APIC_REGISTER_TMR_0)))
+            }
+            APIC_REGISTER_IRR_0..=APIC_REGISTER_IRR_7 => {
+                Some(Self::Irr(bank_index(APIC_REGISTER_IRR_0)))
+            }
+            APIC_REGISTER_ICR => Some(Self::Icr),
+            APIC_REGISTER_SELF_IPI => Some(Self::SelfIpi),
+            _ => None,
+        }
+    }
+}
+
+// The DFR model field occupies bits 31:28; all other bits are reserved and
+// read as ones.
+const APIC_DFR_MODEL_MASK: u32 = 0xF000_0000;
+const APIC_DFR_MODEL_FLAT: u32 = 0xF000_0000;
+// The xAPIC logical ID occupies bits 31:24 of the LDR.
+const APIC_LDR_LOGICAL_ID_MASK: u32 = 0xFF00_0000;
+
 #[derive(Debug, PartialEq)]
 enum IcrDestFmt {
     Dest = 0,
@@ -55,7 +111,9 @@ impl IcrDestFmt {
 #[derive(Debug, PartialEq)]
 enum IcrMessageType {
     Fixed = 0,
-    Unknown = 3,
+    LowestPriority = 1,
+    Smi = 2,
+    RemoteRead = 3,
     Nmi = 4,
     Init = 5,
     Sipi = 6,
@@ -72,8 +130,10 @@ impl IcrMessageType {
             6 => Self::Sipi,
             5 => Self::Init,
             4 => Self::Nmi,
-            0 => Self::Fixed,
-            _ => Self::Unknown,
+            3 => Self::RemoteRead,
+            2 => Self::Smi,
+            1 => Self::LowestPriority,
+            _ => Self::Fixed,
         }
     }
 }
@@ -97,9 +157,35 @@ struct ApicIcr {
     pub destination: u32,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ApicError {
     ApicError,
+    /// The ICR specified a message type that is not supported by the
+    /// emulated APIC.
+    UnsupportedMessageType,
+}
+
+/// Callback used to sample the state of a locally generated level-sensitive
+/// interrupt line.  Returns `true` if the line is still asserted.
+pub type LevelSource = fn() -> bool;
+
+/// Per-vector counts of posted and EOI'd interrupts, used to diagnose
+/// interrupt storms.
+#[cfg(feature = "apic-stats")]
+#[derive(Debug)]
+struct ApicStats {
+    posted: [u64; 256],
+    eoi: [u64; 256],
+}
+
+#[cfg(feature = "apic-stats")]
+impl Default for ApicStats {
+    fn default() -> Self {
+        Self {
+            posted: [0; 256],
+            eoi: [0; 256],
+        }
+    }
 }
 
 // This structure must never be copied because a silent copy will cause APIC
@@ -113,11 +199,19 @@ pub struct LocalApic {
     isr_stack: [u8; 16],
     tmr: [u32; 8],
     host_tmr: [u32; 8],
+    level_reasserted: [u32; 8],
+    host_level_reasserted: [u32; 8],
     update_required: bool,
     interrupt_delivered: bool,
     interrupt_queued: bool,
     lazy_eoi_pending: bool,
     nmi_pending: bool,
+    level_sources: Vec<(u8, LevelSource)>,
+    ldr: u32,
+    dfr: u32,
+    icr: u64,
+    #[cfg(feature = "apic-stats")]
+    stats: ApicStats,
 }
 
 impl LocalApic {
@@ -129,14 +223,59 @@ impl LocalApic {
             isr_stack: [0; 16],
             tmr: [0; 8],
             host_tmr: [0; 8],
+            level_reasserted: [0; 8],
+            host_level_reasserted: [0; 8],
             update_required: false,
             interrupt_delivered: false,
             interrupt_queued: false,
             lazy_eoi_pending: false,
             nmi_pending: false,
+            level_sources: Vec::new(),
+            ldr: 0,
+            dfr: 0xFFFF_FFFF,
+            icr: 0,
+            #[cfg(feature = "apic-stats")]
+            stats: ApicStats::default(),
+        }
+    }
+
+    /// Returns the number of times `vector` has been posted since the
+    /// statistics were last reset.
+    #[cfg(feature = "apic-stats")]
+    pub fn vector_count(&self, vector: u8) -> u64 {
+        self.stats.posted[vector as usize]
+    }
+
+    /// Returns the number of times `vector` has been EOI'd since the
+    /// statistics were last reset.
+    #[cfg(feature = "apic-stats")]
+    pub fn eoi_count(&self, vector: u8) -> u64 {
+        self.stats.eoi[vector as usize]
+    }
+
+    #[cfg(feature = "apic-stats")]
+    pub fn reset_stats(&mut self) {
+        self.stats = ApicStats::default();
+    }
+
+    /// Registers a source for a locally generated level-sensitive interrupt
+    /// on `vector`.  When such an interrupt is EOI'd, the source is consulted
+    /// and the interrupt is posted again if the line is still asserted.  Any
+    /// source previously registered for the same vector is replaced.
+    pub fn register_level_source(&mut self, vector: u8, source: LevelSource) {
+        match self.level_sources.iter_mut().find(|(v, _)| *v == vector) {
+            Some(entry) => entry.1 = source,
+            None => self.level_sources.push((vector, source)),
         }
     }
 
+    fn level_source(&self, vector: u8) -> Option<LevelSource> {
+        self.level_sources
+            .iter()
+            .find(|(v, _)| *v == vector)
+            .map(|(_, source)| *source)
+    }
+
     fn scan_irr(&self) -> u8 {
         // Scan to find the highest pending IRR vector.
         for (i, irr) in self.irr.into_iter().enumerate().rev() {
@@ -165,6 +304,10 @@ impl LocalApic {
         let new_index = self.isr_stack_index.checked_sub(1).unwrap();
         assert!(self.isr_stack.get(new_index) == Some(&irq));
         Self::insert_vector_register(&mut self.irr, irq);
+        // A reassertion while the rewound instance was in service merges
+        // with it in the IRR, so there is no longer a separate instance.
+        Self::remove_vector_register(&mut self.level_reasserted, irq);
+        Self::remove_vector_register(&mut self.host_level_reasserted, irq);
         self.isr_stack_index = new_index;
         self.update_required = true;
     }
@@ -218,18 +361,26 @@ impl LocalApic {
         }
     }
 
+    /// Returns the vector of the highest priority interrupt in service, or
+    /// zero if none is in service.
+    fn in_service_priority(&self) -> u8 {
+        // The ISR stack is ordered by priority, so the most recently
+        // delivered interrupt has the highest priority.
+        match self.isr_stack_index {
+            0 => 0,
+            index => self.isr_stack[index - 1],
+        }
+    }
+
     fn get_ppr_with_tpr(&self, tpr: u8) -> u8 {
         // Determine the priority of the current in-service interrupt, if any.
-        let ppr = if self.isr_stack_index != 0 {
-            self.isr_stack[self.isr_stack_index]
-        } else {
-            0
-        };
+        let ppr = self.in_service_priority();
 
         // The PPR is the higher of the in-service interrupt priority and the
-        // task priority.
+        // task priority.  When the in-service interrupt wins, only its
+        // priority class is reported; the sub-class bits are cleared.
         if (ppr >> 4) > (tpr >> 4) {
-            ppr
+            ppr & 0xf0
         } else {
             tpr
         }
@@ -299,87 +450,101 @@ impl LocalApic {
         }
 
         if self.update_required {
-            // Make sure that all previously delivered interrupts have been
-            // processed before attempting to process any more.
-            self.check_delivered_interrupts(cpu_state, caa_addr);
-            self.update_required = false;
-
-            // If an NMI is pending, then present it first.
-            if self.nmi_pending {
-                cpu_state.request_nmi();
-                self.nmi_pending = false;
-            }
+            self.process_pending_interrupts(cpu_state, caa_addr);
+        }
+    }
 
-            let irq = self.scan_irr();
-            let current_priority = if self.isr_stack_index != 0 {
-                self.isr_stack[self.isr_stack_index - 1]
-            } else {
-                0
-            };
+    /// Reevaluates the IRR and delivers or queues the highest priority
+    /// pending interrupt, subject to the in-service and task priorities.
+    fn process_pending_interrupts<T: GuestCpuState>(
+        &mut self,
+        cpu_state: &mut T,
+        caa_addr: Option<VirtAddr>,
+    ) {
+        // Make sure that all previously delivered interrupts have been
+        // processed before attempting to process any more.
+        self.check_delivered_interrupts(cpu_state, caa_addr);
+        self.update_required = false;
 
-            // Assume no lazy EOI can be attempted unless it is recalculated
-            // below.
-            self.lazy_eoi_pending = false;
-            let guest_caa = Self::clear_guest_eoi_pending(caa_addr);
-
-            // This interrupt is a candidate for delivery only if its priority
-            // exceeds the priority of the highest priority interrupt currently
-            // in service.  This check does not consider TPR, because an
-            // interrupt lower in priority than TPR must be queued for delivery
-            // as soon as TPR is lowered.
-            if (irq & 0xF0) <= (current_priority & 0xF0) {
-                return;
-            }
+        // If an NMI is pending, then present it first.
+        if self.nmi_pending {
+            cpu_state.request_nmi();
+            self.nmi_pending = false;
+        }
 
-            // Determine whether this interrupt can be injected
-            // immediately.  If not, queue it for delivery when possible.
-            let try_lazy_eoi = if self.deliver_interrupt_immediately(irq, cpu_state) {
-                self.interrupt_delivered = true;
+        let irq = self.scan_irr();
+        let current_priority = self.in_service_priority();
 
-                // Use of lazy EOI can safely be attempted, because the
-                // highest priority interrupt in service is unambiguous.
-                true
-            } else {
-                cpu_state.queue_interrupt(irq);
-                self.interrupt_queued = true;
-
-                // A lazy EOI can only be attempted if there is no lower
-                // priority interrupt in service.  If a lower priority
-                // interrupt is in service, then the lazy EOI handler
-                // won't know whether the lazy EOI is for the one that
-                // is already in service or the one that is being queued
-                // here.
-                self.isr_stack_index == 0
-            };
+        // Assume no lazy EOI can be attempted unless it is recalculated
+        // below.
+        self.lazy_eoi_pending = false;
+        let guest_caa = Self::clear_guest_eoi_pending(caa_addr);
+
+        // This interrupt is a candidate for delivery only if its priority
+        // exceeds the priority of the highest priority interrupt currently
+        // in service.  This check does not consider TPR, because an
+        // interrupt lower in priority than TPR must be queued for delivery
+        // as soon as TPR is lowered.
+        if (irq & 0xF0) <= (current_priority & 0xF0) {
+            return;
+        }
+
+        // Each nested interrupt must be of a strictly higher priority class
+        // than the one it interrupts, so the ISR stack cannot normally fill
+        // up.  Should it be full regardless, leave the interrupt pending in
+        // the IRR rather than displacing an in-service interrupt; it will be
+        // reconsidered once an EOI has made room.
+        if self.isr_stack_index == self.isr_stack.len() {
+            return;
+        }
 
-            // Mark this interrupt in-service.  It will be recalled if
-            // the ISR is examined again before the interrupt is actually
-            // delivered.
-            Self::remove_vector_register(&mut self.irr, irq);
-            self.isr_stack[self.isr_stack_index] = irq;
-            self.isr_stack_index += 1;
-
-            // Configure a lazy EOI if possible.  Lazy EOI is not possible
-            // for level-sensitive interrupts, because an explicit EOI
-            // is required to acknowledge the interrupt at the source.
-            if try_lazy_eoi && !Self::test_vector_register(&self.tmr, irq) {
-                // A lazy EOI is possible only if there is no other
-                // interrupt pending.  If another interrupt is pending,
-                // then an explicit EOI will be required to prompt
-                // delivery of the next interrupt.
-                if self.scan_irr() == 0 {
-                    if let Some(calling_area) = guest_caa {
-                        // SAFETY: guest vmsa and ca are always validated before beeing upated
-                        // (core_remap_ca(), core_create_vcpu() or prepare_fw_launch())
-                        // so they're safe to use.
-                        if let Ok(caa) = unsafe { calling_area.read() } {
-                            if unsafe { calling_area.write(caa.update_no_eoi_required(1)).is_ok() }
-                            {
-                                // Only track a pending lazy EOI if the
-                                // calling area page could successfully be
-                                // updated.
-                                self.lazy_eoi_pending = true;
-                            }
+        // Determine whether this interrupt can be injected
+        // immediately.  If not, queue it for delivery when possible.
+        let try_lazy_eoi = if self.deliver_interrupt_immediately(irq, cpu_state) {
+            self.interrupt_delivered = true;
+
+            // Use of lazy EOI can safely be attempted, because the
+            // highest priority interrupt in service is unambiguous.
+            true
+        } else {
+            cpu_state.queue_interrupt(irq);
+            self.interrupt_queued = true;
+
+            // A lazy EOI can only be attempted if there is no lower
+            // priority interrupt in service.  If a lower priority
+            // interrupt is in service, then the lazy EOI handler
+            // won't know whether the lazy EOI is for the one that
+            // is already in service or the one that is being queued
+            // here.
+            self.isr_stack_index == 0
+        };
+
+        // Mark this interrupt in-service.  It will be recalled if
+        // the ISR is examined again before the interrupt is actually
+        // delivered.
+        Self::remove_vector_register(&mut self.irr, irq);
+        self.isr_stack[self.isr_stack_index] = irq;
+        self.isr_stack_index += 1;
+
+        // Configure a lazy EOI if possible.  Lazy EOI is not possible
+        // for level-sensitive interrupts, because an explicit EOI
+        // is required to acknowledge the interrupt at the source.
+        if try_lazy_eoi && !Self::test_vector_register(&self.tmr, irq) {
+            // A lazy EOI is possible only if there is no other
+            // interrupt pending.  If another interrupt is pending,
+            // then an explicit EOI will be required to prompt
+            // delivery of the next interrupt.
+            if self.scan_irr() == 0 {
+                if let Some(calling_area) = guest_caa {
+                    // SAFETY: guest vmsa and ca are always validated before beeing upated
+                    // (core_remap_ca(), core_create_vcpu() or prepare_fw_launch())
+                    // so they're safe to use.
+                    if let Ok(caa) = unsafe { calling_area.read() } {
+                        if unsafe { calling_area.write(caa.update_no_eoi_required(1)).is_ok() } {
+                            // Only track a pending lazy EOI if the
+                            // calling area page could successfully be
+                            // updated.
+                            self.lazy_eoi_pending = true;
                         }
                     }
                 }
@@ -395,29 +560,58 @@ impl LocalApic {
     }
 
     pub fn perform_eoi(&mut self) {
+        if let Some(vector) = self.retire_in_service() {
+            Self::perform_host_eoi(vector);
+        }
+    }
+
+    /// Retires the highest priority in-service interrupt.  Returns the
+    /// vector if the interrupt was a level-sensitive interrupt signaled by
+    /// the host, which must then be sent an EOI as well.
+    fn retire_in_service(&mut self) -> Option<u8> {
         // Pop any in-service interrupt from the stack.  If there is no
         // interrupt in service, then there is nothing to do.
         if self.isr_stack_index == 0 {
-            return;
+            return None;
         }
 
         self.isr_stack_index -= 1;
         let vector = self.isr_stack[self.isr_stack_index];
+        #[cfg(feature = "apic-stats")]
+        {
+            self.stats.eoi[vector as usize] += 1;
+        }
+        let mut host_eoi = None;
         if Self::test_vector_register(&self.tmr, vector) {
+            // If the same vector has been asserted again as level-sensitive
+            // while this instance was in service, then the trigger mode
+            // belongs to the pending instance as well and must survive this
+            // EOI, so that the next EOI is also forwarded to the host.  A
+            // pending edge-triggered instance does not count.
+            if !Self::test_vector_register(&self.level_reasserted, vector) {
+                Self::remove_vector_register(&mut self.tmr, vector);
+            }
+            Self::remove_vector_register(&mut self.level_reasserted, vector);
             if Self::test_vector_register(&self.host_tmr, vector) {
-                Self::perform_host_eoi(vector);
-                Self::remove_vector_register(&mut self.host_tmr, vector);
-            } else {
-                // FIXME: should do something with locally generated
-                // level-sensitive interrupts.
+                host_eoi = Some(vector);
+                if !Self::test_vector_register(&self.host_level_reasserted, vector) {
+                    Self::remove_vector_register(&mut self.host_tmr, vector);
+                }
+                Self::remove_vector_register(&mut self.host_level_reasserted, vector);
+            } else if let Some(source) = self.level_source(vector) {
+                // A locally generated level-sensitive interrupt must be
+                // asserted again if the source is still requesting service.
+                if source() {
+                    self.post_interrupt(vector, true);
+                }
             }
-            Self::remove_vector_register(&mut self.tmr, vector);
         }
 
         // Schedule the APIC for reevaluation so any additional pending
         // interrupt can be processed.
         self.update_required = true;
         self.lazy_eoi_pending = false;
+        host_eoi
     }
 
     fn get_isr(&self, index: usize) -> u32 {
@@ -430,30 +624,58 @@ impl LocalApic {
         value
     }
 
+    /// Returns a snapshot of the eight ISR words reconstructed from the
+    /// in-service stack.  This does not reevaluate interrupt delivery state,
+    /// so an interrupt that has been presented to the guest but not yet
+    /// accepted is reported as in service.
+    pub fn isr_snapshot(&self) -> [u32; 8] {
+        core::array::from_fn(|index| self.get_isr(index))
+    }
+
+    /// Returns a snapshot of the eight IRR words.  Like
+    /// [`isr_snapshot`](Self::isr_snapshot), this does not reevaluate
+    /// interrupt delivery state.
+    pub fn irr_snapshot(&self) -> [u32; 8] {
+        self.irr
+    }
+
+    /// Returns `true` if `vector` is currently in service, i.e. it has been
+    /// delivered to the guest but has not yet been EOI'd.
+    pub fn is_in_service(&self, vector: u8) -> bool {
+        self.isr_stack[..self.isr_stack_index].contains(&vector)
+    }
+
     fn post_interrupt(&mut self, irq: u8, level_sensitive: bool) {
         // Set the appropriate bit in the IRR.  Once set, signal that interrupt
         // processing is required before returning to the guest.
         Self::insert_vector_register(&mut self.irr, irq);
         if level_sensitive {
+            if self.is_in_service(irq) {
+                Self::insert_vector_register(&mut self.level_reasserted, irq);
+            }
             Self::insert_vector_register(&mut self.tmr, irq);
         }
+        #[cfg(feature = "apic-stats")]
+        {
+            self.stats.posted[irq as usize] += 1;
+        }
         self.update_required = true;
     }
 
     fn post_icr_interrupt(&mut self, icr: ApicIcr) {
-        if icr.message_type() == IcrMessageType::Nmi {
-            self.nmi_pending = true;
-            self.update_required = true;
-        } else {
-            self.post_interrupt(icr.vector(), false);
+        match icr.message_type() {
+            IcrMessageType::Nmi => {
+                self.nmi_pending = true;
+                self.update_required = true;
+            }
+            _ => self.post_interrupt(icr.vector(), false),
         }
     }
 
     fn post_ipi_one_target(cpu: &PerCpuShared, icr: ApicIcr) {
-        if icr.message_type() == IcrMessageType::Nmi {
-            cpu.request_nmi();
-        } else {
-            cpu.request_ipi(icr.vector());
+        match icr.message_type() {
+            IcrMessageType::Nmi => cpu.post_nmi(),
+            _ => cpu.request_ipi(icr.vector()),
         }
     }
 
@@ -463,18 +685,19 @@ impl LocalApic {
         // Check whether the current CPU matches the destination.
         let destination = icr.destination();
         let apic_id = this_cpu().get_apic_id();
-        if Self::logical_destination_match(destination, apic_id) {
+        if Self::logical_destination_match(destination, apic_id, self.ldr, self.dfr) {
             self.post_icr_interrupt(icr);
         }
 
-        // Enumerate all CPUs to see which have APIC IDs that match the
-        // requested destination.  Skip the current CPU, since it was checked
-        // above.
+        // Enumerate all CPUs to see which have logical destinations that
+        // match the requested destination.  Skip the current CPU, since it
+        // was checked above.
         for cpu_ref in PERCPU_AREAS.iter() {
             let cpu = cpu_ref.as_cpu_ref();
             let this_apic_id = cpu.apic_id();
+            let (ldr, dfr) = cpu.apic_logical_destination();
             if (this_apic_id != apic_id)
-                && Self::logical_destination_match(destination, this_apic_id)
+                && Self::logical_destination_match(destination, this_apic_id, ldr, dfr)
             {
                 Self::post_ipi_one_target(cpu, icr);
                 signal = true;
@@ -484,13 +707,40 @@ impl LocalApic {
         signal
     }
 
-    fn logical_destination_match(destination: u32, apic_id: u32) -> bool {
-        // CHeck for a cluster match.
-        if (destination >> 16) != (apic_id >> 4) {
-            false
+    fn logical_destination_match(destination: u32, apic_id: u32, ldr: u32, dfr: u32) -> bool {
+        // An all-ones destination is a broadcast to all logical IDs.
+        if destination == 0xFFFF_FFFF {
+            return true;
+        }
+
+        // If the guest has not programmed a logical ID, then use the x2APIC
+        // cluster format, which is derived from the APIC ID.
+        if ldr == 0 {
+            // Check for a cluster match.
+            return if (destination >> 16) != (apic_id >> 4) {
+                false
+            } else {
+                let bit = 1u32 << (apic_id & 0xF);
+                (destination & bit) != 0
+            };
+        }
+
+        // The xAPIC logical destination is an 8-bit value in the high byte
+        // of the destination field, and the logical ID is held in the high
+        // byte of the LDR.
+        let destination = destination >> 24;
+        let logical_id = (ldr & APIC_LDR_LOGICAL_ID_MASK) >> 24;
+        if destination == 0xFF {
+            // This is a broadcast to all processors.
+            true
+        } else if (dfr & APIC_DFR_MODEL_MASK) == APIC_DFR_MODEL_FLAT {
+            // In the flat model, each bit of the destination selects one
+            // processor.
+            (destination & logical_id) != 0
         } else {
-            let bit = 1u32 << (apic_id & 0xF);
-            (destination & bit) != 0
+            // In the cluster model, the upper nibble selects the cluster and
+            // the lower nibble selects processors within the cluster.
+            (destination >> 4) == (logical_id >> 4) && (destination & logical_id & 0xF) != 0
         }
     }
 
@@ -499,16 +749,20 @@ impl LocalApic {
         // as a self-IPI.  Otherwise, locate the target processor by APIC ID.
         let destination = icr.destination();
         if destination == this_cpu().get_apic_id() {
-            self.post_interrupt(icr.vector(), false);
+            self.post_icr_interrupt(icr);
             false
         } else {
             // If the target CPU cannot be located, then simply drop the
             // request.
-            if let Some(cpu) = PERCPU_AREAS.get(destination) {
-                cpu.request_ipi(icr.vector());
-                true
-            } else {
-                false
+            match PERCPU_AREAS.try_get_by_apic_id(destination) {
+                Ok(cpu) => {
+                    Self::post_ipi_one_target(cpu, icr);
+                    true
+                }
+                Err(e) => {
+                    log::debug!("Dropping IPI {:#x}: {:?}", u64::from(icr), e);
+                    false
+                }
             }
         }
     }
@@ -537,11 +791,10 @@ impl LocalApic {
         };
 
         if include_others {
-            // Enumerate all processors in the system except for the
+            // Enumerate all online processors in the system except for the
             // current CPU and indicate that an IPI has been requested.
             let apic_id = this_cpu().get_apic_id();
-            for cpu_ref in PERCPU_AREAS.iter() {
-                let cpu = cpu_ref.as_cpu_ref();
+            for cpu in PERCPU_AREAS.online_cpus() {
                 if cpu.apic_id() != apic_id {
                     Self::post_ipi_one_target(cpu, icr);
                 }
@@ -587,29 +840,42 @@ impl LocalApic {
         // read.
         self.check_delivered_interrupts(cpu_state, caa_addr);
 
+        let Some(register) = ApicRegister::from_offset(register) else {
+            return Err(ApicError::ApicError);
+        };
+
         match register {
-            APIC_REGISTER_APIC_ID => Ok(u64::from(cpu_shared.apic_id())),
-            APIC_REGISTER_IRR_0..=APIC_REGISTER_IRR_7 => {
-                let offset = register - APIC_REGISTER_IRR_0;
-                let index: usize = offset.try_into().unwrap();
-                Ok(self.irr[index] as u64)
-            }
-            APIC_REGISTER_ISR_0..=APIC_REGISTER_ISR_7 => {
-                let offset = register - APIC_REGISTER_ISR_0;
-                Ok(self.get_isr(offset.try_into().unwrap()) as u64)
-            }
-            APIC_REGISTER_TMR_0..=APIC_REGISTER_TMR_7 => {
-                let offset = register - APIC_REGISTER_TMR_0;
-                let index: usize = offset.try_into().unwrap();
-                Ok(self.tmr[index] as u64)
+            ApicRegister::ApicId => Ok(u64::from(cpu_shared.apic_id())),
+            ApicRegister::Irr(index) => Ok(self.irr[index] as u64),
+            ApicRegister::Isr(index) => Ok(self.get_isr(index) as u64),
+            ApicRegister::Tmr(index) => Ok(self.tmr[index] as u64),
+            ApicRegister::Tpr => Ok(cpu_state.get_tpr() as u64),
+            ApicRegister::Ppr => Ok(self.get_ppr(cpu_state) as u64),
+            ApicRegister::Ldr => {
+                if self.ldr != 0 {
+                    Ok(self.ldr as u64)
+                } else {
+                    // Report the x2APIC logical ID derived from the APIC ID.
+                    let apic_id = cpu_shared.apic_id();
+                    Ok((((apic_id >> 4) << 16) | (1 << (apic_id & 0xF))) as u64)
+                }
             }
-            APIC_REGISTER_TPR => Ok(cpu_state.get_tpr() as u64),
-            APIC_REGISTER_PPR => Ok(self.get_ppr(cpu_state) as u64),
-            _ => Err(ApicError::ApicError),
+            ApicRegister::Dfr => Ok(self.dfr as u64),
+            ApicRegister::Icr => Ok(self.read_icr()),
+            // SELF_IPI is write-only and reads as zero.
+            ApicRegister::SelfIpi => Ok(0),
+            ApicRegister::Eoi => Err(ApicError::ApicError),
         }
     }
 
+    fn read_icr(&self) -> u64 {
+        // IPIs are delivered synchronously, so the delivery status of the
+        // last ICR write is always idle.
+        ApicIcr::from(self.icr).with_delivery_status(false).into()
+    }
+
     fn handle_icr_write(&mut self, value: u64) -> Result<(), ApicError> {
+        self.icr = value;
         let icr = ApicIcr::from(value);
 
         // Verify that this message type is supported.
@@ -619,6 +885,22 @@ impl LocalApic {
                 !icr.trigger_mode() && icr.assert()
             }
             IcrMessageType::Nmi => true,
+            IcrMessageType::Init | IcrMessageType::Sipi => {
+                // Guest vCPUs are created and started through the SVSM core
+                // protocol.  There is no wait-for-SIPI state to place a vCPU
+                // in, and resetting the APIC of a running vCPU would corrupt
+                // it, so guest-driven AP startup is not supported.
+                return Err(ApicError::UnsupportedMessageType);
+            }
+            IcrMessageType::RemoteRead => {
+                // Remote reads are obsolete and are not supported in x2APIC
+                // mode.  Report the attempt once so it can be diagnosed.
+                static REMOTE_READ_REPORTED: AtomicBool = AtomicBool::new(false);
+                if !REMOTE_READ_REPORTED.swap(true, Ordering::Relaxed) {
+                    log::warn!("Guest attempted an unsupported APIC remote read");
+                }
+                return Err(ApicError::UnsupportedMessageType);
+            }
             _ => false,
         };
 
@@ -633,6 +915,7 @@ impl LocalApic {
 
     pub fn write_register<T: GuestCpuState>(
         &mut self,
+        cpu_shared: &PerCpuShared,
         cpu_state: &mut T,
         caa_addr: Option<VirtAddr>,
         register: u64,
@@ -642,8 +925,12 @@ impl LocalApic {
         // any register write.
         self.check_delivered_interrupts(cpu_state, caa_addr);
 
+        let Some(register) = ApicRegister::from_offset(register) else {
+            return Err(ApicError::ApicError);
+        };
+
         match register {
-            APIC_REGISTER_TPR => {
+            ApicRegister::Tpr => {
                 // TPR must be an 8-bit value.
                 match u8::try_from(value) {
                     Ok(tpr) => {
@@ -653,22 +940,71 @@ impl LocalApic {
                     Err(_) => Err(ApicError::ApicError),
                 }
             }
-            APIC_REGISTER_EOI => {
+            ApicRegister::Eoi => {
                 self.perform_eoi();
                 Ok(())
             }
-            APIC_REGISTER_ICR => self.handle_icr_write(value),
-            APIC_REGISTER_SELF_IPI => match u8::try_from(value) {
+            ApicRegister::Icr => self.handle_icr_write(value),
+            ApicRegister::Ldr => match u32::try_from(value) {
+                Ok(ldr) => {
+                    self.ldr = ldr & APIC_LDR_LOGICAL_ID_MASK;
+                    this_cpu()
+                        .shared()
+                        .set_apic_logical_destination(self.ldr, self.dfr);
+                    Ok(())
+                }
+                Err(_) => Err(ApicError::ApicError),
+            },
+            ApicRegister::Dfr => match u32::try_from(value) {
+                Ok(dfr) => {
+                    self.dfr = dfr | !APIC_DFR_MODEL_MASK;
+                    this_cpu()
+                        .shared()
+                        .set_apic_logical_destination(self.ldr, self.dfr);
+                    Ok(())
+                }
+                Err(_) => Err(ApicError::ApicError),
+            },
+            ApicRegister::SelfIpi => match u8::try_from(value) {
                 Ok(vector) => {
-                    self.post_interrupt(vector, false);
+                    let host_descriptor = Self::pending_host_descriptor();
+                    self.post_self_ipi(host_descriptor, cpu_shared, cpu_state, caa_addr, vector);
                     Ok(())
                 }
                 Err(_) => Err(ApicError::ApicError),
             },
-            _ => Err(ApicError::ApicError),
+            ApicRegister::ApicId
+            | ApicRegister::Ppr
+            | ApicRegister::Isr(_)
+            | ApicRegister::Tmr(_)
+            | ApicRegister::Irr(_) => Err(ApicError::ApicError),
         }
     }
 
+    /// Posts a self-IPI, which is always a fixed interrupt, and evaluates it
+    /// immediately so that it is subject to the same priority checks as any
+    /// other pending interrupt, and is queued rather than delivered if it
+    /// does not exceed the PPR.  Interrupts already signaled by the host or
+    /// by other CPUs are consumed first so that a higher priority one among
+    /// them is not preempted by the self-IPI.
+    fn post_self_ipi<T: GuestCpuState>(
+        &mut self,
+        host_descriptor: Option<&HVExtIntInfo>,
+        cpu_shared: &PerCpuShared,
+        cpu_state: &mut T,
+        caa_addr: Option<VirtAddr>,
+        vector: u8,
+    ) {
+        if let Some(descriptor) = host_descriptor {
+            self.consume_host_descriptor(descriptor);
+        }
+        if cpu_shared.ipi_pending() {
+            self.consume_pending_ipis(cpu_shared);
+        }
+        self.post_interrupt(vector, false);
+        self.process_pending_interrupts(cpu_state, caa_addr);
+    }
+
     pub fn configure_vector(&mut self, vector: u8, allowed: bool) {
         let index = (vector >> 5) as usize;
         let mask = 1 << (vector & 31);
@@ -699,17 +1035,30 @@ impl LocalApic {
         }
     }
 
-    pub fn consume_host_interrupts(&mut self) {
+    /// Returns the host interrupt descriptor of the guest VMPL if the host
+    /// has signaled events for it since the last call.
+    fn pending_host_descriptor() -> Option<&'static HVExtIntInfo> {
         let hv_doorbell = this_cpu().hv_doorbell().unwrap();
         let vmpl_event_mask = hv_doorbell.per_vmpl_events.swap(0, Ordering::Relaxed);
         // Ignore events other than for the guest VMPL.
         if vmpl_event_mask & (1 << (GUEST_VMPL - 1)) == 0 {
-            return;
+            return None;
         }
 
-        let descriptor = &hv_doorbell.per_vmpl[GUEST_VMPL - 1];
+        Some(&hv_doorbell.per_vmpl[GUEST_VMPL - 1])
+    }
 
-        // First consume any level-sensitive vector that is present.
+    pub fn consume_host_interrupts(&mut self) {
+        if let Some(descriptor) = Self::pending_host_descriptor() {
+            self.consume_host_descriptor(descriptor);
+        }
+    }
+
+    fn consume_host_descriptor(&mut self, descriptor: &HVExtIntInfo) {
+        // First consume any level-sensitive vector that is present.  A
+        // level-sensitive vector is presented again by the host after each
+        // host EOI for as long as the line remains asserted, and each
+        // presentation is posted as a new level-sensitive interrupt.
         let mut flags = HVExtIntStatus::from(descriptor.status.load(Ordering::Relaxed));
         if flags.level_sensitive() {
             let mut vector;
@@ -732,7 +1081,11 @@ impl LocalApic {
                 }
             }
 
+            let in_service = self.is_in_service(vector);
             if self.signal_one_host_interrupt(vector, true) {
+                if in_service {
+                    Self::insert_vector_register(&mut self.host_level_reasserted, vector);
+                }
                 Self::insert_vector_register(&mut self.host_tmr, vector);
             }
         }
@@ -870,3 +1223,444 @@ impl LocalApic {
             .expect("Failed to disable alterate injection");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicU32;
+
+    static LINE_ASSERTED: AtomicBool = AtomicBool::new(false);
+
+    fn test_level_source() -> bool {
+        LINE_ASSERTED.load(Ordering::Relaxed)
+    }
+
+    // Simulates delivery of the highest pending interrupt to the guest by
+    // moving it from the IRR onto the ISR stack.
+    fn deliver_next(apic: &mut LocalApic) -> u8 {
+        let irq = apic.scan_irr();
+        LocalApic::remove_vector_register(&mut apic.irr, irq);
+        apic.isr_stack[apic.isr_stack_index] = irq;
+        apic.isr_stack_index += 1;
+        irq
+    }
+
+    // Minimal guest CPU state that records how interrupts are presented.
+    #[derive(Debug, Default)]
+    struct TestCpuState {
+        tpr: u8,
+        delivered: Option<u8>,
+        queued: Option<u8>,
+    }
+
+    impl GuestCpuState for TestCpuState {
+        fn get_tpr(&self) -> u8 {
+            self.tpr
+        }
+        fn set_tpr(&mut self, tpr: u8) {
+            self.tpr = tpr;
+        }
+        fn request_nmi(&mut self) {}
+        fn queue_interrupt(&mut self, irq: u8) {
+            self.queued = Some(irq);
+        }
+        fn try_deliver_interrupt_immediately(&mut self, irq: u8) -> bool {
+            self.delivered = Some(irq);
+            true
+        }
+        fn in_intr_shadow(&self) -> bool {
+            false
+        }
+        fn interrupts_enabled(&self) -> bool {
+            true
+        }
+        fn check_and_clear_pending_nmi(&mut self) -> bool {
+            false
+        }
+        fn check_and_clear_pending_interrupt_event(&mut self) -> u8 {
+            self.delivered.take().unwrap_or(0)
+        }
+        fn check_and_clear_pending_virtual_interrupt(&mut self) -> u8 {
+            self.queued.take().unwrap_or(0)
+        }
+        fn disable_alternate_injection(&mut self) {}
+    }
+
+    #[test]
+    fn test_self_ipi_below_ppr() {
+        let mut apic = LocalApic::new();
+        let mut cpu_state = TestCpuState {
+            tpr: 0x80,
+            ..Default::default()
+        };
+
+        let cpu_shared = PerCpuShared::new(0);
+
+        // A self-IPI that does not exceed the TPR must be queued rather than
+        // delivered.
+        apic.post_self_ipi(None, &cpu_shared, &mut cpu_state, None, 0x40);
+        assert_eq!(cpu_state.delivered, None);
+        assert_eq!(cpu_state.queued, Some(0x40));
+
+        // A self-IPI above the TPR is delivered immediately.
+        let mut cpu_state = TestCpuState {
+            tpr: 0x80,
+            ..Default::default()
+        };
+        let mut apic = LocalApic::new();
+        apic.post_self_ipi(None, &cpu_shared, &mut cpu_state, None, 0x90);
+        assert_eq!(cpu_state.delivered, Some(0x90));
+        assert_eq!(cpu_state.queued, None);
+    }
+
+    #[test]
+    fn test_self_ipi_after_pending_interrupts() {
+        let mut apic = LocalApic::new();
+        apic.configure_vector(0x80, true);
+        let cpu_shared = PerCpuShared::new(0);
+        let descriptor = HVExtIntInfo {
+            status: AtomicU32::new(HVExtIntStatus::new().with_pending_vector(0x80).into()),
+            irr: Default::default(),
+            isr: Default::default(),
+        };
+
+        // A host interrupt signaled before the self-IPI has a higher
+        // priority and must be delivered first.
+        let mut cpu_state = TestCpuState::default();
+        apic.post_self_ipi(Some(&descriptor), &cpu_shared, &mut cpu_state, None, 0x40);
+        assert_eq!(cpu_state.delivered, Some(0x80));
+        assert!(LocalApic::test_vector_register(&apic.irr, 0x40));
+
+        // The same applies to an IPI sent by another CPU.
+        let mut apic = LocalApic::new();
+        let mut cpu_state = TestCpuState::default();
+        cpu_shared.request_ipi(0x90);
+        apic.post_self_ipi(None, &cpu_shared, &mut cpu_state, None, 0x40);
+        assert_eq!(cpu_state.delivered, Some(0x90));
+        assert!(LocalApic::test_vector_register(&apic.irr, 0x40));
+    }
+
+    #[test]
+    fn test_level_source_reassert() {
+        let mut apic = LocalApic::new();
+        apic.register_level_source(0x40, test_level_source);
+
+        // An EOI while the line is still asserted must post the interrupt
+        // again as a level-sensitive interrupt.
+        LINE_ASSERTED.store(true, Ordering::Relaxed);
+        apic.post_interrupt(0x40, true);
+        assert_eq!(deliver_next(&mut apic), 0x40);
+        apic.perform_eoi();
+        assert!(LocalApic::test_vector_register(&apic.irr, 0x40));
+        assert!(LocalApic::test_vector_register(&apic.tmr, 0x40));
+
+        // Once the line is deasserted, an EOI must retire the interrupt.
+        LINE_ASSERTED.store(false, Ordering::Relaxed);
+        assert_eq!(deliver_next(&mut apic), 0x40);
+        apic.perform_eoi();
+        assert!(!LocalApic::test_vector_register(&apic.irr, 0x40));
+        assert!(!LocalApic::test_vector_register(&apic.tmr, 0x40));
+        assert_eq!(apic.isr_stack_index, 0);
+    }
+
+    #[test]
+    fn test_scan_irr() {
+        let mut apic = LocalApic::new();
+        assert_eq!(apic.scan_irr(), 0);
+        for vector in [0x20, 0x7f, 0xff] {
+            apic.post_interrupt(vector, false);
+            assert_eq!(apic.scan_irr(), vector);
+        }
+    }
+
+    #[test]
+    fn test_ppr() {
+        let mut apic = LocalApic::new();
+        assert_eq!(apic.get_ppr_with_tpr(0), 0);
+        assert_eq!(apic.get_ppr_with_tpr(0x30), 0x30);
+
+        // The PPR follows the highest priority in-service interrupt unless
+        // the TPR is of a higher priority class.
+        for vector in [0x41, 0x62] {
+            apic.post_interrupt(vector, false);
+            deliver_next(&mut apic);
+        }
+        assert_eq!(apic.get_ppr_with_tpr(0), 0x60);
+        assert_eq!(apic.get_ppr_with_tpr(0x65), 0x65);
+        assert_eq!(apic.get_ppr_with_tpr(0x70), 0x70);
+
+        apic.perform_eoi();
+        assert_eq!(apic.get_ppr_with_tpr(0), 0x40);
+    }
+
+    #[test]
+    fn test_isr_stack_full() {
+        let mut apic = LocalApic::new();
+        let mut cpu_state = TestCpuState::default();
+
+        // Each interrupt of a higher priority class nests on top of the ones
+        // already in service, one ISR stack entry per class.
+        for class in 1..=15u8 {
+            let vector = class << 4;
+            apic.post_interrupt(vector, false);
+            apic.process_pending_interrupts(&mut cpu_state, None);
+            assert_eq!(cpu_state.delivered, Some(vector));
+            assert_eq!(apic.isr_stack_index, usize::from(class));
+            // Simulate acceptance of the interrupt by the guest.
+            cpu_state.delivered = None;
+        }
+
+        // Once the priority classes run out, nothing else can nest.
+        apic.post_interrupt(0xff, false);
+        apic.process_pending_interrupts(&mut cpu_state, None);
+        assert_eq!(cpu_state.delivered, None);
+        assert_eq!(cpu_state.queued, None);
+        assert_eq!(apic.isr_stack_index, 15);
+
+        // Once the ISR stack is full, a higher priority interrupt stays
+        // pending in the IRR.
+        let mut apic = LocalApic::new();
+        for _ in 0..apic.isr_stack.len() {
+            apic.post_interrupt(0x20, false);
+            deliver_next(&mut apic);
+        }
+        apic.post_interrupt(0xf0, false);
+        apic.process_pending_interrupts(&mut cpu_state, None);
+        assert_eq!(cpu_state.delivered, None);
+        assert_eq!(cpu_state.queued, None);
+        assert!(LocalApic::test_vector_register(&apic.irr, 0xf0));
+        assert_eq!(apic.isr_stack_index, apic.isr_stack.len());
+    }
+
+    #[test]
+    fn test_host_level_reassert() {
+        let mut apic = LocalApic::new();
+        apic.configure_vector(0x50, true);
+        let descriptor = HVExtIntInfo {
+            status: AtomicU32::new(0),
+            irr: Default::default(),
+            isr: Default::default(),
+        };
+        let assert_line = || {
+            let flags = HVExtIntStatus::new()
+                .with_pending_vector(0x50)
+                .with_level_sensitive(true);
+            descriptor.status.store(flags.into(), Ordering::Relaxed);
+        };
+
+        // Each assertion of the line is posted as a level-sensitive host
+        // interrupt and its EOI is forwarded to the host.
+        for _ in 0..2 {
+            assert_line();
+            apic.consume_host_descriptor(&descriptor);
+            assert_eq!(descriptor.status.load(Ordering::Relaxed), 0);
+            assert!(LocalApic::test_vector_register(&apic.host_tmr, 0x50));
+            assert_eq!(deliver_next(&mut apic), 0x50);
+            assert_eq!(apic.retire_in_service(), Some(0x50));
+            assert!(!LocalApic::test_vector_register(&apic.tmr, 0x50));
+            assert!(!LocalApic::test_vector_register(&apic.host_tmr, 0x50));
+        }
+
+        // A reassertion while the previous instance is still in service must
+        // remain level-sensitive after the first EOI, so the host also
+        // receives an EOI for the second instance.
+        assert_line();
+        apic.consume_host_descriptor(&descriptor);
+        assert_eq!(deliver_next(&mut apic), 0x50);
+        assert_line();
+        apic.consume_host_descriptor(&descriptor);
+        assert_eq!(apic.retire_in_service(), Some(0x50));
+        assert!(LocalApic::test_vector_register(&apic.irr, 0x50));
+        assert!(LocalApic::test_vector_register(&apic.host_tmr, 0x50));
+        assert_eq!(deliver_next(&mut apic), 0x50);
+        assert_eq!(apic.retire_in_service(), Some(0x50));
+        assert!(!LocalApic::test_vector_register(&apic.host_tmr, 0x50));
+        assert_eq!(apic.isr_stack_index, 0);
+
+        // An edge-triggered instance pending while a level-sensitive one is
+        // in service must not inherit its trigger mode.
+        assert_line();
+        apic.consume_host_descriptor(&descriptor);
+        assert_eq!(deliver_next(&mut apic), 0x50);
+        apic.post_interrupt(0x50, false);
+        assert_eq!(apic.retire_in_service(), Some(0x50));
+        assert!(!LocalApic::test_vector_register(&apic.tmr, 0x50));
+        assert!(!LocalApic::test_vector_register(&apic.host_tmr, 0x50));
+        assert_eq!(deliver_next(&mut apic), 0x50);
+        assert_eq!(apic.retire_in_service(), None);
+
+        // A level reassertion is not swallowed by an edge-triggered
+        // instance that is already pending in the IRR.
+        assert_line();
+        apic.consume_host_descriptor(&descriptor);
+        assert_eq!(deliver_next(&mut apic), 0x50);
+        apic.post_interrupt(0x50, false);
+        assert_line();
+        apic.consume_host_descriptor(&descriptor);
+        assert_eq!(apic.retire_in_service(), Some(0x50));
+        assert!(LocalApic::test_vector_register(&apic.tmr, 0x50));
+        assert!(LocalApic::test_vector_register(&apic.host_tmr, 0x50));
+        assert_eq!(deliver_next(&mut apic), 0x50);
+        assert_eq!(apic.retire_in_service(), Some(0x50));
+        assert!(!LocalApic::test_vector_register(&apic.host_tmr, 0x50));
+        assert_eq!(apic.isr_stack_index, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "apic-stats")]
+    fn test_vector_stats() {
+        let mut apic = LocalApic::new();
+        for _ in 0..3 {
+            apic.post_interrupt(0x40, false);
+        }
+        deliver_next(&mut apic);
+        apic.perform_eoi();
+        assert_eq!(apic.vector_count(0x40), 3);
+        assert_eq!(apic.eoi_count(0x40), 1);
+        assert_eq!(apic.vector_count(0x41), 0);
+
+        apic.reset_stats();
+        assert_eq!(apic.vector_count(0x40), 0);
+        assert_eq!(apic.eoi_count(0x40), 0);
+    }
+
+    #[test]
+    fn test_apic_register_offsets() {
+        assert_eq!(ApicRegister::from_offset(0x808), Some(ApicRegister::Tpr));
+        assert_eq!(
+            ApicRegister::from_offset(0x83f),
+            Some(ApicRegister::SelfIpi)
+        );
+        assert_eq!(ApicRegister::from_offset(0x810), Some(ApicRegister::Isr(0)));
+        assert_eq!(ApicRegister::from_offset(0x813), Some(ApicRegister::Isr(3)));
+        assert_eq!(ApicRegister::from_offset(0x827), Some(ApicRegister::Irr(7)));
+        assert_eq!(ApicRegister::from_offset(0x828), None);
+    }
+
+    #[test]
+    fn test_is_in_service() {
+        let mut apic = LocalApic::new();
+        for vector in [0x30, 0x50, 0x80] {
+            apic.post_interrupt(vector, false);
+            deliver_next(&mut apic);
+        }
+
+        assert!(apic.is_in_service(0x30));
+        assert!(apic.is_in_service(0x50));
+        assert!(apic.is_in_service(0x80));
+        assert!(!apic.is_in_service(0x40));
+        assert!(!apic.is_in_service(0));
+
+        // EOI retires the highest priority in-service vector first.
+        apic.perform_eoi();
+        assert!(!apic.is_in_service(0x80));
+        assert!(apic.is_in_service(0x50));
+        assert!(apic.is_in_service(0x30));
+    }
+
+    #[test]
+    fn test_logical_destination_flat() {
+        let matches = |dest| LocalApic::logical_destination_match(dest, 0, 0x0400_0000, !0);
+        assert!(matches(0x0400_0000));
+        assert!(matches(0x0600_0000));
+        assert!(!matches(0x0300_0000));
+        assert!(matches(0xFF00_0000));
+    }
+
+    #[test]
+    fn test_logical_destination_cluster() {
+        let matches =
+            |dest| LocalApic::logical_destination_match(dest, 0, 0x2200_0000, 0x0FFF_FFFF);
+        assert!(matches(0x2200_0000));
+        assert!(matches(0x2300_0000));
+        assert!(!matches(0x2100_0000));
+        assert!(!matches(0x1200_0000));
+        assert!(matches(0xFF00_0000));
+    }
+
+    #[test]
+    fn test_logical_destination_x2apic() {
+        // Without a programmed LDR, the logical ID is derived from the APIC
+        // ID.
+        let matches = |dest| LocalApic::logical_destination_match(dest, 0x12, 0, !0);
+        assert!(matches(0x0001_0004));
+        assert!(!matches(0x0000_0004));
+        assert!(!matches(0x0001_0002));
+    }
+
+    #[test]
+    fn test_logical_destination_broadcast() {
+        // The x2APIC broadcast destination must reach every CPU, regardless
+        // of how its logical ID is configured.
+        let cpus = [
+            (0x00, 0, !0),
+            (0x12, 0, !0),
+            (0x3F, 0, !0),
+            (0x01, 0x0400_0000, !0),
+            (0x02, 0x2200_0000, 0x0FFF_FFFF),
+        ];
+        for (apic_id, ldr, dfr) in cpus {
+            assert!(LocalApic::logical_destination_match(
+                0xFFFF_FFFF,
+                apic_id,
+                ldr,
+                dfr
+            ));
+        }
+    }
+
+    #[test]
+    fn test_remote_read_unsupported() {
+        let mut apic = LocalApic::new();
+        let icr = ApicIcr::new()
+            .with_vector(0x80)
+            .with_message_type(IcrMessageType::RemoteRead)
+            .with_assert(true)
+            .with_destination(1);
+        assert_eq!(
+            apic.handle_icr_write(icr.into()),
+            Err(ApicError::UnsupportedMessageType)
+        );
+    }
+
+    #[test]
+    fn test_init_sipi_unsupported() {
+        let mut apic = LocalApic::new();
+        apic.post_interrupt(0x40, false);
+        deliver_next(&mut apic);
+        apic.ldr = 0x0100_0000;
+
+        for message_type in [IcrMessageType::Init, IcrMessageType::Sipi] {
+            let icr = ApicIcr::new()
+                .with_vector(0x9a)
+                .with_message_type(message_type)
+                .with_assert(true)
+                .with_destination(1);
+            assert_eq!(
+                apic.handle_icr_write(icr.into()),
+                Err(ApicError::UnsupportedMessageType)
+            );
+        }
+
+        // The rejected writes leave the APIC state untouched.
+        assert_eq!(apic.isr_stack_index, 1);
+        assert_eq!(apic.ldr, 0x0100_0000);
+    }
+
+    #[test]
+    fn test_icr_read_back() {
+        let mut apic = LocalApic::new();
+        assert_eq!(apic.read_icr(), 0);
+
+        // A rejected write is still reflected in the ICR, with the delivery
+        // status reported as idle.
+        let icr = ApicIcr::new()
+            .with_vector(0x40)
+            .with_delivery_status(true)
+            .with_trigger_mode(true)
+            .with_assert(true)
+            .with_destination(3);
+        assert_eq!(apic.handle_icr_write(icr.into()), Err(ApicError::ApicError));
+        assert_eq!(apic.read_icr(), u64::from(icr.with_delivery_status(false)));
+    }
+}
@@ -8,6 +8,14 @@ use super::features::cpu_has_pge;
 use crate::address::{Address, PhysAddr};
 use bitflags::bitflags;
 use core::arch::asm;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Tracks whether SMAP has been enabled through [`write_cr4`], so that
+/// [`SmapGuard`] only issues `stac`/`clac` when they are both valid and
+/// required.  A single global flag suffices because all CPUs run with the
+/// same CR4: APs inherit the CR4 value of the BSP when they are started, and
+/// CR4 is only modified during BSP initialization.
+static SMAP_ENABLED: AtomicBool = AtomicBool::new(false);
 
 pub fn cr0_init() {
     let mut cr0 = read_cr0();
@@ -105,6 +113,33 @@ pub fn write_cr3(cr3: PhysAddr) {
     }
 }
 
+/// Reads the task priority register.  Only the low four bits of CR8, which
+/// correspond to bits 7:4 of the APIC TPR, are meaningful; the value is
+/// returned in CR8 format.
+pub fn read_cr8() -> u8 {
+    let ret: u64;
+    unsafe {
+        asm!("mov %cr8, %rax",
+             out("rax") ret,
+             options(att_syntax));
+    }
+    ret as u8
+}
+
+/// Writes the task priority register.  Only the low four bits of `tpr` are
+/// meaningful, corresponding to the priority class in bits 7:4 of the APIC
+/// TPR.
+///
+/// # Safety
+///
+/// Raising the task priority masks interrupts, and lowering it can unmask
+/// interrupts the caller is not prepared to handle.
+pub unsafe fn write_cr8(tpr: u8) {
+    asm!("mov %rax, %cr8",
+         in("rax") u64::from(tpr & 0xf),
+         options(att_syntax));
+}
+
 bitflags! {
     pub struct CR4Flags: u64 {
         const VME       = 1 << 0;  // Virtual-8086 Mode Extensions
@@ -151,4 +186,67 @@ pub fn write_cr4(cr4: CR4Flags) {
              in("rax") reg,
              options(att_syntax));
     }
+
+    SMAP_ENABLED.store(cr4.contains(CR4Flags::SMAP), Ordering::Relaxed);
+}
+
+/// Sets RFLAGS.AC to permit supervisor access to user pages while SMAP is
+/// enabled.
+///
+/// # Safety
+///
+/// The CPU must support SMAP, and the caller must restore protection with
+/// [`clac`] once the access is complete.
+#[inline]
+pub unsafe fn stac() {
+    // The asm is not marked `nomem`, so that it acts as a compiler barrier
+    // and user memory accesses cannot be moved out of the permitted window.
+    asm!("stac", options(att_syntax, nostack));
+}
+
+/// Clears RFLAGS.AC to re-establish SMAP protection.
+///
+/// # Safety
+///
+/// The CPU must support SMAP.
+#[inline]
+pub unsafe fn clac() {
+    asm!("clac", options(att_syntax, nostack));
+}
+
+/// A scoped guard which permits supervisor access to user pages for its
+/// lifetime.  It has no effect unless SMAP has been enabled.
+#[derive(Debug)]
+#[must_use = "if unused SMAP protection is restored immediately"]
+pub struct SmapGuard {
+    active: bool,
+}
+
+impl SmapGuard {
+    #[inline]
+    pub fn new() -> Self {
+        let active = SMAP_ENABLED.load(Ordering::Relaxed);
+        if active {
+            // SAFETY: SMAP is enabled, so the CPU supports stac, and the
+            // matching clac is issued when the guard is dropped.
+            unsafe { stac() };
+        }
+        Self { active }
+    }
+}
+
+impl Default for SmapGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for SmapGuard {
+    #[inline]
+    fn drop(&mut self) {
+        if self.active {
+            // SAFETY: the guard only becomes active if SMAP is supported.
+            unsafe { clac() };
+        }
+    }
 }
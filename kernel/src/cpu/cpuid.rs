@@ -4,8 +4,9 @@
 //
 // Author: Joerg Roedel <jroedel@suse.de>
 
+use crate::error::SvsmError;
 use crate::utils::immut_after_init::ImmutAfterInitRef;
-use cpuarch::snp_cpuid::SnpCpuidTable;
+use cpuarch::snp_cpuid::{SnpCpuidFn, SnpCpuidTable};
 
 use core::arch::asm;
 
@@ -77,6 +78,44 @@ impl CpuidResult {
     }
 }
 
+/// Mask of the C-bit position field in EBX of CPUID leaf 0x8000001F.
+pub const CPUID_C_BIT_POSITION_MASK: u32 = 0x3f;
+
+/// Extracts the page table C-bit position from the result of CPUID leaf
+/// 0x8000001F.  The stage 2 boot code applies the same mask when it reads
+/// the C-bit position from the CPUID page, so the two must be kept in sync.
+pub fn c_bit_position(cpuid: &CpuidResult) -> u8 {
+    (cpuid.ebx & CPUID_C_BIT_POSITION_MASK) as u8
+}
+
+/// Computes the TSC frequency in kHz from CPUID leaves 0x15 (TSC/crystal
+/// clock ratio) and 0x16 (processor base frequency).  Leaf 0x15 is preferred
+/// when it fully describes the TSC frequency.  Returns `None` if neither leaf
+/// reports a usable value.
+pub fn tsc_khz_from_cpuid(
+    leaf_15: Option<CpuidResult>,
+    leaf_16: Option<CpuidResult>,
+) -> Option<u32> {
+    if let Some(leaf) = leaf_15 {
+        // EAX and EBX hold the denominator and numerator of the TSC/crystal
+        // clock ratio, and ECX holds the crystal clock frequency in Hz.
+        if leaf.eax != 0 && leaf.ebx != 0 && leaf.ecx != 0 {
+            let hz = u64::from(leaf.ecx) * u64::from(leaf.ebx) / u64::from(leaf.eax);
+            if let Ok(khz) = u32::try_from(hz / 1000) {
+                return Some(khz);
+            }
+        }
+    }
+
+    // EAX[15:0] of leaf 0x16 holds the base frequency in MHz.
+    let mhz = leaf_16?.eax & 0xFFFF;
+    if mhz != 0 {
+        Some(mhz * 1000)
+    } else {
+        None
+    }
+}
+
 pub fn cpuid_table_raw(eax: u32, ecx: u32, xcr0: u64, xss: u64) -> Option<CpuidResult> {
     let count: usize = CPUID_PAGE.count as usize;
 
@@ -98,8 +137,119 @@ pub fn cpuid_table_raw(eax: u32, ecx: u32, xcr0: u64, xss: u64) -> Option<CpuidR
     None
 }
 
+/// Looks up subleaf `ecx` of leaf `eax` in the CPUID table, for entries that
+/// do not depend on XCR0 or IA32_XSS.
+pub fn cpuid_table_subleaf(eax: u32, ecx: u32) -> Option<CpuidResult> {
+    cpuid_table_raw(eax, ecx, 0, 0)
+}
+
+/// Looks up subleaf 0 of leaf `eax` in the CPUID table.  Leaves with
+/// meaningful subleaves must be queried through [`cpuid_table_subleaf`].
 pub fn cpuid_table(eax: u32) -> Option<CpuidResult> {
-    cpuid_table_raw(eax, 0, 0, 0)
+    cpuid_table_subleaf(eax, 0)
+}
+
+/// Upper bound on the number of subleaves enumerated for any single leaf, to
+/// protect against CPUID implementations that never report a final subleaf.
+const CPUID_MAX_SUBLEAVES: u32 = 32;
+
+/// Fills `table` with the native CPUID values of all standard and extended
+/// leaves, so that platforms without a hypervisor-provided CPUID page can use
+/// the same [`cpuid_table`] lookups as SNP.
+///
+/// Subleaf 0 of every leaf is recorded before any further subleaves, so that
+/// when the table is too small to hold everything only the less commonly
+/// used subleaves are dropped.  Only the XSAVE components enabled in XCR0
+/// are enumerated for leaf 0xD.  If any entry does not fit, the table is left
+/// holding the entries that did and an error is returned.
+pub fn populate_cpuid_table(table: &mut SnpCpuidTable) -> Result<(), SvsmError> {
+    populate_cpuid_table_with(table, CpuidResult::get)
+}
+
+fn populate_cpuid_table_with<F>(table: &mut SnpCpuidTable, mut query: F) -> Result<(), SvsmError>
+where
+    F: FnMut(u32, u32) -> CpuidResult,
+{
+    let mut ranges = [(0u32, 0u32); 2];
+    for (range, base) in ranges.iter_mut().zip([0u32, 0x8000_0000]) {
+        let max_leaf = query(base, 0).eax;
+        if max_leaf >= base {
+            *range = (base, (max_leaf - base).saturating_add(1));
+        }
+    }
+    let leaves = || {
+        ranges
+            .into_iter()
+            .flat_map(|(base, count)| (0..count).map(move |i| base + i))
+    };
+
+    table.count = 0;
+    let mut add = |leaf: u32, subleaf: u32, result: CpuidResult| {
+        let count = table.count as usize;
+        let entry = table.func.get_mut(count).ok_or(SvsmError::Mem)?;
+        *entry = SnpCpuidFn {
+            eax_in: leaf,
+            ecx_in: subleaf,
+            eax_out: result.eax,
+            ebx_out: result.ebx,
+            ecx_out: result.ecx,
+            edx_out: result.edx,
+            ..Default::default()
+        };
+        table.count += 1;
+        Ok(())
+    };
+
+    for leaf in leaves() {
+        add(leaf, 0, query(leaf, 0))?;
+    }
+
+    for leaf in leaves() {
+        let first = query(leaf, 0);
+        for subleaf in 1..CPUID_MAX_SUBLEAVES {
+            let result = match leaf {
+                // Deterministic cache parameters, terminated by a null
+                // cache type.
+                4 | 0x8000_001D => {
+                    let result = query(leaf, subleaf);
+                    if result.eax & 0x1f == 0 {
+                        break;
+                    }
+                    result
+                }
+                // Structured extended features, with the maximum subleaf
+                // reported in EAX of subleaf 0.
+                7 => {
+                    if subleaf > first.eax {
+                        break;
+                    }
+                    query(leaf, subleaf)
+                }
+                // Extended topology, terminated by an invalid level type.
+                0xB => {
+                    let result = query(leaf, subleaf);
+                    if (result.ecx >> 8) & 0xff == 0 {
+                        break;
+                    }
+                    result
+                }
+                // XSAVE state components.  Subleaf 1 is always present;
+                // beyond that only the components supported in XCR0 are
+                // recorded.
+                0xD => {
+                    let xcr0 = u64::from(first.eax) | (u64::from(first.edx) << 32);
+                    if subleaf != 1 && xcr0 & (1u64 << subleaf) == 0 {
+                        continue;
+                    }
+                    query(leaf, subleaf)
+                }
+                _ => break,
+            };
+            add(leaf, subleaf, result)?;
+        }
+    }
+
+    Ok(())
 }
 
 pub fn dump_cpuid_table() {
@@ -120,3 +270,132 @@ pub fn dump_cpuid_table() {
                     eax_in, ecx_in, xcr0_in, xss_in, eax_out, ebx_out, ecx_out, edx_out);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_c_bit_position() {
+        // EBX[5:0] holds the C-bit position and EBX[11:6] holds the number
+        // of physical address bits lost to memory encryption.
+        let cpuid = CpuidResult {
+            eax: 0x0001_0003,
+            ebx: 0x0000_4173,
+            ecx: 0,
+            edx: 0,
+        };
+        assert_eq!(c_bit_position(&cpuid), 51);
+    }
+
+    #[test]
+    fn test_tsc_khz_from_cpuid() {
+        let leaf = |eax, ebx, ecx| {
+            Some(CpuidResult {
+                eax,
+                ebx,
+                ecx,
+                edx: 0,
+            })
+        };
+
+        // A 25 MHz crystal with a ratio of 2/120 yields 1.5 GHz.
+        assert_eq!(
+            tsc_khz_from_cpuid(leaf(2, 120, 25_000_000), leaf(2000, 0, 0)),
+            Some(1_500_000)
+        );
+        // Without a crystal frequency, fall back to the base frequency.
+        assert_eq!(
+            tsc_khz_from_cpuid(leaf(2, 120, 0), leaf(2000, 0, 0)),
+            Some(2_000_000)
+        );
+        assert_eq!(tsc_khz_from_cpuid(None, leaf(2000, 0, 0)), Some(2_000_000));
+        assert_eq!(tsc_khz_from_cpuid(leaf(0, 0, 0), leaf(0, 0, 0)), None);
+        assert_eq!(tsc_khz_from_cpuid(None, None), None);
+    }
+
+    #[test]
+    fn test_populate_cpuid_table() {
+        let query = |leaf: u32, subleaf: u32| {
+            let (eax, ecx) = match (leaf, subleaf) {
+                (0, _) => (0xD, 0),
+                (4, 0..=2) => (subleaf + 1, 0),
+                (7, 0) => (1, 0),
+                (0xB, 0..=1) => (0, (subleaf + 1) << 8),
+                (0xD, 0) => (0x7, 0),
+                (0x8000_0000, _) => (0x8000_0001, 0),
+                _ => (0, 0),
+            };
+            CpuidResult {
+                eax,
+                ebx: subleaf,
+                ecx,
+                edx: 0,
+            }
+        };
+
+        let mut table = SnpCpuidTable::default();
+        populate_cpuid_table_with(&mut table, query).unwrap();
+
+        // Leaves 0-0xD plus two extra subleaves of leaf 4, one of leaf 7,
+        // one of leaf 0xB and two of leaf 0xD, followed by the two extended
+        // leaves.
+        let count = table.count;
+        assert_eq!(count, 14 + 2 + 1 + 1 + 2 + 2);
+        let entries = &table.func[..count as usize];
+        let contains = |leaf, subleaf| {
+            entries
+                .iter()
+                .any(|f| (f.eax_in, f.ecx_in) == (leaf, subleaf))
+        };
+        assert!(contains(4, 2));
+        assert!(!contains(4, 3));
+        assert!(contains(7, 1));
+        assert!(contains(0xB, 1));
+        assert!(!contains(0xB, 2));
+        assert!(contains(0xD, 1));
+        assert!(contains(0xD, 2));
+        assert!(!contains(0xD, 3));
+        assert!(contains(0x8000_0001, 0));
+    }
+
+    #[test]
+    fn test_populate_cpuid_table_overflow() {
+        let query = |leaf: u32, _| CpuidResult {
+            eax: if leaf == 0 { 0x1000 } else { 0 },
+            ebx: 0,
+            ecx: 0,
+            edx: 0,
+        };
+        let mut table = SnpCpuidTable::default();
+        assert!(populate_cpuid_table_with(&mut table, query).is_err());
+        let count = table.count as usize;
+        assert_eq!(count, table.func.len());
+    }
+
+    #[test]
+    fn test_populate_cpuid_table_truncates_subleaves() {
+        // Enough leaves to nearly fill the table, each with many subleaves.
+        let query = |leaf: u32, subleaf: u32| CpuidResult {
+            eax: match (leaf, subleaf) {
+                (0, _) => 0x3f,
+                (4, _) => 1,
+                _ => 0,
+            },
+            ebx: 0,
+            ecx: 0,
+            edx: 0,
+        };
+        let mut table = SnpCpuidTable::default();
+        assert!(populate_cpuid_table_with(&mut table, query).is_err());
+
+        // Every leaf made it into the table even though the subleaves of
+        // leaf 4 did not all fit.
+        let count = table.count as usize;
+        assert_eq!(count, table.func.len());
+        let entries = &table.func[..count];
+        for leaf in 0..0x40 {
+            assert!(entries.iter().any(|f| (f.eax_in, f.ecx_in) == (leaf, 0)));
+        }
+    }
+}
@@ -15,6 +15,7 @@ pub mod idt;
 pub mod msr;
 pub mod percpu;
 pub mod registers;
+pub mod rng;
 pub mod smp;
 pub mod tlb;
 pub mod tss;
@@ -28,7 +28,9 @@ use crate::mm::{
 use crate::platform::{SvsmPlatform, SVSM_PLATFORM};
 use crate::sev::ghcb::GHCB;
 use crate::sev::hv_doorbell::HVDoorbell;
-use crate::sev::msr_protocol::{hypervisor_ghcb_features, GHCBHvFeatures};
+use crate::sev::msr_protocol::{
+    ghcb_supports, hypervisor_ghcb_features, GHCBHvFeatures, GhcbFeature,
+};
 use crate::sev::utils::RMPFlags;
 use crate::sev::vmsa::{allocate_new_vmsa, VMSAControl};
 use crate::task::{schedule, schedule_task, RunQueue, Task, TaskPointer, WaitQueue};
@@ -89,6 +91,19 @@ impl PerCpuAreas {
         ptr.push(info);
     }
 
+    /// Removes the most recently pushed area if it belongs to `apic_id`.
+    /// Only the last entry is ever removed, so the storage of the remaining
+    /// entries is not moved.
+    unsafe fn pop(&self, apic_id: u32) -> bool {
+        let ptr = self.areas.get().as_mut().unwrap();
+        if ptr.last().is_some_and(|info| info.apic_id == apic_id) {
+            ptr.pop();
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn iter(&self) -> Iter<'_, PerCpuInfo> {
         let ptr = unsafe { self.areas.get().as_ref().unwrap() };
         ptr.iter()
@@ -105,6 +120,32 @@ impl PerCpuAreas {
             .find(|info| info.apic_id == apic_id)
             .map(|info| info.cpu_shared)
     }
+
+    /// Like [`get()`](Self::get), but reports a missing CPU as an error that
+    /// names the APIC ID that was looked up.
+    pub fn try_get_by_apic_id(&self, apic_id: u32) -> Result<&'static PerCpuShared, SvsmError> {
+        self.get(apic_id).ok_or(SvsmError::UnknownApicId(apic_id))
+    }
+
+    /// Iterates over the CPUs that have come online.
+    ///
+    /// Each CPU's online flag is read with acquire ordering when the
+    /// iterator reaches it, pairing with the release store in
+    /// [`PerCpuShared::set_online()`], so that all setup performed by a CPU
+    /// before it went online is visible for each CPU yielded.  The result is
+    /// only a snapshot: a CPU that comes online concurrently may or may not
+    /// be included.
+    pub fn online_cpus(&self) -> impl Iterator<Item = &'static PerCpuShared> + '_ {
+        self.iter()
+            .map(|info| info.cpu_shared)
+            .filter(|cpu| cpu.is_online())
+    }
+
+    /// Returns the number of CPUs that have come online, with the same
+    /// snapshot semantics as [`online_cpus()`](Self::online_cpus).
+    pub fn online_count(&self) -> usize {
+        self.online_cpus().count()
+    }
 }
 
 #[derive(Debug)]
@@ -194,10 +235,12 @@ pub struct PerCpuShared {
     ipi_irr: [AtomicU32; 8],
     ipi_pending: AtomicBool,
     nmi_pending: AtomicBool,
+    apic_ldr: AtomicU32,
+    apic_dfr: AtomicU32,
 }
 
 impl PerCpuShared {
-    fn new(apic_id: u32) -> Self {
+    pub fn new(apic_id: u32) -> Self {
         PerCpuShared {
             apic_id,
             guest_vmsa: SpinLock::new(GuestVmsaRef::new()),
@@ -205,6 +248,8 @@ impl PerCpuShared {
             ipi_irr: core::array::from_fn(|_| AtomicU32::new(0)),
             ipi_pending: AtomicBool::new(false),
             nmi_pending: AtomicBool::new(false),
+            apic_ldr: AtomicU32::new(0),
+            apic_dfr: AtomicU32::new(0xFFFF_FFFF),
         }
     }
 
@@ -256,7 +301,9 @@ impl PerCpuShared {
         self.ipi_pending.store(true, Ordering::Release);
     }
 
-    pub fn request_nmi(&self) {
+    /// Requests delivery of an NMI to this CPU.  The NMI is presented to
+    /// the guest the next time this CPU processes pending IPIs.
+    pub fn post_nmi(&self) {
         self.nmi_pending.store(true, Ordering::Relaxed);
         self.ipi_pending.store(true, Ordering::Release);
     }
@@ -272,6 +319,23 @@ impl PerCpuShared {
     pub fn nmi_pending(&self) -> bool {
         self.nmi_pending.swap(false, Ordering::Relaxed)
     }
+
+    /// Publishes the logical destination (LDR and DFR values) programmed
+    /// into the emulated APIC of this CPU so that other CPUs can match
+    /// logical IPI destinations against it.
+    pub fn set_apic_logical_destination(&self, ldr: u32, dfr: u32) {
+        self.apic_ldr.store(ldr, Ordering::Relaxed);
+        self.apic_dfr.store(dfr, Ordering::Relaxed);
+    }
+
+    /// Returns the LDR and DFR values programmed into the emulated APIC of
+    /// this CPU.
+    pub fn apic_logical_destination(&self) -> (u32, u32) {
+        (
+            self.apic_ldr.load(Ordering::Relaxed),
+            self.apic_dfr.load(Ordering::Relaxed),
+        )
+    }
 }
 
 const _: () = assert!(size_of::<PerCpu>() <= PAGE_SIZE);
@@ -354,6 +418,20 @@ impl PerCpu {
         }
     }
 
+    /// Removes a CPU that failed to start from [`PERCPU_AREAS`], so that it
+    /// is no longer a target for IPIs or guest vCPU creation.  This must be
+    /// the most recently allocated CPU.  The per-CPU area itself is leaked,
+    /// since the CPU may still be executing with it.
+    pub fn unregister(&self) {
+        let apic_id = self.shared.apic_id;
+        let removed = unsafe { PERCPU_AREAS.pop(apic_id) };
+        assert!(
+            removed,
+            "APIC-ID {} is not the last registered CPU",
+            apic_id
+        );
+    }
+
     pub fn shared(&self) -> &PerCpuShared {
         &self.shared
     }
@@ -479,7 +557,9 @@ impl PerCpu {
     pub fn configure_hv_doorbell(&self) -> Result<(), SvsmError> {
         // #HV doorbell configuration is only required if this system will make
         // use of restricted injection.
-        if hypervisor_ghcb_features().contains(GHCBHvFeatures::SEV_SNP_RESTR_INJ) {
+        if hypervisor_ghcb_features().contains(GHCBHvFeatures::SEV_SNP_RESTR_INJ)
+            && ghcb_supports(GhcbFeature::HvDoorbell)
+        {
             self.setup_hv_doorbell()?;
         }
         Ok(())
@@ -758,7 +838,7 @@ impl PerCpu {
         // enabled, so the unwrap below is appropriate.
         self.apic_mut()
             .unwrap()
-            .write_register(vmsa, caa_addr, register, value)
+            .write_register(self.shared(), vmsa, caa_addr, register, value)
     }
 
     pub fn configure_apic_vector(&self, vector: u8, allowed: bool) {
@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) Microsoft Corporation
+//
+// Author: agent <agent@local>
+
+use crate::error::SvsmError;
+use core::arch::asm;
+
+/// CPUID.01H:ECX[30] reports support for RDRAND.
+pub const X86_FEATURE_RDRAND: u32 = 30;
+
+/// The number of times a hardware random number instruction is retried
+/// before the entropy source is considered to have failed, as recommended by
+/// the Intel DRNG software implementation guide.
+const RNG_RETRY_COUNT: usize = 10;
+
+/// Executes RDRAND once, returning `None` if no random value was available.
+pub fn rdrand64() -> Option<u64> {
+    let value: u64;
+    let ok: u8;
+    // SAFETY: RDRAND has no memory side effects.
+    unsafe {
+        asm!("rdrand {0}",
+             "setc {1}",
+             out(reg) value,
+             out(reg_byte) ok,
+             options(att_syntax, nomem, nostack));
+    }
+    (ok != 0).then_some(value)
+}
+
+/// Fills `buf` with random data obtained from `source`, retrying each
+/// request up to [`RNG_RETRY_COUNT`] times.  Either the entire buffer is
+/// filled, or an error is returned; no partially predictable data is ever
+/// reported as success.
+pub fn fill_random<F>(buf: &mut [u8], mut source: F) -> Result<(), SvsmError>
+where
+    F: FnMut() -> Option<u64>,
+{
+    for chunk in buf.chunks_mut(8) {
+        let value = (0..RNG_RETRY_COUNT)
+            .find_map(|_| source())
+            .ok_or(SvsmError::Rng)?;
+        chunk.copy_from_slice(&value.to_ne_bytes()[..chunk.len()]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_random_partial_chunk() {
+        let mut next = 0x0101_0101_0101_0101u64;
+        let mut buf = [0u8; 13];
+        fill_random(&mut buf, || {
+            next += 1;
+            Some(next)
+        })
+        .unwrap();
+        assert_eq!(buf[..8], 0x0101_0101_0101_0102u64.to_ne_bytes());
+        assert_eq!(buf[8..], 0x0101_0101_0101_0103u64.to_ne_bytes()[..5]);
+    }
+
+    #[test]
+    fn test_fill_random_retries() {
+        let mut attempts = 0;
+        let mut buf = [0u8; 8];
+        fill_random(&mut buf, || {
+            attempts += 1;
+            (attempts == RNG_RETRY_COUNT).then_some(!0)
+        })
+        .unwrap();
+        assert_eq!(buf, [0xFF; 8]);
+    }
+
+    #[test]
+    fn test_fill_random_failure() {
+        let mut buf = [0u8; 16];
+        assert!(matches!(
+            fill_random(&mut buf, || None),
+            Err(SvsmError::Rng)
+        ));
+    }
+}
@@ -5,7 +5,7 @@
 // Author: Joerg Roedel <jroedel@suse.de>
 
 use crate::acpi::tables::ACPICPUInfo;
-use crate::cpu::percpu::{current_ghcb, this_cpu, this_cpu_shared, PerCpu};
+use crate::cpu::percpu::{this_cpu, this_cpu_shared, PerCpu, PERCPU_AREAS};
 use crate::error::SvsmError;
 use crate::platform::SvsmPlatform;
 use crate::platform::SVSM_PLATFORM;
@@ -13,17 +13,39 @@ use crate::requests::{request_loop, request_processing_main};
 use crate::task::{create_kernel_task, schedule_init};
 use crate::utils::immut_after_init::immut_after_init_set_multithreaded;
 
+/// Number of polling iterations to wait for a started AP to come online
+/// before giving up.
+const AP_ONLINE_SPIN_LIMIT: u64 = 1 << 30;
+
+/// Waits for a CPU whose startup has been initiated to report that it is
+/// online.
+pub fn wait_for_cpu_online(cpu: &PerCpu) -> Result<(), SvsmError> {
+    let percpu_shared = cpu.shared();
+    for _ in 0..AP_ONLINE_SPIN_LIMIT {
+        if percpu_shared.is_online() {
+            return Ok(());
+        }
+        core::hint::spin_loop();
+    }
+
+    log::error!(
+        "AP with APIC-ID {} failed to come online",
+        cpu.get_apic_id()
+    );
+    Err(SvsmError::ApStartTimeout)
+}
+
 fn start_cpu(platform: &dyn SvsmPlatform, apic_id: u32, vtom: u64) -> Result<(), SvsmError> {
     let start_rip: u64 = (start_ap as *const u8) as u64;
     let percpu = PerCpu::alloc(apic_id)?;
 
-    percpu.setup(platform)?;
-    let (vmsa_pa, sev_features) = percpu.alloc_svsm_vmsa(vtom, start_rip)?;
-    let percpu_shared = percpu.shared();
-
-    current_ghcb().ap_create(vmsa_pa, apic_id.into(), 0, sev_features)?;
-    while !percpu_shared.is_online() {}
-    Ok(())
+    let result = percpu
+        .setup(platform)
+        .and_then(|_| platform.start_cpu(percpu, start_rip, vtom));
+    if result.is_err() {
+        percpu.unregister();
+    }
+    result
 }
 
 pub fn start_secondary_cpus(platform: &dyn SvsmPlatform, cpus: &[ACPICPUInfo], vtom: u64) {
@@ -31,10 +53,20 @@ pub fn start_secondary_cpus(platform: &dyn SvsmPlatform, cpus: &[ACPICPUInfo], v
     let mut count: usize = 0;
     for c in cpus.iter().filter(|c| c.apic_id != 0 && c.enabled) {
         log::info!("Launching AP with APIC-ID {}", c.apic_id);
-        start_cpu(platform, c.apic_id, vtom).expect("Failed to bring CPU online");
-        count += 1;
+        match start_cpu(platform, c.apic_id, vtom) {
+            Ok(()) => count += 1,
+            Err(e) => log::error!(
+                "Failed to bring AP with APIC-ID {} online: {:?}",
+                c.apic_id,
+                e
+            ),
+        }
     }
-    log::info!("Brought {} AP(s) online", count);
+    log::info!(
+        "Brought {} AP(s) online, {} CPU(s) running",
+        count,
+        PERCPU_AREAS.online_count()
+    );
 }
 
 #[no_mangle]
@@ -5,6 +5,8 @@
 // Author: Joerg Roedel <jroedel@suse.de>
 
 use crate::address::{Address, VirtAddr};
+use crate::types::PageSize;
+use crate::utils::MemoryRegion;
 use core::arch::asm;
 
 const INVLPGB_VALID_VA: u64 = 1u64 << 0;
@@ -12,6 +14,10 @@ const INVLPGB_VALID_VA: u64 = 1u64 << 0;
 const INVLPGB_VALID_ASID: u64 = 1u64 << 2;
 const INVLPGB_VALID_GLOBAL: u64 = 1u64 << 3;
 
+/// Ranges spanning more pages than this are flushed with a single full TLB
+/// flush rather than one flush per page.
+const TLB_FLUSH_RANGE_MAX_PAGES: usize = 32;
+
 #[inline]
 fn do_invlpgb(rax: u64, rcx: u64, rdx: u64) {
     unsafe {
@@ -62,3 +68,36 @@ pub fn flush_address_sync(va: VirtAddr) {
     flush_address(va);
     do_tlbsync();
 }
+
+/// Returns true if a range of `count` pages should be flushed page by page
+/// rather than with a full flush.
+fn flush_per_page(count: usize) -> bool {
+    count <= TLB_FLUSH_RANGE_MAX_PAGES
+}
+
+/// Flushes the translations for all pages of the given size in `region` on
+/// all CPUs, falling back to a full flush for large ranges, and waits for
+/// the flush to complete.
+pub fn flush_address_range_sync(region: MemoryRegion<VirtAddr>, size: PageSize) {
+    let count = region.len().div_ceil(usize::from(size));
+    if flush_per_page(count) {
+        for va in region.iter_pages(size) {
+            flush_address(va);
+        }
+        do_tlbsync();
+    } else {
+        flush_tlb_global_sync();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flush_per_page_threshold() {
+        assert!(flush_per_page(1));
+        assert!(flush_per_page(TLB_FLUSH_RANGE_MAX_PAGES));
+        assert!(!flush_per_page(TLB_FLUSH_RANGE_MAX_PAGES + 1));
+    }
+}
@@ -7,7 +7,7 @@
 use super::idt::common::X86ExceptionContext;
 use crate::address::Address;
 use crate::address::VirtAddr;
-use crate::cpu::cpuid::{cpuid_table_raw, CpuidLeaf};
+use crate::cpu::cpuid::{cpuid_table_subleaf, CpuidLeaf};
 use crate::cpu::percpu::current_ghcb;
 use crate::cpu::percpu::this_cpu;
 use crate::cpu::X86GeneralRegs;
@@ -207,7 +207,7 @@ fn handle_cpuid(ctx: &mut X86ExceptionContext) -> Result<(), SvsmError> {
 fn snp_cpuid(ctx: &mut X86ExceptionContext) -> Result<(), SvsmError> {
     let mut leaf = CpuidLeaf::new(ctx.regs.rax as u32, ctx.regs.rcx as u32);
 
-    let Some(ret) = cpuid_table_raw(leaf.cpuid_fn, leaf.cpuid_subfn, 0, 0) else {
+    let Some(ret) = cpuid_table_subleaf(leaf.cpuid_fn, leaf.cpuid_subfn) else {
         return Err(VcError::new(ctx, VcErrorType::UnknownCpuidLeaf).into());
     };
 
@@ -290,6 +290,7 @@ mod tests {
     use super::*;
     use crate::cpu::msr::{rdtsc, rdtscp, read_msr, write_msr, RdtscpOut};
     use crate::sev::ghcb::GHCB;
+    use crate::sev::status::SEVStatusFlags;
     use crate::sev::utils::{get_dr7, raw_vmmcall, set_dr7};
     use core::arch::asm;
     use core::arch::x86_64::__cpuid_count;
@@ -491,11 +492,7 @@ mod tests {
     #[test]
     #[cfg_attr(not(test_in_svsm), ignore = "Can only be run inside guest")]
     fn test_sev_snp_enablement_msr() {
-        const MSR_SEV_STATUS: u32 = 0xc0010131;
-        const MSR_SEV_STATUS_SEV_SNP_ENABLED: u64 = 0b10;
-
-        let sev_status = read_msr(MSR_SEV_STATUS);
-        assert_ne!(sev_status & MSR_SEV_STATUS_SEV_SNP_ENABLED, 0);
+        assert!(SEVStatusFlags::read_current().requires_ghcb());
     }
 
     const MSR_APIC_BASE: u32 = 0x1b;
@@ -17,15 +17,18 @@
 //! usually the one corresponding to that module. Each module should provide
 //! a way to convert a leaf error into a SvsmError via the [`From`] trait.
 
+use crate::address::{PhysAddr, VirtAddr};
 use crate::cpu::vc::VcError;
 use crate::fs::FsError;
 use crate::fw_cfg::FwCfgError;
 use crate::insn_decode::InsnError;
 use crate::mm::alloc::AllocError;
+use crate::protocols::errors::SvsmResultCode;
 use crate::sev::ghcb::GhcbError;
 use crate::sev::msr_protocol::GhcbMsrError;
 use crate::sev::SevSnpError;
 use crate::task::TaskError;
+use crate::types::PageSize;
 use elf::ElfError;
 
 /// A generic error during SVSM operation.
@@ -39,6 +42,15 @@ pub enum SvsmError {
     GhcbMsr(GhcbMsrError),
     /// Errors related to SEV-SNP operations, like PVALIDATE or RMPUPDATE
     SevSnp(SevSnpError),
+    /// A PVALIDATE of a page within a range failed. Identifies the page, by
+    /// both its virtual and physical address, and the page size that was
+    /// attempted.
+    Validation {
+        vaddr: VirtAddr,
+        paddr: PhysAddr,
+        size: PageSize,
+        error: SevSnpError,
+    },
     /// Errors related to TDX operations
     Tdx,
     /// Generic errors related to memory management
@@ -73,6 +85,18 @@ pub enum SvsmError {
     NotSupported,
     /// Generic errors related to APIC emulation.
     Apic,
+    /// The hardware random number generator failed to return data.
+    Rng,
+    /// A secondary CPU did not report itself online in time.
+    ApStartTimeout,
+    /// Another event is already pending injection into the guest.
+    EventPending,
+    /// No CPU with the given APIC ID exists.
+    UnknownApicId(u32),
+    /// The platform rejected an attestation request.
+    Attestation,
+    /// A request was rejected with the given SVSM result code.
+    Request(SvsmResultCode),
 }
 
 impl From<ElfError> for SvsmError {
@@ -8,11 +8,10 @@ extern crate alloc;
 
 use crate::address::PhysAddr;
 use crate::config::SvsmConfig;
-use crate::cpu::percpu::current_ghcb;
 use crate::error::SvsmError;
 use crate::kernel_region::new_kernel_region;
 use crate::mm::PerCPUPageMappingGuard;
-use crate::platform::PageStateChangeOp;
+use crate::platform::{PageStateChangeOp, SVSM_PLATFORM};
 use crate::sev::{pvalidate, rmp_adjust, PvalidateOp, RMPFlags};
 use crate::types::{PageSize, PAGE_SIZE};
 use crate::utils::{zero_mem_region, MemoryRegion};
@@ -359,21 +358,12 @@ fn parse_sev_meta(
     Ok(())
 }
 
-fn validate_fw_mem_region(
-    config: &SvsmConfig<'_>,
-    region: MemoryRegion<PhysAddr>,
-) -> Result<(), SvsmError> {
+fn validate_fw_mem_region(region: MemoryRegion<PhysAddr>) -> Result<(), SvsmError> {
     let pstart = region.start();
     let pend = region.end();
 
     log::info!("Validating {:#018x}-{:#018x}", pstart, pend);
 
-    if config.page_state_change_required() {
-        current_ghcb()
-            .page_state_change(region, PageSize::Regular, PageStateChangeOp::Private)
-            .expect("GHCB PSC call failed to validate firmware memory");
-    }
-
     for paddr in region.iter_pages(PageSize::Regular) {
         let guard = PerCPUPageMappingGuard::create_4k(paddr)?;
         let vaddr = guard.virt_addr();
@@ -393,10 +383,7 @@ fn validate_fw_mem_region(
     Ok(())
 }
 
-fn validate_fw_memory_vec(
-    config: &SvsmConfig<'_>,
-    regions: Vec<MemoryRegion<PhysAddr>>,
-) -> Result<(), SvsmError> {
+fn validate_fw_memory_vec(regions: Vec<MemoryRegion<PhysAddr>>) -> Result<(), SvsmError> {
     if regions.is_empty() {
         return Ok(());
     }
@@ -412,8 +399,8 @@ fn validate_fw_memory_vec(
         }
     }
 
-    validate_fw_mem_region(config, region)?;
-    validate_fw_memory_vec(config, next_vec)
+    validate_fw_mem_region(region)?;
+    validate_fw_memory_vec(next_vec)
 }
 
 pub fn validate_fw_memory(
@@ -450,7 +437,16 @@ pub fn validate_fw_memory(
         }
     }
 
-    validate_fw_memory_vec(config, regions)
+    // Make all regions private with as few page state change requests as
+    // possible before validating them.
+    if config.page_state_change_required() {
+        SVSM_PLATFORM
+            .as_dyn_ref()
+            .page_state_change_batch(&regions, PageSize::Regular, PageStateChangeOp::Private)
+            .expect("GHCB PSC call failed to validate firmware memory");
+    }
+
+    validate_fw_memory_vec(regions)
 }
 
 pub fn print_fw_meta(fw_meta: &SevFWMetaData) {
@@ -21,7 +21,7 @@ use crate::{
     greq::msg::{SnpGuestRequestExtData, SnpGuestRequestMsg, SnpGuestRequestMsgType},
     locking::SpinLock,
     protocols::errors::{SvsmReqError, SvsmResultCode},
-    sev::{ghcb::GhcbError, secrets_page, secrets_page_mut, VMPCK_SIZE},
+    sev::{ghcb::GhcbError, secrets_page, secrets_page_mut, SequenceCounters, VMPCK_SIZE},
     types::PAGE_SHIFT,
     BIT,
 };
@@ -59,22 +59,8 @@ struct SnpGuestRequestDriver {
     /// Extended data size (`certs` size) provided by the user in [`super::services::get_extended_report`].
     /// It will be provided to the hypervisor.
     user_extdata_size: usize,
-    /// Each `SNP_GUEST_REQUEST` message contains a sequence number per VMPCK.
-    /// The sequence number is incremented with each message sent. Messages
-    /// sent by the guest to the PSP and by the PSP to the guest must be
-    /// delivered in order. If not, the PSP will reject subsequent messages
-    /// by the guest when it detects that the sequence numbers are out of sync.
-    ///
-    /// NOTE: If the vmpl field of a `SNP_GUEST_REQUEST` message is set to VMPL0,
-    /// then it must contain the VMPL0 sequence number and be protected (encrypted)
-    /// with the VMPCK0 key; additionally, if this message fails, the VMPCK0 key
-    /// must be disabled. The same idea applies to the other VMPL levels.
-    ///
-    /// The SVSM needs to support only VMPL0 `SNP_GUEST_REQUEST` commands because
-    /// other layers in the software stack (e.g. OVMF and guest kernel) can send
-    /// non-VMPL0 commands directly to PSP. Therefore, the SVSM needs to maintain
-    /// the sequence number and the VMPCK only for VMPL0.
-    vmpck0_seqno: u64,
+    /// Message sequence numbers, serialized by the driver lock
+    seqnos: SequenceCounters,
 }
 
 impl Drop for SnpGuestRequestDriver {
@@ -117,7 +103,7 @@ impl SnpGuestRequestDriver {
             staging,
             ext_data,
             user_extdata_size: size_of::<SnpGuestRequestExtData>(),
-            vmpck0_seqno: 0,
+            seqnos: SequenceCounters::new(),
         };
 
         driver.request.set_shared()?;
@@ -128,15 +114,36 @@ impl SnpGuestRequestDriver {
     }
 
     /// Get the last VMPCK0 sequence number accounted
+    ///
+    /// Each `SNP_GUEST_REQUEST` message contains a sequence number per VMPCK.
+    /// The sequence number is incremented with each message sent. Messages
+    /// sent by the guest to the PSP and by the PSP to the guest must be
+    /// delivered in order. If not, the PSP will reject subsequent messages
+    /// by the guest when it detects that the sequence numbers are out of sync.
+    ///
+    /// NOTE: If the vmpl field of a `SNP_GUEST_REQUEST` message is set to VMPL0,
+    /// then it must contain the VMPL0 sequence number and be protected (encrypted)
+    /// with the VMPCK0 key; additionally, if this message fails, the VMPCK0 key
+    /// must be disabled. The same idea applies to the other VMPL levels.
+    ///
+    /// The SVSM needs to support only VMPL0 `SNP_GUEST_REQUEST` commands because
+    /// other layers in the software stack (e.g. OVMF and guest kernel) can send
+    /// non-VMPL0 commands directly to PSP. Therefore, the SVSM needs to maintain
+    /// the sequence number and the VMPCK only for VMPL0.
     fn seqno_last_used(&self) -> u64 {
-        self.vmpck0_seqno
+        self.seqnos.last_used(0)
     }
 
-    /// Increase the VMPCK0 sequence number by two. In order to keep the
-    /// sequence number in-sync with the PSP, this is called only when the
-    /// `SNP_GUEST_REQUEST` response is received.
-    fn seqno_add_two(&mut self) {
-        self.vmpck0_seqno += 2;
+    /// Commit the reserved VMPCK0 sequence number and the one of the
+    /// response. In order to keep the sequence number in-sync with the PSP,
+    /// this is called only when the `SNP_GUEST_REQUEST` response is received.
+    fn seqno_commit(&mut self) -> Result<(), SvsmReqError> {
+        if self.seqnos.commit(0).is_none() {
+            log::error!("SNP_GUEST_REQUEST: sequence number overflow");
+            secrets_page_mut().clear_vmpck(0);
+            return Err(SvsmReqError::invalid_request());
+        }
+        Ok(())
     }
 
     /// Set the user_extdata_size to `n` and clear the first `n` bytes from `ext_data`
@@ -168,9 +175,7 @@ impl SnpGuestRequestDriver {
             ghcb.guest_request(req_page, resp_page)?;
         }
 
-        self.seqno_add_two();
-
-        Ok(())
+        self.seqno_commit()
     }
 
     // Encrypt the request message from encrypted memory
@@ -255,12 +260,29 @@ impl SnpGuestRequestDriver {
         // Message sequence number overflow, the driver will not able
         // to send subsequent `SNP_GUEST_REQUEST` messages to the PSP.
         // The sequence number is restored only when the guest is rebooted.
-        let Some(msg_seqno) = self.seqno_last_used().checked_add(1) else {
+        let Some(msg_seqno) = self.seqnos.reserve(0) else {
             log::error!("SNP_GUEST_REQUEST: sequence number overflow");
             secrets_page_mut().clear_vmpck(0);
             return Err(SvsmReqError::invalid_request());
         };
 
+        let result = self.send_reserved(req_class, msg_type, msg_seqno, buffer, command_len);
+        // A request that the host did not accept does not consume its
+        // sequence number.
+        self.seqnos.release(0);
+        result
+    }
+
+    /// Send a VMPL0 `SNP_GUEST_REQUEST` command using the reserved sequence
+    /// number `msg_seqno`. See [`Self::send_request()`].
+    fn send_reserved(
+        &mut self,
+        req_class: SnpGuestRequestClass,
+        msg_type: SnpGuestRequestMsgType,
+        msg_seqno: u64,
+        buffer: &mut [u8],
+        command_len: usize,
+    ) -> Result<usize, SvsmReqError> {
         self.encrypt_request(msg_type, msg_seqno, buffer, command_len)?;
 
         if let Err(e) = self.send(req_class) {
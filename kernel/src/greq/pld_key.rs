@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) Microsoft Corporation
+//
+// Author: agent <agent@local>
+
+//! `SNP_GUEST_REQUEST` command to request a derived key.
+
+use core::mem::size_of;
+
+use bitflags::bitflags;
+
+use crate::protocols::errors::SvsmReqError;
+
+/// Size of the derived key returned in `SnpDerivedKeyResponse`
+pub const DERIVED_KEY_SIZE: usize = 32;
+
+bitflags! {
+    /// Selects the guest data mixed into a derived key
+    /// (AMD SEV-SNP spec. table 18, GUEST_FIELD_SELECT)
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct GuestFieldSelect: u64 {
+        const GUEST_POLICY = 1 << 0;
+        const IMAGE_ID     = 1 << 1;
+        const FAMILY_ID    = 1 << 2;
+        const MEASUREMENT  = 1 << 3;
+        const GUEST_SVN    = 1 << 4;
+        const TCB_VERSION  = 1 << 5;
+    }
+}
+
+/// MSG_KEY_REQ payload format (AMD SEV-SNP spec. table 18)
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct SnpDerivedKeyRequest {
+    /// 31:1 - Reserved
+    ///    0 - ROOT_KEY_SELECT. Selects the root key to derive the key from
+    ///        0: Derive from VCEK
+    ///        1: Derive from VMRK
+    root_key_select: u32,
+    /// Reserved, must be zero
+    rsvd: u32,
+    /// Selects the guest data mixed into the key, see [`GuestFieldSelect`]
+    guest_field_select: u64,
+    /// The VMPL to mix into the key. Must be greater than or equal to the
+    /// current VMPL
+    vmpl: u32,
+    /// The guest SVN to mix into the key
+    guest_svn: u32,
+    /// The TCB version to mix into the key
+    tcb_version: u64,
+}
+
+impl SnpDerivedKeyRequest {
+    /// Create a request for a key derived from the VCEK
+    pub fn new(
+        guest_field_select: GuestFieldSelect,
+        vmpl: u32,
+        guest_svn: u32,
+        tcb_version: u64,
+    ) -> Self {
+        Self {
+            root_key_select: 0,
+            rsvd: 0,
+            guest_field_select: guest_field_select.bits(),
+            vmpl,
+            guest_svn,
+            tcb_version,
+        }
+    }
+
+    /// Return the request as a byte slice
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: SnpDerivedKeyRequest is comprised entirely of integer types
+        // and is repr(packed), so it has no padding and can be viewed as a
+        // byte slice of its own size.
+        unsafe {
+            core::slice::from_raw_parts((self as *const Self).cast::<u8>(), size_of::<Self>())
+        }
+    }
+}
+
+/// MSG_KEY_RSP payload format (AMD SEV-SNP spec. table 19)
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct SnpDerivedKeyResponse {
+    /// The status of the key derivation operation, see
+    /// [`SnpDerivedKeyResponseStatus`]
+    status: u32,
+    /// Reserved
+    _reserved: [u8; 28],
+    /// The requested derived key
+    derived_key: [u8; DERIVED_KEY_SIZE],
+}
+
+/// Supported values for SnpDerivedKeyResponse.status
+#[repr(u32)]
+#[derive(Clone, Copy, Debug)]
+pub enum SnpDerivedKeyResponseStatus {
+    Success = 0,
+    InvalidParameters = 0x16,
+}
+
+impl SnpDerivedKeyResponse {
+    pub fn try_from_as_ref(buffer: &[u8]) -> Result<&Self, SvsmReqError> {
+        let buffer = buffer
+            .get(..size_of::<Self>())
+            .ok_or_else(SvsmReqError::invalid_parameter)?;
+
+        // SAFETY: SnpDerivedKeyResponse has no invalid representations, as
+        // it is comprised entirely of integer types. It is repr(packed), so
+        // its required alignment is simply 1. We have checked the size, so
+        // this is entirely safe.
+        let response = unsafe { &*buffer.as_ptr().cast::<Self>() };
+        Ok(response)
+    }
+
+    /// Validate the [`SnpDerivedKeyResponse`] fields
+    pub fn validate(&self) -> Result<(), SvsmReqError> {
+        if self.status != SnpDerivedKeyResponseStatus::Success as u32 {
+            return Err(SvsmReqError::invalid_request());
+        }
+        Ok(())
+    }
+
+    /// Return the derived key
+    pub fn derived_key(&self) -> [u8; DERIVED_KEY_SIZE] {
+        self.derived_key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::offset_of;
+
+    #[test]
+    fn test_snp_derived_key_request_offsets() {
+        assert_eq!(offset_of!(SnpDerivedKeyRequest, root_key_select), 0x0);
+        assert_eq!(offset_of!(SnpDerivedKeyRequest, rsvd), 0x4);
+        assert_eq!(offset_of!(SnpDerivedKeyRequest, guest_field_select), 0x8);
+        assert_eq!(offset_of!(SnpDerivedKeyRequest, vmpl), 0x10);
+        assert_eq!(offset_of!(SnpDerivedKeyRequest, guest_svn), 0x14);
+        assert_eq!(offset_of!(SnpDerivedKeyRequest, tcb_version), 0x18);
+        assert_eq!(size_of::<SnpDerivedKeyRequest>(), 0x20);
+    }
+
+    #[test]
+    fn test_snp_derived_key_response_offsets() {
+        assert_eq!(offset_of!(SnpDerivedKeyResponse, status), 0x0);
+        assert_eq!(offset_of!(SnpDerivedKeyResponse, _reserved), 0x4);
+        assert_eq!(offset_of!(SnpDerivedKeyResponse, derived_key), 0x20);
+        assert_eq!(size_of::<SnpDerivedKeyResponse>(), 0x40);
+    }
+}
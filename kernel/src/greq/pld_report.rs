@@ -99,6 +99,11 @@ impl SnpReportResponse {
         Ok(response)
     }
 
+    /// Returns a copy of the attestation report carried by this response.
+    pub fn report(&self) -> AttestationReport {
+        self.report
+    }
+
     /// Validate the [SnpReportResponse] fields
     pub fn validate(&self) -> Result<(), SvsmReqError> {
         if self.status != SnpReportResponseStatus::Success as u32 {
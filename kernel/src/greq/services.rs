@@ -7,17 +7,23 @@
 //! API to send `SNP_GUEST_REQUEST` commands to the PSP
 
 use crate::{
+    error::SvsmError,
     greq::{
         driver::{send_extended_guest_request, send_regular_guest_request},
         msg::SnpGuestRequestMsgType,
-        pld_report::{SnpReportRequest, SnpReportResponse},
+        pld_key::{
+            GuestFieldSelect, SnpDerivedKeyRequest, SnpDerivedKeyResponse, DERIVED_KEY_SIZE,
+        },
+        pld_report::{AttestationReport, SnpReportRequest, SnpReportResponse, USER_DATA_SIZE},
     },
     protocols::errors::SvsmReqError,
+    utils::zero_secret,
 };
 use core::mem::size_of;
 
 const REPORT_REQUEST_SIZE: usize = size_of::<SnpReportRequest>();
 const REPORT_RESPONSE_SIZE: usize = size_of::<SnpReportResponse>();
+const KEY_RESPONSE_SIZE: usize = size_of::<SnpDerivedKeyResponse>();
 
 fn get_report(buffer: &mut [u8], certs: Option<&mut [u8]>) -> Result<usize, SvsmReqError> {
     let request: &SnpReportRequest = SnpReportRequest::try_from_as_ref(buffer)?;
@@ -26,19 +32,18 @@ fn get_report(buffer: &mut [u8], certs: Option<&mut [u8]>) -> Result<usize, Svsm
     if !request.is_vmpl0() {
         return Err(SvsmReqError::invalid_parameter());
     }
-    let response_len = if certs.is_none() {
-        send_regular_guest_request(
+    let response_len = match certs {
+        None => send_regular_guest_request(
             SnpGuestRequestMsgType::ReportRequest,
             buffer,
             REPORT_REQUEST_SIZE,
-        )?
-    } else {
-        send_extended_guest_request(
+        )?,
+        Some(certs) => send_extended_guest_request(
             SnpGuestRequestMsgType::ReportRequest,
             buffer,
             REPORT_REQUEST_SIZE,
-            certs.unwrap(),
-        )?
+            certs,
+        )?,
     };
     if REPORT_RESPONSE_SIZE > response_len {
         return Err(SvsmReqError::invalid_request());
@@ -74,6 +79,23 @@ pub fn get_regular_report(buffer: &mut [u8]) -> Result<usize, SvsmReqError> {
     get_report(buffer, None)
 }
 
+/// Request a regular VMPL0 attestation report carrying `report_data` from
+/// the PSP, and return the report itself.
+///
+/// The report is signed with the VCEK, which is what the all-zero key
+/// selection of the request asks for.
+pub fn get_attestation_report(
+    report_data: &[u8; USER_DATA_SIZE],
+) -> Result<AttestationReport, SvsmReqError> {
+    // The response is larger than the request and overwrites it in place.
+    let mut buffer = [0u8; REPORT_RESPONSE_SIZE];
+    // The user data is the first field of MSG_REPORT_REQ; all remaining
+    // fields (including the VMPL) are left as zero.
+    buffer[..USER_DATA_SIZE].copy_from_slice(report_data);
+    get_regular_report(&mut buffer)?;
+    Ok(SnpReportResponse::try_from_as_ref(&buffer)?.report())
+}
+
 /// Request an extended VMPL0 attestation report to the PSP.
 ///
 /// We say that it is extended because it requests a VMPL0 attestation report
@@ -104,3 +126,92 @@ pub fn get_regular_report(buffer: &mut [u8]) -> Result<usize, SvsmReqError> {
 pub fn get_extended_report(buffer: &mut [u8], certs: &mut [u8]) -> Result<usize, SvsmReqError> {
     get_report(buffer, Some(certs))
 }
+
+/// Send a regular `SNP_GUEST_REQUEST` command of any request type to the PSP.
+///
+/// The request is encrypted with the VMPCK0 and sent with the next message
+/// sequence number by the `SNP_GUEST_REQUEST` driver, which also decrypts
+/// the response. Typed commands are built on top of this function so that
+/// sequence number handling is never duplicated.
+///
+/// The VMPCK0 is disabled for subsequent calls if this function fails in a way that
+/// the VM state can be compromised.
+///
+/// # Arguments
+///
+/// * `msg_type`: Request message type, e.g. [`SnpGuestRequestMsgType::KeyRequest`].
+/// * `req`: Plaintext request payload.
+/// * `resp`: Buffer to store the plaintext response payload. It must be at
+///           least as large as `req`.
+///
+/// # Returns
+///
+/// * Success
+///     * `usize`: Number of bytes written to `resp`.
+/// * Error
+///     * [`SvsmError`]
+///     * `SvsmError::Request(code)`: the request was rejected by the driver
+///       or the PSP with the given result code.
+pub fn snp_guest_request(
+    msg_type: SnpGuestRequestMsgType,
+    req: &[u8],
+    resp: &mut [u8],
+) -> Result<usize, SvsmError> {
+    let buffer = resp
+        .get_mut(..req.len())
+        .ok_or_else(SvsmReqError::invalid_parameter)?;
+    buffer.copy_from_slice(req);
+
+    Ok(send_regular_guest_request(msg_type, resp, req.len())?)
+}
+
+/// Request a key derived from the VCEK to the PSP.
+///
+/// Use the `SNP_GUEST_REQUEST` driver to send a `MSG_KEY_REQ` command to the
+/// PSP. The request shares the VMPCK0 message sequence number with the
+/// attestation report requests, since both are sent through the same driver.
+///
+/// The VMPCK0 is disabled for subsequent calls if this function fails in a way that
+/// the VM state can be compromised.
+///
+/// # Arguments
+///
+/// * `guest_field_select`: Guest data to mix into the derived key.
+/// * `vmpl`: VMPL to mix into the derived key.
+/// * `guest_svn`: Guest SVN to mix into the derived key.
+/// * `tcb_version`: TCB version to mix into the derived key.
+///
+/// # Returns
+///
+/// * Success
+///     * `[u8; DERIVED_KEY_SIZE]`: The derived key.
+/// * Error
+///     * [`SvsmReqError`]
+pub fn snp_derive_key(
+    guest_field_select: GuestFieldSelect,
+    vmpl: u32,
+    guest_svn: u32,
+    tcb_version: u64,
+) -> Result<[u8; DERIVED_KEY_SIZE], SvsmReqError> {
+    let request = SnpDerivedKeyRequest::new(guest_field_select, vmpl, guest_svn, tcb_version);
+    let mut buffer = [0u8; KEY_RESPONSE_SIZE];
+
+    let result = snp_guest_request(
+        SnpGuestRequestMsgType::KeyRequest,
+        request.as_bytes(),
+        &mut buffer,
+    )
+    .map_err(SvsmReqError::from)
+    .and_then(|response_len| {
+        if KEY_RESPONSE_SIZE > response_len {
+            return Err(SvsmReqError::invalid_request());
+        }
+        let response = SnpDerivedKeyResponse::try_from_as_ref(&buffer)?;
+        response.validate()?;
+        Ok(response.derived_key())
+    });
+
+    // Do not leave a copy of the key behind on the stack.
+    zero_secret(&mut buffer);
+    result
+}
@@ -7,36 +7,38 @@
 extern crate alloc;
 
 use crate::acpi::tables::ACPICPUInfo;
-use crate::address::{PhysAddr, VirtAddr};
+use crate::address::{Address, PhysAddr, VirtAddr};
 use crate::cpu::efer::EFERFlags;
 use crate::error::SvsmError;
 use crate::fw_meta::SevFWMetaData;
-use crate::mm::{GuestPtr, PerCPUPageMappingGuard, PAGE_SIZE};
-use crate::platform::{PageStateChangeOp, SVSM_PLATFORM};
+use crate::mm::{GuestSlice, PerCPUPageMappingGuard, PAGE_SIZE};
+use crate::platform::{coalesce_regions, PageStateChangeOp, SVSM_PLATFORM};
 use crate::types::PageSize;
 use crate::utils::MemoryRegion;
 use alloc::vec::Vec;
-use cpuarch::vmsa::VMSA;
+use cpuarch::vmsa::{SegmentRegister, VMSASegment, VMSA};
 
 use bootlib::igvm_params::{IgvmGuestContext, IgvmParamBlock, IgvmParamPage};
 use core::mem::size_of;
+use core::slice;
 use igvm_defs::{IgvmEnvironmentInfo, MemoryMapEntryType, IGVM_VHS_MEMORY_MAP_ENTRY};
 
-const IGVM_MEMORY_ENTRIES_PER_PAGE: usize = PAGE_SIZE / size_of::<IGVM_VHS_MEMORY_MAP_ENTRY>();
-
 const STAGE2_END_ADDR: usize = 0xA0000;
 
-#[derive(Clone, Debug)]
-#[repr(C, align(64))]
-pub struct IgvmMemoryMap {
-    memory_map: [IGVM_VHS_MEMORY_MAP_ENTRY; IGVM_MEMORY_ENTRIES_PER_PAGE],
+/// A range of the IGVM memory map together with the type and flags the host
+/// supplied for it.
+#[derive(Clone, Copy, Debug)]
+pub struct IgvmMemoryRegion {
+    pub region: MemoryRegion<PhysAddr>,
+    pub entry_type: MemoryMapEntryType,
+    pub flags: u16,
 }
 
 #[derive(Clone, Debug)]
 pub struct IgvmParams<'a> {
     igvm_param_block: &'a IgvmParamBlock,
     igvm_param_page: &'a IgvmParamPage,
-    igvm_memory_map: &'a IgvmMemoryMap,
+    igvm_memory_map: &'a [IGVM_VHS_MEMORY_MAP_ENTRY],
     igvm_guest_context: Option<&'a IgvmGuestContext>,
 }
 
@@ -46,7 +48,16 @@ impl IgvmParams<'_> {
         let param_page_address = addr + param_block.param_page_offset as usize;
         let param_page = Self::try_aligned_ref::<IgvmParamPage>(param_page_address)?;
         let memory_map_address = addr + param_block.memory_map_offset as usize;
-        let memory_map = Self::try_aligned_ref::<IgvmMemoryMap>(memory_map_address)?;
+        // A memory map size of zero describes a single page, which is the
+        // layout used before multi-page memory maps were supported.
+        let memory_map_size = match param_block.memory_map_size {
+            0 => PAGE_SIZE,
+            size => size as usize,
+        };
+        let memory_map = Self::try_aligned_slice::<IGVM_VHS_MEMORY_MAP_ENTRY>(
+            memory_map_address,
+            memory_map_size / size_of::<IGVM_VHS_MEMORY_MAP_ENTRY>(),
+        )?;
         let guest_context = if param_block.guest_context_offset != 0 {
             let offset = usize::try_from(param_block.guest_context_offset).unwrap();
             Some(Self::try_aligned_ref::<IgvmGuestContext>(addr + offset)?)
@@ -68,6 +79,16 @@ impl IgvmParams<'_> {
         unsafe { addr.aligned_ref::<T>().ok_or(SvsmError::Firmware) }
     }
 
+    fn try_aligned_slice<'a, T>(addr: VirtAddr, len: usize) -> Result<&'a [T], SvsmError> {
+        if addr.is_null() || !addr.is_aligned_to::<T>() {
+            return Err(SvsmError::Firmware);
+        }
+        // SAFETY: we trust the caller to provide an address pointing to
+        // `len` valid elements which are not mutably aliased.  Alignment and
+        // NULL checks were performed above.
+        unsafe { Ok(slice::from_raw_parts(addr.as_ptr::<T>(), len)) }
+    }
+
     pub fn size(&self) -> usize {
         // Calculate the total size of the parameter area.  The
         // parameter area always begins at the kernel base
@@ -102,48 +123,30 @@ impl IgvmParams<'_> {
     }
 
     pub fn get_memory_regions(&self) -> Result<Vec<MemoryRegion<PhysAddr>>, SvsmError> {
-        // Count the number of memory entries present.  They must be
-        // non-overlapping and strictly increasing.
-        let mut number_of_entries = 0;
-        let mut next_page_number = 0;
-        for entry in self.igvm_memory_map.memory_map.iter() {
-            if entry.number_of_pages == 0 {
-                break;
-            }
-            if entry.starting_gpa_page_number < next_page_number {
-                return Err(SvsmError::Firmware);
-            }
-            let next_supplied_page_number = entry.starting_gpa_page_number + entry.number_of_pages;
-            if next_supplied_page_number < next_page_number {
-                return Err(SvsmError::Firmware);
-            }
-            next_page_number = next_supplied_page_number;
-            number_of_entries += 1;
-        }
+        parse_memory_map(self.igvm_memory_map)
+    }
 
-        // Now loop over the supplied entires and add a region for each
-        // known type.
-        let mut regions: Vec<MemoryRegion<PhysAddr>> = Vec::new();
-        for entry in self
-            .igvm_memory_map
-            .memory_map
-            .iter()
-            .take(number_of_entries)
-        {
-            if entry.entry_type == MemoryMapEntryType::MEMORY {
-                let starting_page: usize = entry.starting_gpa_page_number.try_into().unwrap();
-                let number_of_pages: usize = entry.number_of_pages.try_into().unwrap();
-                regions.push(MemoryRegion::new(
-                    PhysAddr::new(starting_page * PAGE_SIZE),
-                    number_of_pages * PAGE_SIZE,
-                ));
-            }
-        }
+    /// Returns every entry of the memory map, including those not describing
+    /// usable memory, with its type and flags.
+    pub fn get_memory_regions_detailed(&self) -> Result<Vec<IgvmMemoryRegion>, SvsmError> {
+        parse_memory_map_detailed(self.igvm_memory_map)
+    }
 
-        Ok(regions)
+    /// Builds the memory map to present to the guest from the usable guest
+    /// memory `usable`, which is a subset of the host memory map.  Usable
+    /// regions keep the flags of the host entry they came from, and all
+    /// non-memory entries of the host map are passed through unchanged.
+    pub fn guest_memory_map(
+        &self,
+        usable: &[MemoryRegion<PhysAddr>],
+    ) -> Result<Vec<IgvmMemoryRegion>, SvsmError> {
+        Ok(build_guest_memory_map(
+            usable,
+            &self.get_memory_regions_detailed()?,
+        ))
     }
 
-    pub fn write_guest_memory_map(&self, map: &[MemoryRegion<PhysAddr>]) -> Result<(), SvsmError> {
+    pub fn write_guest_memory_map(&self, map: &[IgvmMemoryRegion]) -> Result<(), SvsmError> {
         // If the parameters do not include a guest memory map area, then no
         // work is required.
         let fw_info = &self.igvm_param_block.firmware;
@@ -181,52 +184,44 @@ impl IgvmParams<'_> {
         }
 
         let mem_map_va_region = MemoryRegion::<VirtAddr>::new(mem_map_va, mem_map_region.len());
-        platform.validate_page_range(mem_map_va_region)?;
+        platform.validate_page_range(mem_map_va_region, mem_map_region.start())?;
 
         // Calculate the maximum number of entries that can be inserted.
         let max_entries = fw_info.memory_map_page_count as usize * PAGE_SIZE
             / size_of::<IGVM_VHS_MEMORY_MAP_ENTRY>();
 
-        // Generate a guest pointer range to hold the memory map.
-        let mem_map = GuestPtr::new(mem_map_va);
-
-        for (i, entry) in map.iter().enumerate() {
-            // Return an error if an overflow occurs.
-            if i >= max_entries {
-                return Err(SvsmError::Firmware);
-            }
-
-            mem_map.offset(i as isize);
-            // SAFETY: mem_map_va points to newly mapped memory, whose physical
-            // address is defined in the IGVM config.
-            unsafe {
-                mem_map.write(IGVM_VHS_MEMORY_MAP_ENTRY {
-                    starting_gpa_page_number: u64::from(entry.start()) / PAGE_SIZE as u64,
-                    number_of_pages: entry.len() as u64 / PAGE_SIZE as u64,
-                    entry_type: MemoryMapEntryType::default(),
-                    flags: 0,
-                    reserved: 0,
-                })?;
-            }
+        // Return an error if an overflow occurs.
+        if map.len() > max_entries {
+            return Err(SvsmError::Firmware);
         }
 
+        let mut entries: Vec<IGVM_VHS_MEMORY_MAP_ENTRY> = map
+            .iter()
+            .map(|entry| IGVM_VHS_MEMORY_MAP_ENTRY {
+                starting_gpa_page_number: u64::from(entry.region.start()) / PAGE_SIZE as u64,
+                number_of_pages: entry.region.len() as u64 / PAGE_SIZE as u64,
+                entry_type: entry.entry_type,
+                flags: entry.flags,
+                reserved: 0,
+            })
+            .collect();
+
         // Write a zero page count into the last entry to terminate the list.
-        let index = map.len();
-        if index < max_entries {
-            mem_map.offset(index as isize);
-            // SAFETY: mem_map_va points to newly mapped memory, whose physical
-            // address is defined in the IGVM config.
-            unsafe {
-                mem_map.write(IGVM_VHS_MEMORY_MAP_ENTRY {
-                    starting_gpa_page_number: 0,
-                    number_of_pages: 0,
-                    entry_type: MemoryMapEntryType::default(),
-                    flags: 0,
-                    reserved: 0,
-                })?;
-            }
+        if entries.len() < max_entries {
+            entries.push(IGVM_VHS_MEMORY_MAP_ENTRY {
+                starting_gpa_page_number: 0,
+                number_of_pages: 0,
+                entry_type: MemoryMapEntryType::default(),
+                flags: 0,
+                reserved: 0,
+            });
         }
 
+        let mem_map = GuestSlice::new(mem_map_va, max_entries)?;
+        // SAFETY: mem_map_va points to newly mapped memory, whose physical
+        // address is defined in the IGVM config.
+        unsafe { mem_map.copy_from_slice(&entries)? };
+
         Ok(())
     }
 
@@ -298,7 +293,7 @@ impl IgvmParams<'_> {
         Some(fw_meta)
     }
 
-    pub fn get_fw_regions(&self) -> Vec<MemoryRegion<PhysAddr>> {
+    pub fn get_fw_regions(&self) -> Result<Vec<MemoryRegion<PhysAddr>>, SvsmError> {
         assert!(self.should_launch_fw());
 
         let mut regions = Vec::new();
@@ -314,7 +309,8 @@ impl IgvmParams<'_> {
             self.igvm_param_block.firmware.size as usize,
         ));
 
-        regions
+        check_fw_regions(&regions, &self.get_memory_regions()?)?;
+        Ok(regions)
     }
 
     pub fn fw_in_low_memory(&self) -> bool {
@@ -326,58 +322,7 @@ impl IgvmParams<'_> {
             return Ok(());
         };
 
-        // Copy the specified registers into the VMSA.
-        vmsa.cr0 = guest_context.cr0;
-        vmsa.cr3 = guest_context.cr3;
-        vmsa.cr4 = guest_context.cr4;
-        vmsa.efer = guest_context.efer;
-        vmsa.rip = guest_context.rip;
-        vmsa.rax = guest_context.rax;
-        vmsa.rcx = guest_context.rcx;
-        vmsa.rdx = guest_context.rdx;
-        vmsa.rbx = guest_context.rbx;
-        vmsa.rsp = guest_context.rsp;
-        vmsa.rbp = guest_context.rbp;
-        vmsa.rsi = guest_context.rsi;
-        vmsa.rdi = guest_context.rdi;
-        vmsa.r8 = guest_context.r8;
-        vmsa.r9 = guest_context.r9;
-        vmsa.r10 = guest_context.r10;
-        vmsa.r11 = guest_context.r11;
-        vmsa.r12 = guest_context.r12;
-        vmsa.r13 = guest_context.r13;
-        vmsa.r14 = guest_context.r14;
-        vmsa.r15 = guest_context.r15;
-        vmsa.gdt.base = guest_context.gdt_base;
-        vmsa.gdt.limit = guest_context.gdt_limit;
-
-        // If a non-zero code selector is specified, then set the code
-        // segment attributes based on EFER.LMA.
-        if guest_context.code_selector != 0 {
-            vmsa.cs.selector = guest_context.code_selector;
-            let efer_lma = EFERFlags::LMA;
-            if (vmsa.efer & efer_lma.bits()) != 0 {
-                vmsa.cs.flags = 0xA9B;
-            } else {
-                vmsa.cs.flags = 0xC9B;
-                vmsa.cs.limit = 0xFFFFFFFF;
-            }
-        }
-
-        let efer_svme = EFERFlags::SVME;
-        vmsa.efer &= !efer_svme.bits();
-
-        // If a non-zero data selector is specified, then modify the data
-        // segment attributes to be compatible with protected mode.
-        if guest_context.data_selector != 0 {
-            vmsa.ds.selector = guest_context.data_selector;
-            vmsa.ds.flags = 0xA93;
-            vmsa.ds.limit = 0xFFFFFFFF;
-            vmsa.ss = vmsa.ds;
-            vmsa.es = vmsa.ds;
-            vmsa.fs = vmsa.ds;
-            vmsa.gs = vmsa.ds;
-        }
+        init_vmsa_from_context(vmsa, guest_context);
 
         // Configure vTOM if requested.
         if self.igvm_param_block.vtom != 0 {
@@ -396,3 +341,361 @@ impl IgvmParams<'_> {
         self.igvm_param_block.use_alternate_injection != 0
     }
 }
+
+/// Applies the register state described by an IGVM guest context to `vmsa`.
+fn init_vmsa_from_context(vmsa: &mut VMSA, guest_context: &IgvmGuestContext) {
+    // A long-mode context starts from a clean 64-bit entry state, so that
+    // the segment registers not described by the context are usable.
+    if (guest_context.efer & EFERFlags::LMA.bits()) != 0 {
+        vmsa.init_long_mode(guest_context.rip, guest_context.rsp, guest_context.cr3);
+    }
+
+    // Copy the specified registers into the VMSA.
+    vmsa.cr0 = guest_context.cr0;
+    vmsa.cr3 = guest_context.cr3;
+    vmsa.cr4 = guest_context.cr4;
+    vmsa.efer = guest_context.efer;
+    vmsa.rip = guest_context.rip;
+    vmsa.rax = guest_context.rax;
+    vmsa.rcx = guest_context.rcx;
+    vmsa.rdx = guest_context.rdx;
+    vmsa.rbx = guest_context.rbx;
+    vmsa.rsp = guest_context.rsp;
+    vmsa.rbp = guest_context.rbp;
+    vmsa.rsi = guest_context.rsi;
+    vmsa.rdi = guest_context.rdi;
+    vmsa.r8 = guest_context.r8;
+    vmsa.r9 = guest_context.r9;
+    vmsa.r10 = guest_context.r10;
+    vmsa.r11 = guest_context.r11;
+    vmsa.r12 = guest_context.r12;
+    vmsa.r13 = guest_context.r13;
+    vmsa.r14 = guest_context.r14;
+    vmsa.r15 = guest_context.r15;
+    let mut gdt = vmsa.get_segment(SegmentRegister::GDTR);
+    gdt.base = guest_context.gdt_base;
+    gdt.limit = guest_context.gdt_limit;
+    vmsa.set_segment(SegmentRegister::GDTR, gdt);
+
+    // If a non-zero code selector is specified, then set the code
+    // segment attributes based on EFER.LMA.
+    if guest_context.code_selector != 0 {
+        let mut cs = vmsa.get_segment(SegmentRegister::CS);
+        cs.selector = guest_context.code_selector;
+        let efer_lma = EFERFlags::LMA;
+        if (vmsa.efer & efer_lma.bits()) != 0 {
+            cs.flags = VMSASegment::CODE64_FLAGS;
+        } else {
+            cs.flags = VMSASegment::CODE32_FLAGS;
+            cs.limit = 0xFFFFFFFF;
+        }
+        vmsa.set_segment(SegmentRegister::CS, cs);
+    }
+
+    let efer_svme = EFERFlags::SVME;
+    vmsa.efer &= !efer_svme.bits();
+
+    // If a non-zero data selector is specified, then modify the data
+    // segment attributes to be compatible with protected mode.
+    if guest_context.data_selector != 0 {
+        let mut ds = vmsa.get_segment(SegmentRegister::DS);
+        ds.selector = guest_context.data_selector;
+        ds.flags = 0xA93;
+        ds.limit = 0xFFFFFFFF;
+        for reg in [
+            SegmentRegister::DS,
+            SegmentRegister::SS,
+            SegmentRegister::ES,
+            SegmentRegister::FS,
+            SegmentRegister::GS,
+        ] {
+            vmsa.set_segment(reg, ds);
+        }
+    }
+}
+
+/// Converts an IGVM memory map, which may span multiple pages, into a list
+/// of usable memory regions.  See [`parse_memory_map_detailed()`] for the
+/// requirements on the map.
+fn parse_memory_map(
+    memory_map: &[IGVM_VHS_MEMORY_MAP_ENTRY],
+) -> Result<Vec<MemoryRegion<PhysAddr>>, SvsmError> {
+    Ok(parse_memory_map_detailed(memory_map)?
+        .into_iter()
+        .filter(|entry| entry.entry_type == MemoryMapEntryType::MEMORY)
+        .map(|entry| entry.region)
+        .collect())
+}
+
+/// Converts an IGVM memory map, which may span multiple pages, into a list
+/// of regions of any type.  The entries must be non-overlapping and strictly
+/// increasing, and the list ends at the first entry with a zero page count
+/// or at the end of the map.
+fn parse_memory_map_detailed(
+    memory_map: &[IGVM_VHS_MEMORY_MAP_ENTRY],
+) -> Result<Vec<IgvmMemoryRegion>, SvsmError> {
+    // Count the number of memory entries present.  They must be
+    // non-overlapping and strictly increasing.
+    let mut number_of_entries = 0;
+    let mut next_page_number = 0;
+    for entry in memory_map.iter() {
+        if entry.number_of_pages == 0 {
+            break;
+        }
+        if entry.starting_gpa_page_number < next_page_number {
+            return Err(SvsmError::Firmware);
+        }
+        let next_supplied_page_number = entry.starting_gpa_page_number + entry.number_of_pages;
+        if next_supplied_page_number < next_page_number {
+            return Err(SvsmError::Firmware);
+        }
+        next_page_number = next_supplied_page_number;
+        number_of_entries += 1;
+    }
+
+    // Now loop over the supplied entires and add a region for each.
+    let mut regions: Vec<IgvmMemoryRegion> = Vec::new();
+    for entry in memory_map.iter().take(number_of_entries) {
+        let starting_page: usize = entry.starting_gpa_page_number.try_into().unwrap();
+        let number_of_pages: usize = entry.number_of_pages.try_into().unwrap();
+        regions.push(IgvmMemoryRegion {
+            region: MemoryRegion::new(
+                PhysAddr::new(starting_page * PAGE_SIZE),
+                number_of_pages * PAGE_SIZE,
+            ),
+            entry_type: entry.entry_type,
+            flags: entry.flags,
+        });
+    }
+
+    Ok(regions)
+}
+
+fn build_guest_memory_map(
+    usable: &[MemoryRegion<PhysAddr>],
+    host: &[IgvmMemoryRegion],
+) -> Vec<IgvmMemoryRegion> {
+    let mut map: Vec<IgvmMemoryRegion> = usable
+        .iter()
+        .map(|region| {
+            let flags = host
+                .iter()
+                .find(|entry| {
+                    entry.entry_type == MemoryMapEntryType::MEMORY
+                        && entry.region.contains_region(region)
+                })
+                .map_or(0, |entry| entry.flags);
+            IgvmMemoryRegion {
+                region: *region,
+                entry_type: MemoryMapEntryType::MEMORY,
+                flags,
+            }
+        })
+        .chain(
+            host.iter()
+                .filter(|entry| entry.entry_type != MemoryMapEntryType::MEMORY)
+                .copied(),
+        )
+        .collect();
+    map.sort_unstable_by_key(|entry| entry.region.start());
+    map
+}
+
+/// Checks that each firmware region lies either entirely inside guest memory
+/// or entirely outside of it.  A region straddling the boundary indicates a
+/// malformed image.
+fn check_fw_regions(
+    fw_regions: &[MemoryRegion<PhysAddr>],
+    memory_regions: &[MemoryRegion<PhysAddr>],
+) -> Result<(), SvsmError> {
+    // Adjacent memory map entries form a single range of guest memory.
+    let memory_regions = coalesce_regions(memory_regions);
+    for fw_region in fw_regions {
+        let partial = memory_regions
+            .iter()
+            .find(|mem| mem.overlap(fw_region) && !mem.contains_region(fw_region));
+        if let Some(mem) = partial {
+            log::error!(
+                "Firmware region {:#018x}-{:#018x} partially overlaps memory {:#018x}-{:#018x}",
+                fw_region.start(),
+                fw_region.end(),
+                mem.start(),
+                mem.end()
+            );
+            return Err(SvsmError::Firmware);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRIES_PER_PAGE: usize = PAGE_SIZE / size_of::<IGVM_VHS_MEMORY_MAP_ENTRY>();
+
+    fn entry(start: u64, pages: u64) -> IGVM_VHS_MEMORY_MAP_ENTRY {
+        IGVM_VHS_MEMORY_MAP_ENTRY {
+            starting_gpa_page_number: start,
+            number_of_pages: pages,
+            entry_type: MemoryMapEntryType::MEMORY,
+            flags: 0,
+            reserved: 0,
+        }
+    }
+
+    #[test]
+    fn test_memory_map_spans_pages() {
+        // Fill more than one page worth of entries, leaving a terminator.
+        let count = ENTRIES_PER_PAGE + 2;
+        let mut map: Vec<IGVM_VHS_MEMORY_MAP_ENTRY> =
+            (0..count as u64).map(|i| entry(i * 2, 1)).collect();
+        map.push(entry(0, 0));
+
+        let regions = parse_memory_map(&map).unwrap();
+        assert_eq!(regions.len(), count);
+        let last = regions.last().unwrap();
+        assert_eq!(last.start(), PhysAddr::new((count - 1) * 2 * PAGE_SIZE));
+    }
+
+    #[test]
+    fn test_memory_map_ordering_across_pages() {
+        let mut map: Vec<IGVM_VHS_MEMORY_MAP_ENTRY> = (0..ENTRIES_PER_PAGE as u64)
+            .map(|i| entry(i * 2, 1))
+            .collect();
+        // The first entry of the second page overlaps the last entry of the
+        // first page.
+        map.push(entry((ENTRIES_PER_PAGE as u64 - 1) * 2, 1));
+
+        assert!(parse_memory_map(&map).is_err());
+    }
+
+    #[test]
+    fn test_memory_map_detailed() {
+        let map = [
+            entry(0, 0x10),
+            IGVM_VHS_MEMORY_MAP_ENTRY {
+                entry_type: MemoryMapEntryType::PLATFORM_RESERVED,
+                flags: 1,
+                ..entry(0x10, 0x10)
+            },
+            entry(0x20, 0x10),
+            entry(0, 0),
+        ];
+
+        let detailed = parse_memory_map_detailed(&map).unwrap();
+        assert_eq!(detailed.len(), 3);
+        assert_eq!(
+            detailed[1].entry_type,
+            MemoryMapEntryType::PLATFORM_RESERVED
+        );
+        assert_eq!(detailed[1].flags, 1);
+        assert_eq!(detailed[1].region.start(), PhysAddr::new(0x10 * PAGE_SIZE));
+
+        // Only usable memory is reported by the plain parser.
+        let regions = parse_memory_map(&map).unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[1].start(), PhysAddr::new(0x20 * PAGE_SIZE));
+    }
+
+    #[test]
+    fn test_guest_memory_map_types() {
+        let region = |start: usize, len: usize| MemoryRegion::new(PhysAddr::new(start), len);
+        let host = [
+            IgvmMemoryRegion {
+                region: region(0, 0x100000),
+                entry_type: MemoryMapEntryType::MEMORY,
+                flags: 0,
+            },
+            IgvmMemoryRegion {
+                region: region(0x100000, 0x10000),
+                entry_type: MemoryMapEntryType::PLATFORM_RESERVED,
+                flags: 0,
+            },
+            IgvmMemoryRegion {
+                region: region(0x200000, 0x100000),
+                entry_type: MemoryMapEntryType::MEMORY,
+                flags: 2,
+            },
+        ];
+        // The SVSM has carved a hole out of the second memory range.
+        let usable = [
+            region(0, 0x100000),
+            region(0x200000, 0x40000),
+            region(0x280000, 0x80000),
+        ];
+
+        let map = build_guest_memory_map(&usable, &host);
+        let summary: Vec<(usize, MemoryMapEntryType, u16)> = map
+            .iter()
+            .map(|e| (e.region.start().into(), e.entry_type, e.flags))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0, MemoryMapEntryType::MEMORY, 0),
+                (0x100000, MemoryMapEntryType::PLATFORM_RESERVED, 0),
+                (0x200000, MemoryMapEntryType::MEMORY, 2),
+                (0x280000, MemoryMapEntryType::MEMORY, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fw_regions_vs_memory_map() {
+        let region = |start: usize, len: usize| MemoryRegion::new(PhysAddr::new(start), len);
+        let memory = [
+            region(0, 0x9f000),
+            region(0x9f000, 0x1000),
+            region(0x100000, 0x100000),
+        ];
+
+        // Regions fully inside or fully outside memory are accepted, even if
+        // they span adjacent memory map entries.
+        assert!(check_fw_regions(&[region(0, 0xa0000)], &memory).is_ok());
+        assert!(check_fw_regions(&[region(0xc0000000, 0x1000)], &memory).is_ok());
+
+        // A region straddling the end of memory is rejected.
+        assert!(check_fw_regions(&[region(0x1ff000, 0x2000)], &memory).is_err());
+        assert!(check_fw_regions(&[region(0x90000, 0x20000)], &memory).is_err());
+    }
+
+    #[test]
+    fn test_long_mode_guest_context() {
+        let context = IgvmGuestContext {
+            cr0: 0x8001_0033,
+            cr3: 0x10_0000,
+            cr4: 0x620,
+            efer: (EFERFlags::LME | EFERFlags::LMA | EFERFlags::SVME).bits(),
+            code_selector: 0x8,
+            data_selector: 0x10,
+            rip: 0xff_f000,
+            rsp: 0x20_0000,
+            ..Default::default()
+        };
+        let mut vmsa = VMSA::default();
+        init_vmsa_from_context(&mut vmsa, &context);
+
+        assert_eq!({ vmsa.rip }, 0xff_f000);
+        assert_eq!({ vmsa.rsp }, 0x20_0000);
+        assert_eq!({ vmsa.cr3 }, 0x10_0000);
+        assert_eq!({ vmsa.efer }, (EFERFlags::LME | EFERFlags::LMA).bits());
+
+        let cs = vmsa.get_segment(SegmentRegister::CS);
+        assert_eq!({ cs.selector }, 0x8);
+        assert_eq!({ cs.flags }, VMSASegment::CODE64_FLAGS);
+        for reg in [
+            SegmentRegister::DS,
+            SegmentRegister::SS,
+            SegmentRegister::ES,
+            SegmentRegister::FS,
+            SegmentRegister::GS,
+        ] {
+            let seg = vmsa.get_segment(reg);
+            assert_eq!({ seg.selector }, 0x10);
+            assert_eq!({ seg.flags }, 0xA93);
+        }
+        // The system segments come from the clean long-mode state.
+        assert_eq!({ vmsa.get_segment(SegmentRegister::TR).flags }, 0x8b);
+    }
+}
@@ -5,7 +5,9 @@
 // Author: Joerg Roedel <jroedel@suse.de>
 
 pub mod rwlock;
+pub mod seqlock;
 pub mod spinlock;
 
-pub use rwlock::{RWLock, ReadLockGuard, WriteLockGuard};
+pub use rwlock::{RWLock, ReadLockGuard, UpgradeableReadGuard, WriteLockGuard};
+pub use seqlock::SeqLock;
 pub use spinlock::{LockGuard, SpinLock};
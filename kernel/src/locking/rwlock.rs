@@ -6,7 +6,7 @@
 
 use core::cell::UnsafeCell;
 use core::ops::{Deref, DerefMut};
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 /// A guard that provides read access to the data protected by [`RWLock`]
 #[derive(Debug)]
@@ -71,12 +71,84 @@ impl<T> DerefMut for WriteLockGuard<'_, T> {
     }
 }
 
+/// A guard that provides read access to the data protected by [`RWLock`]
+/// and which can later be upgraded to exclusive write access. Only one
+/// upgradeable reader can exist at a time, but it coexists with ordinary
+/// readers.
+#[derive(Debug)]
+#[must_use = "if unused the RWLock will immediately unlock"]
+pub struct UpgradeableReadGuard<'a, T> {
+    /// Reference to the associated [`RWLock`]
+    lock: &'a RWLock<T>,
+}
+
+impl<'a, T> UpgradeableReadGuard<'a, T> {
+    /// Attempts to upgrade the upgradeable read lock into a write lock.
+    /// The upgrade succeeds only if no other readers currently hold the
+    /// lock and no writer is waiting for it.
+    ///
+    /// # Returns
+    ///
+    /// A [`WriteLockGuard`] on success, or the original
+    /// [`UpgradeableReadGuard`] if other readers or a waiting writer
+    /// prevent the upgrade. Callers must not spin on this function while
+    /// a writer is waiting, as the writer is in turn waiting for this
+    /// reader to drop.
+    pub fn try_upgrade(self) -> Result<WriteLockGuard<'a, T>, Self> {
+        if self
+            .lock
+            .rwlock
+            .compare_exchange(
+                compose_val(1, 0),
+                compose_val(0, 1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return Err(self);
+        }
+
+        // The read reference has been converted into the write lock, so
+        // only the upgradeable slot needs to be released here.
+        let lock = self.lock;
+        core::mem::forget(self);
+        lock.upgradeable.store(false, Ordering::Release);
+
+        Ok(WriteLockGuard {
+            rwlock: &lock.rwlock,
+            data: unsafe { &mut *lock.data.get() },
+        })
+    }
+}
+
+/// Implements the behavior of the [`UpgradeableReadGuard`] when it is dropped
+impl<T> Drop for UpgradeableReadGuard<'_, T> {
+    /// Release the read lock and the upgradeable slot
+    fn drop(&mut self) {
+        self.lock.rwlock.fetch_sub(1, Ordering::Release);
+        self.lock.upgradeable.store(false, Ordering::Release);
+    }
+}
+
+/// Implements the behavior of dereferencing the [`UpgradeableReadGuard`]
+/// to access the protected data.
+impl<T> Deref for UpgradeableReadGuard<'_, T> {
+    type Target = T;
+    /// Allow reading the protected data through deref
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
 /// A simple Read-Write Lock (RWLock) that allows multiple readers or
 /// one exclusive writer.
 #[derive(Debug)]
 pub struct RWLock<T> {
     /// An atomic 64-bit integer used for synchronization
     rwlock: AtomicU64,
+    /// Set while an [`UpgradeableReadGuard`] exists
+    upgradeable: AtomicBool,
     /// An UnsafeCell for interior mutability
     data: UnsafeCell<T>,
 }
@@ -151,6 +223,7 @@ impl<T> RWLock<T> {
     pub const fn new(data: T) -> Self {
         RWLock {
             rwlock: AtomicU64::new(0),
+            upgradeable: AtomicBool::new(false),
             data: UnsafeCell::new(data),
         }
     }
@@ -222,6 +295,29 @@ impl<T> RWLock<T> {
         }
     }
 
+    /// This function acquires a read lock which can later be upgraded to a
+    /// write lock without releasing it. It waits until no other
+    /// upgradeable reader exists, but does not exclude ordinary readers.
+    ///
+    /// # Returns
+    ///
+    /// An [`UpgradeableReadGuard`] that provides read access to the
+    /// protected data.
+    pub fn upgradeable_read(&self) -> UpgradeableReadGuard<'_, T> {
+        while self
+            .upgradeable
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        // Take a plain read reference on behalf of the upgradeable guard.
+        core::mem::forget(self.lock_read());
+
+        UpgradeableReadGuard { lock: self }
+    }
+
     /// This function ensures exclusive access for a single writer and waits
     /// for all readers to finish before granting access to the writer.
     ///
@@ -329,4 +425,31 @@ mod tests {
         drop(read_guard1);
         drop(read_guard2);
     }
+
+    #[test]
+    fn test_upgradeable_read() {
+        use crate::locking::*;
+        let rwlock = RWLock::new(7);
+
+        let upgradeable = rwlock.upgradeable_read();
+        let reader = rwlock.lock_read();
+        assert_eq!(*upgradeable, 7);
+        assert_eq!(*reader, 7);
+
+        // A plain reader prevents the upgrade.
+        let upgradeable = upgradeable.try_upgrade().unwrap_err();
+
+        drop(reader);
+        let mut writer = upgradeable.try_upgrade().unwrap();
+        *writer = 8;
+        drop(writer);
+
+        // The upgradeable slot is available again once the writer drops.
+        let upgradeable = rwlock.upgradeable_read();
+        assert_eq!(*upgradeable, 8);
+        drop(upgradeable);
+
+        let writer = rwlock.lock_write();
+        assert_eq!(*writer, 8);
+    }
 }
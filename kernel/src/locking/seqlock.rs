@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) Microsoft Corporation
+//
+// Author: agent <agent@local>
+
+use core::cell::UnsafeCell;
+use core::ptr;
+use core::sync::atomic::{fence, AtomicU64, Ordering};
+
+/// A sequence lock for read-mostly data.
+///
+/// Readers never write to shared state: they take a snapshot of the
+/// protected value and retry if a writer was active in the meantime, which
+/// is detected through a sequence counter that is odd while a write is in
+/// progress. Writers are serialized against each other.
+///
+/// Since readers copy the value out and may need to retry, `T` must be
+/// [`Copy`] and should be small, e.g. a handful of machine words.
+///
+/// # Examples
+///
+/// ```
+/// use svsm::locking::SeqLock;
+///
+/// let seq_lock = SeqLock::new((1u64, 2u64));
+/// seq_lock.write((3, 4));
+/// assert_eq!(seq_lock.read(), (3, 4));
+/// ```
+#[derive(Debug, Default)]
+pub struct SeqLock<T: Copy> {
+    /// Sequence counter, odd while a write is in progress
+    seq: AtomicU64,
+    /// The protected data
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Copy + Send> Send for SeqLock<T> {}
+unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+
+impl<T: Copy> SeqLock<T> {
+    /// Creates a new SeqLock instance with the specified initial data.
+    pub const fn new(data: T) -> Self {
+        SeqLock {
+            seq: AtomicU64::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Returns a consistent copy of the protected data, spinning while a
+    /// write is in progress.
+    pub fn read(&self) -> T {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq & 1 != 0 {
+                core::hint::spin_loop();
+                continue;
+            }
+
+            // SAFETY: the pointer is valid and aligned. The copy may be torn
+            // by a concurrent writer, in which case the sequence check below
+            // fails and the copy is discarded. Volatile access prevents the
+            // compiler from assuming the data is stable.
+            let data = unsafe { ptr::read_volatile(self.data.get()) };
+
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return data;
+            }
+        }
+    }
+
+    /// Replaces the protected data, waiting for any other writer to finish
+    /// first.
+    pub fn write(&self, data: T) {
+        let mut seq = self.seq.load(Ordering::Relaxed);
+        loop {
+            if seq & 1 == 0 {
+                match self.seq.compare_exchange_weak(
+                    seq,
+                    seq + 1,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(cur) => seq = cur,
+                }
+            } else {
+                core::hint::spin_loop();
+                seq = self.seq.load(Ordering::Relaxed);
+            }
+        }
+
+        // Make sure the odd sequence number is visible before any of the
+        // data updates.
+        fence(Ordering::Release);
+        // SAFETY: the pointer is valid and aligned, and the odd sequence
+        // number excludes other writers. Readers racing with this write will
+        // discard what they read.
+        unsafe { ptr::write_volatile(self.data.get(), data) };
+        self.seq.store(seq + 2, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seq_lock() {
+        let seq_lock = SeqLock::new(1u64);
+        assert_eq!(seq_lock.read(), 1);
+
+        seq_lock.write(2);
+        assert_eq!(seq_lock.read(), 2);
+        assert_eq!(seq_lock.seq.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    #[cfg(not(test_in_svsm))]
+    fn test_seq_lock_no_torn_reads() {
+        extern crate std;
+        use std::thread;
+
+        const WRITES: u64 = 100_000;
+        let seq_lock = SeqLock::new([0u64; 4]);
+
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| loop {
+                    let val = seq_lock.read();
+                    assert!(val.iter().all(|v| *v == val[0]), "torn read {:?}", val);
+                    if val[0] == WRITES {
+                        break;
+                    }
+                });
+            }
+
+            for i in 1..=WRITES {
+                seq_lock.write([i; 4]);
+            }
+        });
+    }
+}
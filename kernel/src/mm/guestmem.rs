@@ -5,10 +5,11 @@
 // Author: Joerg Roedel <jroedel@suse.de>
 
 use crate::address::{Address, VirtAddr};
+use crate::cpu::control_regs::SmapGuard;
 use crate::error::SvsmError;
 
 use core::arch::asm;
-use core::mem::{size_of, MaybeUninit};
+use core::mem::{size_of, size_of_val, MaybeUninit};
 
 #[allow(dead_code)]
 #[inline]
@@ -159,7 +160,11 @@ unsafe fn read_u64(v: VirtAddr) -> Result<u64, SvsmError> {
 
 #[inline]
 unsafe fn do_movsb<T>(src: *const T, dst: *mut T) -> Result<(), SvsmError> {
-    let size: usize = size_of::<T>();
+    unsafe { do_movsb_bytes(src.cast(), dst.cast(), size_of::<T>()) }
+}
+
+#[inline]
+unsafe fn do_movsb_bytes(src: *const u8, dst: *mut u8, size: usize) -> Result<(), SvsmError> {
     let mut rcx: u64;
 
     asm!("1:cld
@@ -211,6 +216,7 @@ impl<T: Copy> GuestPtr<T> {
     #[inline]
     pub unsafe fn read(&self) -> Result<T, SvsmError> {
         let mut buf = MaybeUninit::<T>::uninit();
+        let _guard = SmapGuard::new();
 
         unsafe {
             do_movsb(self.ptr, buf.as_mut_ptr())?;
@@ -229,6 +235,7 @@ impl<T: Copy> GuestPtr<T> {
     /// with the appropriate write permissions.
     #[inline]
     pub unsafe fn write(&self, buf: T) -> Result<(), SvsmError> {
+        let _guard = SmapGuard::new();
         unsafe { do_movsb(&buf, self.ptr) }
     }
 
@@ -243,6 +250,7 @@ impl<T: Copy> GuestPtr<T> {
     /// with the appropriate write permissions.
     #[inline]
     pub unsafe fn write_ref(&self, buf: &T) -> Result<(), SvsmError> {
+        let _guard = SmapGuard::new();
         unsafe { do_movsb(buf, self.ptr) }
     }
 
@@ -257,6 +265,98 @@ impl<T: Copy> GuestPtr<T> {
     }
 }
 
+/// A contiguous array of `T` in guest memory.  The bounds of the array are
+/// checked once on creation, and bulk copies are performed with a single
+/// SMAP override.
+#[derive(Debug)]
+pub struct GuestSlice<T: Copy> {
+    ptr: GuestPtr<T>,
+    len: usize,
+}
+
+impl<T: Copy> GuestSlice<T> {
+    /// Creates a guest slice of `len` elements starting at `v`.
+    ///
+    /// # Returns
+    ///
+    /// Returns an error if the slice would wrap around the end of the
+    /// address space.
+    pub fn new(v: VirtAddr, len: usize) -> Result<Self, SvsmError> {
+        len.checked_mul(size_of::<T>())
+            .and_then(|size| v.checked_add(size))
+            .ok_or(SvsmError::InvalidAddress)?;
+        Ok(Self {
+            ptr: GuestPtr::new(v),
+            len,
+        })
+    }
+
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn element(&self, index: usize) -> Result<*mut T, SvsmError> {
+        if index < self.len {
+            Ok(self.ptr.ptr.wrapping_add(index))
+        } else {
+            Err(SvsmError::InvalidAddress)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The caller must verify not to read arbitrary memory, as this function
+    /// doesn't make any checks in that regard.
+    ///
+    /// # Returns
+    ///
+    /// Returns an error if `index` is out of bounds or the element is not
+    /// mapped.
+    pub unsafe fn get(&self, index: usize) -> Result<T, SvsmError> {
+        let src = self.element(index)?;
+        unsafe { GuestPtr::from_ptr(src).read() }
+    }
+
+    /// # Safety
+    ///
+    /// The caller must verify not to corrupt arbitrary memory, as this function
+    /// doesn't make any checks in that regard.
+    ///
+    /// # Returns
+    ///
+    /// Returns an error if `index` is out of bounds or the element is not
+    /// mapped with the appropriate write permissions.
+    pub unsafe fn set(&self, index: usize, val: T) -> Result<(), SvsmError> {
+        let dst = self.element(index)?;
+        unsafe { GuestPtr::from_ptr(dst).write(val) }
+    }
+
+    /// Copies all elements of `src` into the start of the guest slice.
+    ///
+    /// # Safety
+    ///
+    /// The caller must verify not to corrupt arbitrary memory, as this function
+    /// doesn't make any checks in that regard.
+    ///
+    /// # Returns
+    ///
+    /// Returns an error if `src` is longer than the guest slice or the
+    /// destination is not mapped with the appropriate write permissions.
+    pub unsafe fn copy_from_slice(&self, src: &[T]) -> Result<(), SvsmError> {
+        if src.len() > self.len {
+            return Err(SvsmError::InvalidAddress);
+        }
+        let _guard = SmapGuard::new();
+        unsafe { do_movsb_bytes(src.as_ptr().cast(), self.ptr.ptr.cast(), size_of_val(src)) }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -311,4 +411,32 @@ mod tests {
         let err = unsafe { ptr.read() };
         assert!(err.is_err());
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "inline assembly")]
+    fn test_guest_slice() {
+        let mut test_buffer = [0u32; 4];
+        let test_addr = VirtAddr::from(test_buffer.as_mut_ptr());
+        let slice = GuestSlice::<u32>::new(test_addr, test_buffer.len()).unwrap();
+        assert_eq!(slice.len(), 4);
+
+        // SAFETY: slice covers exactly test_buffer.
+        unsafe {
+            slice.copy_from_slice(&[1, 2, 3]).unwrap();
+            slice.set(3, 4).unwrap();
+            assert_eq!(slice.get(2).unwrap(), 3);
+            assert!(slice.get(4).is_err());
+            assert!(slice.set(4, 5).is_err());
+            assert!(slice.copy_from_slice(&[0; 5]).is_err());
+        }
+        assert_eq!(test_buffer, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_guest_slice_overflow() {
+        let addr = VirtAddr::from(0xffff_ffff_ffff_f000u64);
+        assert!(GuestSlice::<u64>::new(addr, 0x100).is_ok());
+        assert!(GuestSlice::<u64>::new(addr, 0x400).is_err());
+        assert!(GuestSlice::<u64>::new(addr, usize::MAX).is_err());
+    }
 }
@@ -18,7 +18,7 @@ pub mod virtualrange;
 pub mod vm;
 
 pub use address_space::*;
-pub use guestmem::GuestPtr;
+pub use guestmem::{GuestPtr, GuestSlice};
 pub use memory::{valid_phys_address, writable_phys_addr};
 pub use ptguards::*;
 
@@ -7,6 +7,7 @@
 use crate::address::{PhysAddr, VirtAddr};
 use crate::cpu::percpu::PerCpu;
 use crate::error::SvsmError;
+use crate::greq::pld_report::AttestationReport;
 use crate::io::IOPort;
 use crate::platform::native::NativePlatform;
 use crate::platform::snp::SnpPlatform;
@@ -15,6 +16,7 @@ use crate::types::PageSize;
 use crate::utils::immut_after_init::ImmutAfterInitCell;
 use crate::utils::MemoryRegion;
 
+use alloc::vec::Vec;
 use bootlib::platform::SvsmPlatformType;
 
 pub mod guest_cpu;
@@ -32,6 +34,15 @@ pub struct PageEncryptionMasks {
     pub phys_addr_sizes: u32,
 }
 
+impl PageEncryptionMasks {
+    /// Returns the width of the physical address mask when the shared bit
+    /// is described by vTOM.  vTOM is a single address bit, so the addressable
+    /// private range extends up to, but not including, that bit.
+    pub fn vtom_addr_mask_width(vtom: usize) -> u32 {
+        vtom.trailing_zeros()
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum PageStateChangeOp {
     Private,
@@ -40,6 +51,29 @@ pub enum PageStateChangeOp {
     Unsmash,
 }
 
+/// Evidence returned by [`SvsmPlatform::attest()`], with one variant per
+/// confidential computing technology.
+#[derive(Clone, Copy, Debug)]
+pub enum AttestationEvidence {
+    /// An SEV-SNP attestation report signed by the PSP.
+    Snp(AttestationReport),
+}
+
+/// Sorts `regions` by address and merges any that touch or overlap.
+pub fn coalesce_regions(regions: &[MemoryRegion<PhysAddr>]) -> Vec<MemoryRegion<PhysAddr>> {
+    let mut sorted = regions.to_vec();
+    sorted.sort_unstable_by_key(|r| r.start());
+
+    let mut merged: Vec<MemoryRegion<PhysAddr>> = Vec::with_capacity(sorted.len());
+    for region in sorted {
+        match merged.last_mut() {
+            Some(last) if last.contiguous(&region) => *last = last.merge(&region),
+            _ => merged.push(region),
+        }
+    }
+    merged
+}
+
 /// This defines a platform abstraction to permit the SVSM to run on different
 /// underlying architectures.
 pub trait SvsmPlatform {
@@ -59,6 +93,11 @@ pub trait SvsmPlatform {
     /// Determines the paging encryption masks for the current architecture.
     fn get_page_encryption_masks(&self, vtom: usize) -> PageEncryptionMasks;
 
+    /// Returns the position of the page table bit that marks a page as
+    /// encrypted, or `None` if encryption is not described by a single
+    /// C-bit (for example, when the shared bit is described by vTOM).
+    fn encryption_bit_position(&self) -> Option<u32>;
+
     /// Establishes state required for guest/host communication.
     fn setup_guest_host_comm(&mut self, cpu: &PerCpu, is_bsp: bool);
 
@@ -73,11 +112,36 @@ pub trait SvsmPlatform {
         op: PageStateChangeOp,
     ) -> Result<(), SvsmError>;
 
-    /// Marks a range of pages as valid for use as private pages.
-    fn validate_page_range(&self, region: MemoryRegion<VirtAddr>) -> Result<(), SvsmError>;
+    /// Performs a page state change for several regions at once.  Adjacent
+    /// or overlapping regions are coalesced first, so that the platform can
+    /// issue as few requests as possible.
+    fn page_state_change_batch(
+        &self,
+        regions: &[MemoryRegion<PhysAddr>],
+        size: PageSize,
+        op: PageStateChangeOp,
+    ) -> Result<(), SvsmError> {
+        for region in coalesce_regions(regions) {
+            self.page_state_change(region, size, op)?;
+        }
+        Ok(())
+    }
+
+    /// Marks a range of pages as valid for use as private pages.  `paddr` is
+    /// the physical address that the start of `region` maps to.
+    fn validate_page_range(
+        &self,
+        region: MemoryRegion<VirtAddr>,
+        paddr: PhysAddr,
+    ) -> Result<(), SvsmError>;
 
-    /// Marks a range of pages as invalid for use as private pages.
-    fn invalidate_page_range(&self, region: MemoryRegion<VirtAddr>) -> Result<(), SvsmError>;
+    /// Marks a range of pages as invalid for use as private pages.  `paddr`
+    /// is the physical address that the start of `region` maps to.
+    fn invalidate_page_range(
+        &self,
+        region: MemoryRegion<VirtAddr>,
+        paddr: PhysAddr,
+    ) -> Result<(), SvsmError>;
 
     /// Configures the use of alternate injection as requested.
     fn configure_alternate_injection(&mut self, alt_inj_requested: bool) -> Result<(), SvsmError>;
@@ -96,6 +160,32 @@ pub trait SvsmPlatform {
 
     /// Perform an EOI of the current interrupt.
     fn eoi(&self);
+
+    /// Returns the TSC frequency in kHz, or `None` if the frequency cannot
+    /// be determined and must be calibrated by the caller.
+    fn tsc_khz(&self) -> Option<u32>;
+
+    /// Fills `buf` with random data from a hardware entropy source.  The
+    /// entire buffer is filled, or an error is returned.
+    fn get_random(&self, buf: &mut [u8]) -> Result<(), SvsmError>;
+
+    /// Starts an additional processor, which begins execution at
+    /// `start_rip`, and waits for it to report itself online.
+    fn start_cpu(&self, cpu: &PerCpu, start_rip: u64, vtom: u64) -> Result<(), SvsmError>;
+
+    /// Raises exception `vector`, with an optional error code, in the guest
+    /// running on `cpu` when it is next entered.  `cpu` must be the current
+    /// CPU.
+    fn inject_guest_exception(
+        &self,
+        cpu: &PerCpu,
+        vector: u8,
+        error_code: Option<u32>,
+    ) -> Result<(), SvsmError>;
+
+    /// Produces hardware-rooted evidence of the SVSM's identity that binds
+    /// the caller-supplied `report_data`.
+    fn attest(&self, report_data: &[u8; 64]) -> Result<AttestationEvidence, SvsmError>;
 }
 
 //FIXME - remove Copy trait
@@ -131,3 +221,35 @@ impl SvsmPlatformCell {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coalesce_regions() {
+        let region = |start: usize, len: usize| MemoryRegion::new(PhysAddr::from(start), len);
+        let regions = [
+            region(0x5000, 0x1000),
+            region(0x1000, 0x1000),
+            region(0x2000, 0x2000),
+            region(0x8000, 0x1000),
+            region(0x3000, 0x1000),
+        ];
+        let merged: Vec<(usize, usize)> = coalesce_regions(&regions)
+            .iter()
+            .map(|r| (r.start().into(), r.len()))
+            .collect();
+        assert_eq!(
+            merged,
+            [(0x1000, 0x3000), (0x5000, 0x1000), (0x8000, 0x1000)]
+        );
+        assert!(coalesce_regions(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_vtom_addr_mask_width() {
+        assert_eq!(PageEncryptionMasks::vtom_addr_mask_width(1 << 46), 46);
+        assert_eq!(PageEncryptionMasks::vtom_addr_mask_width(1 << 51), 51);
+    }
+}
@@ -5,19 +5,292 @@
 // Author: Jon Lange <jlange@microsoft.com>
 
 use crate::address::{PhysAddr, VirtAddr};
-use crate::cpu::cpuid::CpuidResult;
-use crate::cpu::msr::write_msr;
+use crate::cpu::control_regs::{read_cr0, read_cr4};
+use crate::cpu::cpuid::{tsc_khz_from_cpuid, CpuidResult};
+use crate::cpu::efer::{read_efer, EFERFlags};
+use crate::cpu::gdt::gdt;
+use crate::cpu::idt::common::idt;
+use crate::cpu::msr::{rdtsc, write_msr};
 use crate::cpu::percpu::PerCpu;
+use crate::cpu::rng::{fill_random, rdrand64, X86_FEATURE_RDRAND};
+use crate::cpu::smp::wait_for_cpu_online;
 use crate::error::SvsmError;
-use crate::platform::{IOPort, PageEncryptionMasks, PageStateChangeOp, SvsmPlatform};
+use crate::mm::pagetable::PTEntryFlags;
+use crate::mm::{writable_phys_addr, PerCPUPageMappingGuard};
+use crate::platform::{
+    AttestationEvidence, IOPort, PageEncryptionMasks, PageStateChangeOp, SvsmPlatform,
+};
 use crate::svsm_console::NativeIOPort;
-use crate::types::PageSize;
+use crate::types::{PageSize, PAGE_SIZE, SVSM_CS, SVSM_DS};
 use crate::utils::MemoryRegion;
 
+use alloc::vec::Vec;
+use core::arch::global_asm;
+use core::mem::{offset_of, size_of};
+use core::ptr;
+
 static CONSOLE_IO: NativeIOPort = NativeIOPort::new();
 
+const APIC_MSR_EOI: u32 = 0x80B;
 const APIC_MSR_ICR: u32 = 0x830;
 
+// ICR delivery modes and flags used for AP startup.
+const APIC_ICR_INIT: u64 = 5 << 8;
+const APIC_ICR_SIPI: u64 = 6 << 8;
+const APIC_ICR_LEVEL_ASSERT: u64 = 1 << 14;
+const APIC_ICR_TRIGGER_LEVEL: u64 = 1 << 15;
+
+// Delays required by the INIT-SIPI-SIPI protocol, in microseconds.
+const AP_INIT_DELAY_US: u64 = 10_000;
+const AP_SIPI_DELAY_US: u64 = 200;
+
+// TSC frequency assumed for delays when the real frequency is unknown.  It
+// is deliberately high so that delays err on the long side.
+const FALLBACK_TSC_KHZ: u64 = 10_000_000;
+
+/// End of the low memory that can hold the AP startup trampoline.  The SIPI
+/// vector is the page number of the trampoline, so it must lie below 1 MB,
+/// and the ISA range above this address is not usable RAM.
+const AP_TRAMPOLINE_LIMIT: u64 = 0xa0000;
+
+/// Space at the top of the trampoline page used as the stack for the
+/// trampoline's transition out of real mode.
+const AP_TRAMPOLINE_STACK_SIZE: usize = 64;
+
+/// Processor state handed to an AP by the startup trampoline.  The layout
+/// must match the offsets used by the trampoline code below.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C, packed)]
+struct ApTrampolineParams {
+    cr0: u32,
+    cr3: u32,
+    cr4: u64,
+    efer: u64,
+    gdt_limit: u16,
+    gdt_base: u64,
+    idt_limit: u16,
+    idt_base: u64,
+    rsp: u64,
+    rip: u64,
+}
+
+extern "C" {
+    static ap_trampoline_start: u8;
+    static ap_trampoline_params: u8;
+    static ap_trampoline_end: u8;
+}
+
+// The trampoline starts in real mode at offset 0 of the page selected by the
+// SIPI vector, switches to protected mode through a temporary GDT held in the
+// trampoline itself, enables paging with the AP's page table and enters long
+// mode.  The AP's page table identity-maps the trampoline page for the
+// duration of the startup.  The 64-bit code then loads the SVSM GDT, IDT,
+// CR4 and stack and continues at the start address.  All references within
+// the trampoline are relative, so it runs correctly wherever it is copied.
+global_asm!(
+    r#"
+        .text
+        .code16
+        .globl ap_trampoline_start
+    ap_trampoline_start:
+        cli
+        movw    %cs, %ax
+        movw    %ax, %ds
+        movzwl  %ax, %esi
+        shll    $4, %esi
+
+        /* Relocate the temporary GDT descriptor and the far pointer into
+         * 32-bit code to the physical location of the trampoline. */
+        leal    (.Lap_gdt - ap_trampoline_start)(%esi), %eax
+        movl    %eax, (.Lap_gdt_desc - ap_trampoline_start + 2)
+        leal    (.Lap_code32 - ap_trampoline_start)(%esi), %eax
+        movl    %eax, (.Lap_jmp32 - ap_trampoline_start)
+
+        lgdtl   (.Lap_gdt_desc - ap_trampoline_start)
+        movl    %cr0, %eax
+        orl     $1, %eax
+        movl    %eax, %cr0
+        ljmpl   *(.Lap_jmp32 - ap_trampoline_start)
+
+        .code32
+    .Lap_code32:
+        movw    $0x18, %ax
+        movw    %ax, %ds
+        movw    %ax, %es
+        movw    %ax, %ss
+        leal    0x1000(%esi), %esp
+
+        /* Enable PAE, load the page table and enter long mode. */
+        movl    %cr4, %eax
+        orl     $0x20, %eax
+        movl    %eax, %cr4
+        movl    (ap_trampoline_params - ap_trampoline_start + {cr3})(%esi), %eax
+        movl    %eax, %cr3
+        movl    $0xc0000080, %ecx
+        movl    (ap_trampoline_params - ap_trampoline_start + {efer})(%esi), %eax
+        movl    (ap_trampoline_params - ap_trampoline_start + {efer} + 4)(%esi), %edx
+        wrmsr
+        movl    (ap_trampoline_params - ap_trampoline_start + {cr0})(%esi), %eax
+        movl    %eax, %cr0
+
+        pushl   $0x08
+        leal    (.Lap_code64 - ap_trampoline_start)(%esi), %eax
+        pushl   %eax
+        lret
+
+        .code64
+    .Lap_code64:
+        lgdt    (ap_trampoline_params + {gdt})(%rip)
+        lidt    (ap_trampoline_params + {idt})(%rip)
+        movq    (ap_trampoline_params + {cr4})(%rip), %rax
+        movq    %rax, %cr4
+
+        movw    ${ds}, %ax
+        movw    %ax, %ds
+        movw    %ax, %es
+        movw    %ax, %fs
+        movw    %ax, %gs
+        movw    %ax, %ss
+
+        movq    (ap_trampoline_params + {rsp})(%rip), %rsp
+        pushq   ${cs}
+        pushq   (ap_trampoline_params + {rip})(%rip)
+        lretq
+
+        .balign 8
+    .Lap_gdt:
+        .quad   0
+        .quad   0x00af9b000000ffff  /* 64-bit code */
+        .quad   0x00cf9b000000ffff  /* 32-bit code */
+        .quad   0x00cf93000000ffff  /* data */
+    .Lap_gdt_desc:
+        .word   .Lap_gdt_desc - .Lap_gdt - 1
+        .long   0
+    .Lap_jmp32:
+        .long   0
+        .word   0x10
+
+        .balign 8
+        .globl ap_trampoline_params
+    ap_trampoline_params:
+        .skip   {params_size}
+        .globl ap_trampoline_end
+    ap_trampoline_end:
+        "#,
+    cr0 = const offset_of!(ApTrampolineParams, cr0),
+    cr3 = const offset_of!(ApTrampolineParams, cr3),
+    cr4 = const offset_of!(ApTrampolineParams, cr4),
+    efer = const offset_of!(ApTrampolineParams, efer),
+    gdt = const offset_of!(ApTrampolineParams, gdt_limit),
+    idt = const offset_of!(ApTrampolineParams, idt_limit),
+    rsp = const offset_of!(ApTrampolineParams, rsp),
+    rip = const offset_of!(ApTrampolineParams, rip),
+    params_size = const size_of::<ApTrampolineParams>(),
+    cs = const SVSM_CS,
+    ds = const SVSM_DS,
+    options(att_syntax)
+);
+
+/// Selects the page that holds the AP startup trampoline: the first page
+/// of guest RAM below [`AP_TRAMPOLINE_LIMIT`] according to the memory map.
+/// Page 0 is skipped because it holds the real-mode interrupt vector table.
+/// SVSM memory and VMSA pages are excluded from the memory map, so the page
+/// belongs to the guest, which has not been started yet.
+fn ap_trampoline_page() -> Result<PhysAddr, SvsmError> {
+    (1..AP_TRAMPOLINE_LIMIT >> 12)
+        .map(|pfn| PhysAddr::from(pfn << 12))
+        .find(|&paddr| writable_phys_addr(paddr))
+        .ok_or(SvsmError::Mem)
+}
+
+/// The AP startup trampoline, installed in a low-memory page and
+/// identity-mapped in the page table of the CPU being started.  Dropping it
+/// removes the mapping and restores the original contents of the page.
+#[derive(Debug)]
+struct ApTrampoline<'a> {
+    cpu: &'a PerCpu,
+    paddr: PhysAddr,
+    saved: Vec<u8>,
+}
+
+impl<'a> ApTrampoline<'a> {
+    fn install(cpu: &'a PerCpu, params: &ApTrampolineParams) -> Result<Self, SvsmError> {
+        // SAFETY: the symbols are defined by the trampoline code above and
+        // only their addresses are used.
+        let (start, params_start, end) = unsafe {
+            (
+                ptr::addr_of!(ap_trampoline_start),
+                ptr::addr_of!(ap_trampoline_params),
+                ptr::addr_of!(ap_trampoline_end),
+            )
+        };
+        let code_size = end as usize - start as usize;
+        let params_offset = params_start as usize - start as usize;
+        assert!(code_size <= PAGE_SIZE - AP_TRAMPOLINE_STACK_SIZE);
+
+        let paddr = ap_trampoline_page()?;
+        let guard = PerCPUPageMappingGuard::create_4k(paddr)?;
+        let page = guard.virt_addr().as_mut_ptr::<u8>();
+
+        let mut saved = Vec::new();
+        saved
+            .try_reserve_exact(PAGE_SIZE)
+            .map_err(|_| SvsmError::Mem)?;
+        saved.resize(PAGE_SIZE, 0);
+
+        // The trampoline writes its stack and enables CR0.WP before leaving
+        // the page, so the mapping must be writable.
+        let vaddr = VirtAddr::from(u64::from(paddr));
+        cpu.get_pgtable().map_4k(
+            vaddr,
+            paddr,
+            PTEntryFlags::PRESENT
+                | PTEntryFlags::WRITABLE
+                | PTEntryFlags::ACCESSED
+                | PTEntryFlags::DIRTY,
+        )?;
+
+        // SAFETY: the mapping covers a full page, which is not accessed by
+        // anything else while APs are being started, and the trampoline
+        // code and parameters fit within it as asserted above.
+        unsafe {
+            page.copy_to_nonoverlapping(saved.as_mut_ptr(), PAGE_SIZE);
+            page.copy_from_nonoverlapping(start, code_size);
+            page.add(params_offset)
+                .cast::<ApTrampolineParams>()
+                .write_unaligned(*params);
+        }
+
+        Ok(Self { cpu, paddr, saved })
+    }
+
+    fn sipi_vector(&self) -> u64 {
+        u64::from(self.paddr) >> 12
+    }
+}
+
+impl Drop for ApTrampoline<'_> {
+    fn drop(&mut self) {
+        // The AP only caches this non-global translation until it switches
+        // to a task page table, and nothing else runs on its page table.
+        self.cpu
+            .get_pgtable()
+            .unmap_4k(VirtAddr::from(u64::from(self.paddr)));
+
+        match PerCPUPageMappingGuard::create_4k(self.paddr) {
+            // SAFETY: the mapping covers a full page, and the saved copy
+            // holds a full page.
+            Ok(guard) => unsafe {
+                guard
+                    .virt_addr()
+                    .as_mut_ptr::<u8>()
+                    .copy_from_nonoverlapping(self.saved.as_ptr(), PAGE_SIZE);
+            },
+            Err(e) => log::error!("Failed to restore AP trampoline page: {:?}", e),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct NativePlatform {}
 
@@ -25,6 +298,26 @@ impl NativePlatform {
     pub fn new() -> Self {
         Self {}
     }
+
+    fn send_ipi(&self, apic_id: u32, icr: u64) {
+        // In x2APIC mode, the destination occupies the upper 32 bits of the
+        // ICR.
+        write_msr(APIC_MSR_ICR, (u64::from(apic_id) << 32) | icr);
+    }
+
+    fn send_init(&self, apic_id: u32) {
+        let init = APIC_ICR_INIT | APIC_ICR_TRIGGER_LEVEL;
+        self.send_ipi(apic_id, init | APIC_ICR_LEVEL_ASSERT);
+        self.send_ipi(apic_id, init);
+    }
+
+    fn delay_us(&self, us: u64) {
+        let khz = self.tsc_khz().map_or(FALLBACK_TSC_KHZ, u64::from);
+        let end = rdtsc() + us * khz / 1000;
+        while rdtsc() < end {
+            core::hint::spin_loop();
+        }
+    }
 }
 
 impl Default for NativePlatform {
@@ -56,6 +349,10 @@ impl SvsmPlatform for NativePlatform {
         }
     }
 
+    fn encryption_bit_position(&self) -> Option<u32> {
+        None
+    }
+
     fn setup_guest_host_comm(&mut self, _cpu: &PerCpu, _is_bsp: bool) {}
 
     fn get_console_io_port(&self) -> &'static dyn IOPort {
@@ -72,12 +369,20 @@ impl SvsmPlatform for NativePlatform {
     }
 
     /// Marks a range of pages as valid for use as private pages.
-    fn validate_page_range(&self, _region: MemoryRegion<VirtAddr>) -> Result<(), SvsmError> {
+    fn validate_page_range(
+        &self,
+        _region: MemoryRegion<VirtAddr>,
+        _paddr: PhysAddr,
+    ) -> Result<(), SvsmError> {
         Ok(())
     }
 
     /// Marks a range of pages as invalid for use as private pages.
-    fn invalidate_page_range(&self, _region: MemoryRegion<VirtAddr>) -> Result<(), SvsmError> {
+    fn invalidate_page_range(
+        &self,
+        _region: MemoryRegion<VirtAddr>,
+        _paddr: PhysAddr,
+    ) -> Result<(), SvsmError> {
         Ok(())
     }
 
@@ -103,6 +408,73 @@ impl SvsmPlatform for NativePlatform {
     }
 
     fn eoi(&self) {
-        todo!();
+        write_msr(APIC_MSR_EOI, 0);
+    }
+
+    fn tsc_khz(&self) -> Option<u32> {
+        let max_leaf = CpuidResult::get(0, 0).eax;
+        let leaf = |eax| (max_leaf >= eax).then(|| CpuidResult::get(eax, 0));
+        tsc_khz_from_cpuid(leaf(0x15), leaf(0x16))
+    }
+
+    fn get_random(&self, buf: &mut [u8]) -> Result<(), SvsmError> {
+        if (CpuidResult::get(1, 0).ecx & (1 << X86_FEATURE_RDRAND)) == 0 {
+            return Err(SvsmError::NotSupported);
+        }
+        fill_random(buf, rdrand64)
+    }
+
+    fn start_cpu(&self, cpu: &PerCpu, start_rip: u64, _vtom: u64) -> Result<(), SvsmError> {
+        // The trampoline loads CR3 while still in 32-bit mode, so the AP's
+        // page table must live below 4 GB.
+        let cr3 = u32::try_from(u64::from(cpu.get_pgtable().cr3_value()))
+            .map_err(|_| SvsmError::InvalidAddress)?;
+        let (gdt_base, gdt_limit) = gdt().base_limit();
+        let (idt_base, idt_limit) = idt().base_limit();
+        let params = ApTrampolineParams {
+            cr0: read_cr0().bits() as u32,
+            cr3,
+            cr4: read_cr4().bits(),
+            efer: (read_efer() - EFERFlags::LMA).bits(),
+            gdt_limit: gdt_limit as u16,
+            gdt_base,
+            idt_limit: idt_limit as u16,
+            idt_base,
+            rsp: cpu.get_top_of_stack().into(),
+            rip: start_rip,
+        };
+        let trampoline = ApTrampoline::install(cpu, &params)?;
+
+        // Issue the INIT-SIPI-SIPI sequence to the target APIC ID.
+        let apic_id = cpu.get_apic_id();
+        self.send_init(apic_id);
+        self.delay_us(AP_INIT_DELAY_US);
+        for _ in 0..2 {
+            self.send_ipi(apic_id, APIC_ICR_SIPI | trampoline.sipi_vector());
+            self.delay_us(AP_SIPI_DELAY_US);
+        }
+
+        // The trampoline must stay in place until the AP has left it.  If
+        // the AP never comes online, put it back into the wait-for-SIPI
+        // state so that it cannot run the trampoline after the page has been
+        // restored.
+        let result = wait_for_cpu_online(cpu);
+        if result.is_err() {
+            self.send_init(apic_id);
+        }
+        result
+    }
+
+    fn inject_guest_exception(
+        &self,
+        _cpu: &PerCpu,
+        _vector: u8,
+        _error_code: Option<u32>,
+    ) -> Result<(), SvsmError> {
+        Err(SvsmError::NotSupported)
+    }
+
+    fn attest(&self, _report_data: &[u8; 64]) -> Result<AttestationEvidence, SvsmError> {
+        Err(SvsmError::NotSupported)
     }
 }
@@ -5,14 +5,21 @@
 // Author: Jon Lange <jlange@microsoft.com>
 
 use crate::address::{PhysAddr, VirtAddr};
-use crate::cpu::cpuid::cpuid_table;
+use crate::cpu::cpuid::{c_bit_position, cpuid_table, tsc_khz_from_cpuid};
 use crate::cpu::percpu::{current_ghcb, PerCpu};
+use crate::cpu::rng::{fill_random, rdrand64, X86_FEATURE_RDRAND};
+use crate::cpu::smp::wait_for_cpu_online;
 use crate::error::SvsmError;
+use crate::greq::services::get_attestation_report;
 use crate::io::IOPort;
-use crate::platform::{PageEncryptionMasks, PageStateChangeOp, SvsmPlatform};
+use crate::platform::{
+    coalesce_regions, AttestationEvidence, PageEncryptionMasks, PageStateChangeOp, SvsmPlatform,
+};
+use crate::protocols::errors::SvsmReqError;
 use crate::sev::hv_doorbell::current_hv_doorbell;
 use crate::sev::msr_protocol::{hypervisor_ghcb_features, verify_ghcb_version, GHCBHvFeatures};
 use crate::sev::status::vtom_enabled;
+use crate::sev::vmsa::VMSAControl;
 use crate::sev::{
     init_hypervisor_ghcb_features, pvalidate_range, sev_status_init, sev_status_verify, PvalidateOp,
 };
@@ -29,6 +36,12 @@ const APIC_EMULATION_ENABLED: u8 = 1;
 const APIC_EMULATION_LOCKED: u8 = 2;
 static APIC_EMULATION_STATE: AtomicU8 = AtomicU8::new(APIC_EMULATION_ENABLED);
 
+/// Number of times a transiently failed AP creation request is retried.
+const AP_CREATE_RETRIES: u32 = 3;
+/// Polling iterations to wait before the first AP creation retry.  The
+/// delay doubles with each retry.
+const AP_CREATE_INITIAL_BACKOFF: u32 = 1000;
+
 #[derive(Clone, Copy, Debug)]
 pub struct SnpPlatform {
     use_alternate_injection: bool,
@@ -55,6 +68,10 @@ impl SvsmPlatform for SnpPlatform {
 
     fn env_setup_late(&mut self) {
         sev_status_verify();
+        // The negotiated version does not survive the hand-off from stage 2,
+        // so negotiate it again before any version-gated GHCB feature is
+        // used.
+        verify_ghcb_version();
         init_hypervisor_ghcb_features().expect("Failed to obtain hypervisor GHCB features");
     }
 
@@ -72,25 +89,31 @@ impl SvsmPlatform for SnpPlatform {
         // Find physical address size.
         let processor_capacity =
             cpuid_table(0x80000008).expect("Can not get physical address size from CPUID table");
-        if vtom_enabled() {
-            PageEncryptionMasks {
-                private_pte_mask: 0,
-                shared_pte_mask: vtom,
-                addr_mask_width: vtom.leading_zeros(),
-                phys_addr_sizes: processor_capacity.eax,
-            }
-        } else {
-            // Find C-bit position.
-            let sev_capabilities =
-                cpuid_table(0x8000001f).expect("Can not get C-Bit position from CPUID table");
-            let c_bit = sev_capabilities.ebx & 0x3f;
-            PageEncryptionMasks {
+        match self.encryption_bit_position() {
+            Some(c_bit) => PageEncryptionMasks {
                 private_pte_mask: 1 << c_bit,
                 shared_pte_mask: 0,
                 addr_mask_width: c_bit,
                 phys_addr_sizes: processor_capacity.eax,
-            }
+            },
+            None => PageEncryptionMasks {
+                private_pte_mask: 0,
+                shared_pte_mask: vtom,
+                addr_mask_width: PageEncryptionMasks::vtom_addr_mask_width(vtom),
+                phys_addr_sizes: processor_capacity.eax,
+            },
+        }
+    }
+
+    fn encryption_bit_position(&self) -> Option<u32> {
+        if vtom_enabled() {
+            return None;
         }
+
+        // Find C-bit position.
+        let sev_capabilities =
+            cpuid_table(0x8000001f).expect("Can not get C-Bit position from CPUID table");
+        Some(u32::from(c_bit_position(&sev_capabilities)))
     }
 
     fn setup_guest_host_comm(&mut self, cpu: &PerCpu, is_bsp: bool) {
@@ -121,14 +144,31 @@ impl SvsmPlatform for SnpPlatform {
         current_ghcb().page_state_change(region, size, op)
     }
 
+    fn page_state_change_batch(
+        &self,
+        regions: &[MemoryRegion<PhysAddr>],
+        size: PageSize,
+        op: PageStateChangeOp,
+    ) -> Result<(), SvsmError> {
+        current_ghcb().page_state_change_batch(&coalesce_regions(regions), size, op)
+    }
+
     /// Marks a range of pages as valid for use as private pages.
-    fn validate_page_range(&self, region: MemoryRegion<VirtAddr>) -> Result<(), SvsmError> {
-        pvalidate_range(region, PvalidateOp::Valid)
+    fn validate_page_range(
+        &self,
+        region: MemoryRegion<VirtAddr>,
+        paddr: PhysAddr,
+    ) -> Result<(), SvsmError> {
+        pvalidate_range(region, paddr, PvalidateOp::Valid)
     }
 
     /// Marks a range of pages as invalid for use as private pages.
-    fn invalidate_page_range(&self, region: MemoryRegion<VirtAddr>) -> Result<(), SvsmError> {
-        pvalidate_range(region, PvalidateOp::Invalid)
+    fn invalidate_page_range(
+        &self,
+        region: MemoryRegion<VirtAddr>,
+        paddr: PhysAddr,
+    ) -> Result<(), SvsmError> {
+        pvalidate_range(region, paddr, PvalidateOp::Invalid)
     }
 
     fn configure_alternate_injection(&mut self, alt_inj_requested: bool) -> Result<(), SvsmError> {
@@ -210,4 +250,91 @@ impl SvsmPlatform for SnpPlatform {
             let _ = current_ghcb().wrmsr(0x80B, 0);
         }
     }
+
+    fn tsc_khz(&self) -> Option<u32> {
+        tsc_khz_from_cpuid(cpuid_table(0x15), cpuid_table(0x16))
+    }
+
+    fn get_random(&self, buf: &mut [u8]) -> Result<(), SvsmError> {
+        // RDRAND is executed directly by the processor and cannot be
+        // emulated by the hypervisor without raising #VC, which the SVSM
+        // never satisfies for RDRAND, so its output can be trusted here.
+        let features = cpuid_table(1).ok_or(SvsmError::NotSupported)?;
+        if (features.ecx & (1 << X86_FEATURE_RDRAND)) == 0 {
+            return Err(SvsmError::NotSupported);
+        }
+        fill_random(buf, rdrand64)
+    }
+
+    fn start_cpu(&self, cpu: &PerCpu, start_rip: u64, vtom: u64) -> Result<(), SvsmError> {
+        let (vmsa_pa, sev_features) = cpu.alloc_svsm_vmsa(vtom, start_rip)?;
+
+        // Retry with an increasing delay if the host reports that it is
+        // busy.  All other errors, such as an invalid VMSA, are returned
+        // immediately.
+        let mut backoff = AP_CREATE_INITIAL_BACKOFF;
+        let mut attempt = 0;
+        let result = loop {
+            match current_ghcb().ap_create(vmsa_pa, cpu.get_apic_id().into(), 0, sev_features) {
+                Err(SvsmError::Ghcb(err)) if err.is_transient() && attempt < AP_CREATE_RETRIES => {
+                    attempt += 1;
+                    log::warn!(
+                        "AP_CREATE for APIC-ID {} failed ({:?}), retry {}/{}",
+                        cpu.get_apic_id(),
+                        err,
+                        attempt,
+                        AP_CREATE_RETRIES
+                    );
+                    for _ in 0..backoff {
+                        core::hint::spin_loop();
+                    }
+                    backoff *= 2;
+                }
+                result => break result,
+            }
+        };
+        result?;
+
+        let result = wait_for_cpu_online(cpu);
+        if result.is_err() {
+            // The caller unregisters the per-CPU area of an AP that failed
+            // to come online, so the host must not be allowed to run the
+            // VMSA that still refers to it.
+            let apic_id = cpu.get_apic_id();
+            if let Err(e) = current_ghcb().ap_destroy(vmsa_pa, apic_id.into(), 0) {
+                panic!("AP_DESTROY for APIC-ID {} failed: {:?}", apic_id, e);
+            }
+        }
+        result
+    }
+
+    fn inject_guest_exception(
+        &self,
+        cpu: &PerCpu,
+        vector: u8,
+        error_code: Option<u32>,
+    ) -> Result<(), SvsmError> {
+        // Return any interrupt already presented to the guest to the APIC
+        // so the event injection field can be used for the exception.  The
+        // interrupt will be presented again on the next guest entry.
+        cpu.clear_pending_interrupts();
+
+        let mut vmsa_ref = cpu.guest_vmsa_ref();
+        if vmsa_ref.vmsa_phys().is_none() {
+            return Err(SvsmError::MissingVMSA);
+        }
+        if vmsa_ref.vmsa().inject_exception(vector, error_code) {
+            Ok(())
+        } else {
+            Err(SvsmError::EventPending)
+        }
+    }
+
+    fn attest(&self, report_data: &[u8; 64]) -> Result<AttestationEvidence, SvsmError> {
+        match get_attestation_report(report_data) {
+            Ok(report) => Ok(AttestationEvidence::Snp(report)),
+            Err(SvsmReqError::FatalError(err)) => Err(err),
+            Err(SvsmReqError::RequestError(_)) => Err(SvsmError::Attestation),
+        }
+    }
 }
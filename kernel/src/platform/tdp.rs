@@ -5,11 +5,12 @@
 // Author: Peter Fang <peter.fang@intel.com>
 
 use crate::address::{PhysAddr, VirtAddr};
-use crate::cpu::cpuid::CpuidResult;
+use crate::cpu::cpuid::{tsc_khz_from_cpuid, CpuidResult};
 use crate::cpu::percpu::PerCpu;
+use crate::cpu::rng::{fill_random, rdrand64, X86_FEATURE_RDRAND};
 use crate::error::SvsmError;
 use crate::io::IOPort;
-use crate::platform::{PageEncryptionMasks, PageStateChangeOp, SvsmPlatform};
+use crate::platform::{AttestationEvidence, PageEncryptionMasks, PageStateChangeOp, SvsmPlatform};
 use crate::svsm_console::SVSMIOPort;
 use crate::types::PageSize;
 use crate::utils::MemoryRegion;
@@ -50,11 +51,15 @@ impl SvsmPlatform for TdpPlatform {
         PageEncryptionMasks {
             private_pte_mask: 0,
             shared_pte_mask: vtom,
-            addr_mask_width: vtom.trailing_zeros(),
+            addr_mask_width: PageEncryptionMasks::vtom_addr_mask_width(vtom),
             phys_addr_sizes: res.eax & 0xff,
         }
     }
 
+    fn encryption_bit_position(&self) -> Option<u32> {
+        None
+    }
+
     fn setup_guest_host_comm(&mut self, _cpu: &PerCpu, _is_bsp: bool) {}
 
     fn get_console_io_port(&self) -> &'static dyn IOPort {
@@ -70,11 +75,19 @@ impl SvsmPlatform for TdpPlatform {
         Err(SvsmError::Tdx)
     }
 
-    fn validate_page_range(&self, _region: MemoryRegion<VirtAddr>) -> Result<(), SvsmError> {
+    fn validate_page_range(
+        &self,
+        _region: MemoryRegion<VirtAddr>,
+        _paddr: PhysAddr,
+    ) -> Result<(), SvsmError> {
         Err(SvsmError::Tdx)
     }
 
-    fn invalidate_page_range(&self, _region: MemoryRegion<VirtAddr>) -> Result<(), SvsmError> {
+    fn invalidate_page_range(
+        &self,
+        _region: MemoryRegion<VirtAddr>,
+        _paddr: PhysAddr,
+    ) -> Result<(), SvsmError> {
         Err(SvsmError::Tdx)
     }
 
@@ -99,4 +112,39 @@ impl SvsmPlatform for TdpPlatform {
     }
 
     fn eoi(&self) {}
+
+    fn tsc_khz(&self) -> Option<u32> {
+        // The TDX module virtualizes the TSC frequency leaves.
+        let max_leaf = CpuidResult::get(0, 0).eax;
+        let leaf = |eax| (max_leaf >= eax).then(|| CpuidResult::get(eax, 0));
+        tsc_khz_from_cpuid(leaf(0x15), leaf(0x16))
+    }
+
+    fn get_random(&self, buf: &mut [u8]) -> Result<(), SvsmError> {
+        if (CpuidResult::get(1, 0).ecx & (1 << X86_FEATURE_RDRAND)) == 0 {
+            return Err(SvsmError::NotSupported);
+        }
+        fill_random(buf, rdrand64)
+    }
+
+    fn start_cpu(&self, _cpu: &PerCpu, _start_rip: u64, _vtom: u64) -> Result<(), SvsmError> {
+        Err(SvsmError::Tdx)
+    }
+
+    fn inject_guest_exception(
+        &self,
+        _cpu: &PerCpu,
+        _vector: u8,
+        _error_code: Option<u32>,
+    ) -> Result<(), SvsmError> {
+        // Guest state is owned by the TDX module, and no TDCALL for event
+        // injection into the L2 guest is implemented yet.
+        Err(SvsmError::Tdx)
+    }
+
+    fn attest(&self, _report_data: &[u8; 64]) -> Result<AttestationEvidence, SvsmError> {
+        // Quote generation through TDG.VP.VMCALL<GetQuote> is not
+        // implemented yet.
+        Err(SvsmError::Tdx)
+    }
 }
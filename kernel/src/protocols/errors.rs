@@ -73,10 +73,22 @@ impl From<SvsmError> for SvsmReqError {
             SvsmError::Mem => Self::FatalError(err),
             // SEV-SNP errors obtained from PVALIDATE or RMPADJUST are returned
             // to the guest as protocol-specific errors.
-            SvsmError::SevSnp(e) => Self::protocol(e.ret()),
+            SvsmError::SevSnp(e) | SvsmError::Validation { error: e, .. } => {
+                Self::protocol(e.ret())
+            }
             SvsmError::InvalidAddress => Self::invalid_address(),
+            SvsmError::Request(code) => Self::RequestError(code),
             // Use a fatal error for now
             _ => Self::FatalError(err),
         }
     }
 }
+
+impl From<SvsmReqError> for SvsmError {
+    fn from(err: SvsmReqError) -> Self {
+        match err {
+            SvsmReqError::RequestError(code) => Self::Request(code),
+            SvsmReqError::FatalError(e) => e,
+        }
+    }
+}
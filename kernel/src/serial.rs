@@ -5,9 +5,22 @@
 // Author: Joerg Roedel <jroedel@suse.de>
 
 use super::io::{IOPort, DEFAULT_IO_DRIVER};
+use crate::address::{Address, VirtAddr};
+use crate::error::SvsmError;
 
-pub const SERIAL_PORT: u16 = 0x3f8;
+/// I/O port base of the first legacy serial port.
+pub const COM1_PORT: u16 = 0x3f8;
+/// I/O port base of the second legacy serial port.
+pub const COM2_PORT: u16 = 0x2f8;
+/// I/O port base of the third legacy serial port.
+pub const COM3_PORT: u16 = 0x3e8;
+/// I/O port base of the fourth legacy serial port.
+pub const COM4_PORT: u16 = 0x2e8;
+
+/// Serial port used when the boot configuration does not specify one.
+pub const SERIAL_PORT: u16 = COM1_PORT;
 const BAUD: u32 = 9600;
+const MAX_BAUD: u32 = 115200;
 const DLAB: u8 = 0x80;
 
 pub const TXR: u16 = 0; // Transmit register
@@ -25,11 +38,73 @@ pub const DLH: u16 = 1; // Divisor Latch High
 pub const RCVRDY: u8 = 0x01;
 pub const XMTRDY: u8 = 0x20;
 
+/// Computes the divisor latch value for the requested baud rate, or `None`
+/// if the baud rate cannot be generated exactly from the base clock.
+fn baud_divisor(baud: u32) -> Option<u16> {
+    if baud == 0 || baud > MAX_BAUD || MAX_BAUD % baud != 0 {
+        return None;
+    }
+    u16::try_from(MAX_BAUD / baud).ok()
+}
+
 pub trait Terminal: Sync {
     fn put_byte(&self, _ch: u8) {}
     fn get_byte(&self) -> u8 {
         0
     }
+    /// Returns the next received byte, or `None` if no input is pending.
+    /// Unlike [`Terminal::get_byte`], this never blocks.
+    fn try_get_byte(&self) -> Option<u8> {
+        None
+    }
+}
+
+/// Register-level access to a 16550-compatible UART.  The programming
+/// sequences shared by all transports are provided on top of it.
+trait UartRegisters {
+    fn read_reg(&self, reg: u16) -> u8;
+    fn write_reg(&self, reg: u16, val: u8);
+
+    fn program(&self, divisor: u16) {
+        self.write_reg(LCR, 0x3); // 8n1
+        self.write_reg(IER, 0x0); // No Interrupt
+        self.write_reg(FCR, 0x0); // No FIFO
+        self.write_reg(MCR, 0x3); // DTR + RTS
+
+        let c = self.read_reg(LCR);
+        self.write_reg(LCR, c | DLAB);
+        self.write_reg(DLL, (divisor & 0xff) as u8);
+        self.write_reg(DLH, ((divisor >> 8) & 0xff) as u8);
+        self.write_reg(LCR, c & !DLAB);
+    }
+
+    fn transmit(&self, ch: u8) {
+        loop {
+            let xmt = self.read_reg(LSR);
+            if (xmt & XMTRDY) == XMTRDY {
+                break;
+            }
+        }
+
+        self.write_reg(TXR, ch)
+    }
+
+    fn receive(&self) -> u8 {
+        loop {
+            if let Some(ch) = self.try_receive() {
+                return ch;
+            }
+        }
+    }
+
+    fn try_receive(&self) -> Option<u8> {
+        let rcv = self.read_reg(LSR);
+        if (rcv & RCVRDY) == RCVRDY {
+            Some(self.read_reg(0))
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -44,51 +119,175 @@ impl<'a> SerialPort<'a> {
     }
 
     pub fn init(&self) {
-        let divisor: u32 = 115200 / BAUD;
-
-        self.outb(LCR, 0x3); // 8n1
-        self.outb(IER, 0x0); // No Interrupt
-        self.outb(FCR, 0x0); // No FIFO
-        self.outb(MCR, 0x3); // DTR + RTS
+        // The default baud rate always has an exact divisor.
+        self.program(baud_divisor(BAUD).unwrap());
+    }
 
-        let c = self.inb(LCR);
-        self.outb(LCR, c | DLAB);
-        self.outb(DLL, (divisor & 0xff) as u8);
-        self.outb(DLH, ((divisor >> 8) & 0xff) as u8);
-        self.outb(LCR, c & !DLAB);
+    /// Initializes the serial port to run at the specified baud rate.  The
+    /// baud rate must evenly divide the 115200 baud base clock rate, or an
+    /// error is returned and the port is left untouched.
+    pub fn init_with_baud(&self, baud: u32) -> Result<(), SvsmError> {
+        let divisor = baud_divisor(baud).ok_or(SvsmError::NotSupported)?;
+        self.program(divisor);
+        Ok(())
     }
+}
 
+impl UartRegisters for SerialPort<'_> {
     #[inline]
-    fn inb(&self, port: u16) -> u8 {
-        self.driver.inb(self.port + port)
+    fn read_reg(&self, reg: u16) -> u8 {
+        self.driver.inb(self.port + reg)
     }
 
     #[inline]
-    fn outb(&self, port: u16, val: u8) {
-        self.driver.outb(self.port + port, val);
+    fn write_reg(&self, reg: u16, val: u8) {
+        self.driver.outb(self.port + reg, val);
     }
 }
 
 impl Terminal for SerialPort<'_> {
     fn put_byte(&self, ch: u8) {
-        loop {
-            let xmt = self.inb(LSR);
-            if (xmt & XMTRDY) == XMTRDY {
-                break;
-            }
+        self.transmit(ch)
+    }
+
+    fn get_byte(&self) -> u8 {
+        self.receive()
+    }
+
+    fn try_get_byte(&self) -> Option<u8> {
+        self.try_receive()
+    }
+}
+
+/// A 16550-compatible UART whose registers are memory-mapped rather than
+/// accessed through port I/O.  Each register occupies `stride` bytes and is
+/// accessed with a load or store of that width.
+#[derive(Debug, Copy, Clone)]
+pub struct MmioSerial {
+    base: VirtAddr,
+    stride: usize,
+}
+
+impl MmioSerial {
+    /// Creates a new MMIO serial driver for the UART mapped at `base`, with
+    /// registers spaced `stride` bytes apart.  Only strides of 1, 2 and 4
+    /// bytes are supported, and `base` must be aligned to the stride.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that `base` maps the UART register window for
+    /// the lifetime of the returned object, and that the mapping is suitable
+    /// for device access.
+    pub unsafe fn new(base: VirtAddr, stride: usize) -> Result<Self, SvsmError> {
+        if !matches!(stride, 1 | 2 | 4) {
+            return Err(SvsmError::NotSupported);
+        }
+        if !base.is_aligned(stride) {
+            return Err(SvsmError::InvalidAddress);
         }
+        Ok(Self { base, stride })
+    }
 
-        self.outb(TXR, ch)
+    pub fn init(&self) {
+        // The default baud rate always has an exact divisor.
+        self.program(baud_divisor(BAUD).unwrap());
     }
 
-    fn get_byte(&self) -> u8 {
-        loop {
-            let rcv = self.inb(LSR);
-            if (rcv & RCVRDY) == RCVRDY {
-                return self.inb(0);
+    /// Initializes the UART to run at the specified baud rate, with the same
+    /// restrictions as [`SerialPort::init_with_baud`].
+    pub fn init_with_baud(&self, baud: u32) -> Result<(), SvsmError> {
+        let divisor = baud_divisor(baud).ok_or(SvsmError::NotSupported)?;
+        self.program(divisor);
+        Ok(())
+    }
+
+    fn reg_addr(&self, reg: u16) -> VirtAddr {
+        self.base + usize::from(reg) * self.stride
+    }
+}
+
+impl UartRegisters for MmioSerial {
+    fn read_reg(&self, reg: u16) -> u8 {
+        let addr = self.reg_addr(reg);
+        // SAFETY: the constructor's contract guarantees that the register
+        // window is mapped, and the address is aligned to the access width.
+        unsafe {
+            match self.stride {
+                1 => addr.as_ptr::<u8>().read_volatile(),
+                2 => addr.as_ptr::<u16>().read_volatile() as u8,
+                _ => addr.as_ptr::<u32>().read_volatile() as u8,
             }
         }
     }
+
+    fn write_reg(&self, reg: u16, val: u8) {
+        let addr = self.reg_addr(reg);
+        // SAFETY: see read_reg().
+        unsafe {
+            match self.stride {
+                1 => addr.as_mut_ptr::<u8>().write_volatile(val),
+                2 => addr.as_mut_ptr::<u16>().write_volatile(val.into()),
+                _ => addr.as_mut_ptr::<u32>().write_volatile(val.into()),
+            }
+        }
+    }
+}
+
+impl Terminal for MmioSerial {
+    fn put_byte(&self, ch: u8) {
+        self.transmit(ch)
+    }
+
+    fn get_byte(&self) -> u8 {
+        self.receive()
+    }
+
+    fn try_get_byte(&self) -> Option<u8> {
+        self.try_receive()
+    }
 }
 
+/// Placeholder console writer used until [`init_console()`] installs a
+/// terminal for the configured debug serial port.  Output is suppressed
+/// until then, so nothing is ever written to this port.
+///
+/// [`init_console()`]: crate::console::init_console
 pub static DEFAULT_SERIAL_PORT: SerialPort<'_> = SerialPort::new(&DEFAULT_IO_DRIVER, SERIAL_PORT);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_baud_divisor() {
+        assert_eq!(baud_divisor(115200), Some(1));
+        assert_eq!(baud_divisor(38400), Some(3));
+        assert_eq!(baud_divisor(9600), Some(12));
+        assert_eq!(baud_divisor(0), None);
+        assert_eq!(baud_divisor(230400), None);
+        assert_eq!(baud_divisor(10000), None);
+    }
+
+    #[test]
+    fn test_mmio_serial_stride() {
+        let mut regs = [0u32; 8];
+        let base = VirtAddr::from(regs.as_mut_ptr());
+        // SAFETY: the register window is a local array that outlives the
+        // driver.
+        let uart = unsafe { MmioSerial::new(base, 4).unwrap() };
+        uart.write_reg(LSR, XMTRDY | RCVRDY);
+        uart.put_byte(b'A');
+        assert_eq!(regs[usize::from(TXR)], u32::from(b'A'));
+        assert_eq!(regs[usize::from(LSR)], u32::from(XMTRDY | RCVRDY));
+        assert_eq!(uart.get_byte(), b'A');
+        assert_eq!(uart.try_get_byte(), Some(b'A'));
+
+        // Nothing pending: the non-blocking read must not spin.
+        uart.write_reg(LSR, XMTRDY);
+        assert_eq!(uart.try_get_byte(), None);
+
+        // SAFETY: construction is expected to fail before any access.
+        assert!(unsafe { MmioSerial::new(base, 3) }.is_err());
+        assert!(unsafe { MmioSerial::new(base + 1, 4) }.is_err());
+    }
+}
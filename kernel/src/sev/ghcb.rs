@@ -81,6 +81,28 @@ pub enum GhcbError {
     VmgexitError(u64, u64),
 }
 
+// SW_EXITINFO1 response code indicating that the hypervisor is reporting an
+// exception.  SW_EXITINFO2 holds the exception event information.
+const GHCB_HV_RESP_EXCEPTION: u64 = 1;
+
+// Hypervisor error code, reported in the upper 32 bits of SW_EXITINFO2,
+// indicating that the hypervisor was busy and the request should be retried.
+const GHCB_HV_ERR_BUSY: u64 = 2;
+
+impl GhcbError {
+    /// Returns true if the hypervisor explicitly reported that the request
+    /// failed because it was busy, so that retrying it may succeed.  All
+    /// other failures are considered permanent.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::VmgexitError(info1, info2) => {
+                (info1 & 0xFFFF_FFFF) != GHCB_HV_RESP_EXCEPTION && (info2 >> 32) == GHCB_HV_ERR_BUSY
+            }
+            _ => false,
+        }
+    }
+}
+
 impl From<GhcbError> for SvsmError {
     fn from(e: GhcbError) -> Self {
         Self::Ghcb(e)
@@ -452,12 +474,22 @@ impl GHCB {
         region: MemoryRegion<PhysAddr>,
         size: PageSize,
         op: PageStateChangeOp,
+    ) -> Result<(), SvsmError> {
+        self.page_state_change_batch(&[region], size, op)
+    }
+
+    /// Performs a page state change for all of `regions`, packing the
+    /// entries of consecutive regions into the same request until the
+    /// shared buffer is full.
+    pub fn page_state_change_batch(
+        &self,
+        regions: &[MemoryRegion<PhysAddr>],
+        size: PageSize,
+        op: PageStateChangeOp,
     ) -> Result<(), SvsmError> {
         // Maximum entries (8 bytes each_ minus 8 bytes for header
         let max_entries: u16 = ((GHCB_BUFFER_SIZE - 8) / 8).try_into().unwrap();
         let mut entries: u16 = 0;
-        let mut paddr = region.start();
-        let end = region.end();
         let op_mask: u64 = match op {
             PageStateChangeOp::Private => PSC_OP_PRIVATE,
             PageStateChangeOp::Shared => PSC_OP_SHARED,
@@ -467,53 +499,66 @@ impl GHCB {
 
         self.clear();
 
-        while paddr < end {
-            let size = if size == PageSize::Huge
-                && paddr.is_aligned(PAGE_SIZE_2M)
-                && paddr + PAGE_SIZE_2M <= end
-            {
-                PageSize::Huge
-            } else {
-                PageSize::Regular
-            };
-            let pgsize = usize::from(size);
-            let entry = self.psc_entry(paddr, op_mask, 0, size);
-            let offset = usize::from(entries) * 8 + 8;
-            self.write_buffer(&entry, offset)?;
-            entries += 1;
-            paddr = paddr + pgsize;
-
-            if entries == max_entries || paddr >= end {
-                let header = PageStateChangeHeader {
-                    cur_entry: 0,
-                    end_entry: entries - 1,
-                    reserved: 0,
+        for region in regions {
+            let mut paddr = region.start();
+            let end = region.end();
+            while paddr < end {
+                let size = if size == PageSize::Huge
+                    && paddr.is_aligned(PAGE_SIZE_2M)
+                    && paddr + PAGE_SIZE_2M <= end
+                {
+                    PageSize::Huge
+                } else {
+                    PageSize::Regular
                 };
-                self.write_buffer(&header, 0)?;
-
-                let buffer_va = VirtAddr::from(self.buffer.as_ptr());
-                let buffer_pa = u64::from(virt_to_phys(buffer_va));
-                self.set_sw_scratch_valid(buffer_pa);
-
-                if let Err(mut e) = self.vmgexit(GHCBExitCode::SNP_PSC, 0, 0) {
-                    if let Err(err) = self.get_exit_info_2_valid() {
-                        e = err;
-                    }
-
-                    if let GhcbError::VmgexitError(_, info2) = e {
-                        let info_high: u32 = (info2 >> 32) as u32;
-                        let info_low: u32 = (info2 & 0xffff_ffffu64) as u32;
-                        log::error!(
-                            "GHCB SnpPageStateChange failed err_high: {:#x} err_low: {:#x}",
-                            info_high,
-                            info_low
-                        );
-                    }
-                    return Err(e.into());
+                let pgsize = usize::from(size);
+                let entry = self.psc_entry(paddr, op_mask, 0, size);
+                let offset = usize::from(entries) * 8 + 8;
+                self.write_buffer(&entry, offset)?;
+                entries += 1;
+                paddr = paddr + pgsize;
+
+                if entries == max_entries {
+                    self.submit_psc(entries)?;
+                    entries = 0;
                 }
+            }
+        }
+
+        if entries != 0 {
+            self.submit_psc(entries)?;
+        }
+
+        Ok(())
+    }
+
+    fn submit_psc(&self, entries: u16) -> Result<(), SvsmError> {
+        let header = PageStateChangeHeader {
+            cur_entry: 0,
+            end_entry: entries - 1,
+            reserved: 0,
+        };
+        self.write_buffer(&header, 0)?;
 
-                entries = 0;
+        let buffer_va = VirtAddr::from(self.buffer.as_ptr());
+        let buffer_pa = u64::from(virt_to_phys(buffer_va));
+        self.set_sw_scratch_valid(buffer_pa);
+
+        if let Err(mut e) = self.vmgexit(GHCBExitCode::SNP_PSC, 0, 0) {
+            if let Err(err) = self.get_exit_info_2_valid() {
+                e = err;
+            }
+
+            if let GhcbError::VmgexitError(_, info2) = e {
+                let info_high: u32 = (info2 >> 32) as u32;
+                let info_low: u32 = (info2 & 0xffff_ffffu64) as u32;
+                log::error!(
+                    "GHCB SnpPageStateChange failed err_high: {:#x} err_low: {:#x}",
+                    info_high,
+                    info_low
+                );
             }
+            return Err(e.into());
         }
 
         Ok(())
@@ -534,6 +579,14 @@ impl GHCB {
         Ok(())
     }
 
+    pub fn ap_destroy(&self, vmsa_gpa: PhysAddr, apic_id: u64, vmpl: u64) -> Result<(), SvsmError> {
+        self.clear();
+        let exit_info_1: u64 = 2 | (vmpl & 0xf) << 16 | apic_id << 32;
+        let exit_info_2: u64 = vmsa_gpa.into();
+        self.vmgexit(GHCBExitCode::AP_CREATE, exit_info_1, exit_info_2)?;
+        Ok(())
+    }
+
     pub fn register_guest_vmsa(
         &self,
         vmsa_gpa: PhysAddr,
@@ -785,4 +838,17 @@ mod tests {
         assert_eq!(offset_of!(GHCB, usage), 0xffc);
         assert_eq!(mem::size_of::<GHCB>(), 0x1000);
     }
+
+    #[test]
+    fn test_ghcb_error_is_transient() {
+        let busy = GHCB_HV_ERR_BUSY << 32;
+        assert!(GhcbError::VmgexitError(0, busy).is_transient());
+        assert!(!GhcbError::VmgexitError(0, 1 << 32).is_transient());
+        assert!(!GhcbError::VmgexitError(GHCB_HV_RESP_EXCEPTION, 0).is_transient());
+        assert!(!GhcbError::VmgexitError(GHCB_HV_RESP_EXCEPTION, busy).is_transient());
+        // Malformed input, with the reason code in SW_EXITINFO2.
+        assert!(!GhcbError::VmgexitError(2, 2).is_transient());
+        assert!(!GhcbError::VmgexitInvalid.is_transient());
+        assert!(!GhcbError::InvalidOffset.is_transient());
+    }
 }
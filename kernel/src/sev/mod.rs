@@ -14,7 +14,7 @@ pub mod vmsa;
 pub mod utils;
 
 pub use msr_protocol::init_hypervisor_ghcb_features;
-pub use secrets_page::{secrets_page, secrets_page_mut, SecretsPage, VMPCK_SIZE};
+pub use secrets_page::{secrets_page, secrets_page_mut, SecretsPage, SequenceCounters, VMPCK_SIZE};
 pub use status::sev_status_init;
 pub use status::sev_status_verify;
 pub use status::{sev_es_enabled, sev_snp_enabled};
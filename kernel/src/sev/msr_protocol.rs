@@ -69,7 +69,42 @@ impl Display for GHCBHvFeatures {
 
 static GHCB_HV_FEATURES: ImmutAfterInitCell<GHCBHvFeatures> = ImmutAfterInitCell::uninit();
 
-/// Check that we support the hypervisor's advertised GHCB versions.
+/// The lowest GHCB protocol version supported by the SVSM.
+const GHCB_PROTOCOL_MIN: u16 = 2;
+/// The highest GHCB protocol version supported by the SVSM.
+const GHCB_PROTOCOL_MAX: u16 = 2;
+
+/// The GHCB protocol version agreed with the hypervisor, or zero before
+/// negotiation.
+static GHCB_PROTOCOL_VERSION: ImmutAfterInitCell<u16> = ImmutAfterInitCell::new(0);
+
+/// GHCB operations which are only available from a minimum GHCB protocol
+/// version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GhcbFeature {
+    ApCreation,
+    GuestRequest,
+    ExtGuestRequest,
+    HvDoorbell,
+}
+
+impl GhcbFeature {
+    const fn min_version(self) -> u16 {
+        match self {
+            Self::ApCreation | Self::GuestRequest | Self::ExtGuestRequest | Self::HvDoorbell => 2,
+        }
+    }
+}
+
+/// Selects the highest protocol version supported by both the SVSM and the
+/// hypervisor.
+fn negotiate_ghcb_version(hv_min: u16, hv_max: u16) -> Option<u16> {
+    let version = hv_max.min(GHCB_PROTOCOL_MAX);
+    (version >= hv_min.max(GHCB_PROTOCOL_MIN)).then_some(version)
+}
+
+/// Check that we support the hypervisor's advertised GHCB versions and
+/// record the negotiated version.
 pub fn verify_ghcb_version() {
     // Request SEV information.
     write_msr(SEV_GHCB, GHCBMsr::SEV_INFO_REQ);
@@ -87,12 +122,29 @@ pub fn verify_ghcb_version() {
 
     // Compare announced supported GHCB MSR protocol version range
     // for compatibility.
-    let min_version = (sev_info >> 32) & 0xffff;
-    let max_version = (sev_info >> 48) & 0xffff;
-    assert!(
-        (min_version..=max_version).contains(&2),
-        "the hypervisor doesn't support GHCB version 2 (min: {min_version}, max: {max_version})"
-    );
+    let min_version = ((sev_info >> 32) & 0xffff) as u16;
+    let max_version = ((sev_info >> 48) & 0xffff) as u16;
+    let version = negotiate_ghcb_version(min_version, max_version).unwrap_or_else(|| {
+        panic!(
+            "the hypervisor doesn't support GHCB version 2 (min: {min_version}, max: {max_version})"
+        )
+    });
+    GHCB_PROTOCOL_VERSION
+        .reinit(&version)
+        .expect("Failed to record GHCB protocol version");
+}
+
+/// Returns the negotiated GHCB protocol version, or zero if the version has
+/// not been negotiated yet.
+pub fn ghcb_protocol_version() -> u16 {
+    *GHCB_PROTOCOL_VERSION
+}
+
+/// Returns true if the negotiated GHCB protocol version permits `feature`.
+/// Operations that also require a hypervisor feature must additionally
+/// check [`hypervisor_ghcb_features()`].
+pub fn ghcb_supports(feature: GhcbFeature) -> bool {
+    ghcb_protocol_version() >= feature.min_version()
 }
 
 pub fn hypervisor_ghcb_features() -> GHCBHvFeatures {
@@ -191,3 +243,25 @@ pub fn request_termination_msr() -> ! {
         halt();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_ghcb_version() {
+        assert_eq!(negotiate_ghcb_version(1, 2), Some(2));
+        assert_eq!(negotiate_ghcb_version(2, 5), Some(2));
+        assert_eq!(negotiate_ghcb_version(1, 1), None);
+        assert_eq!(negotiate_ghcb_version(3, 5), None);
+    }
+
+    #[test]
+    #[cfg_attr(not(test_in_svsm), ignore = "Can only be run inside guest")]
+    fn test_ghcb_version_recorded() {
+        // Platform setup must have negotiated a version, or every
+        // version-gated feature, like the #HV doorbell, is silently off.
+        assert!(ghcb_protocol_version() >= GHCB_PROTOCOL_MIN);
+        assert!(ghcb_supports(GhcbFeature::HvDoorbell));
+    }
+}
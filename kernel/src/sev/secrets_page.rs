@@ -133,3 +133,107 @@ pub fn secrets_page() -> ReadLockGuard<'static, SecretsPage> {
 pub fn secrets_page_mut() -> WriteLockGuard<'static, SecretsPage> {
     SECRETS_PAGE.lock_write()
 }
+
+/// Message sequence numbers of the `SNP_GUEST_REQUEST` messages protected
+/// with each VMPCK.  Each request consumes two sequence numbers, one for the
+/// request and one for the response.  A request first reserves its number,
+/// and the reservation is only committed once the host has accepted the
+/// request, so the counter stays in sync with the PSP.  The counters are
+/// owned by the guest request driver, whose lock serializes every request
+/// from reservation to commit.
+// A silent copy would let a sequence number be used twice.
+#[allow(missing_copy_implementations)]
+#[derive(Debug)]
+pub struct SequenceCounters {
+    last_used: [u64; VMPL_MAX],
+    reserved: [Option<u64>; VMPL_MAX],
+}
+
+impl SequenceCounters {
+    pub const fn new() -> Self {
+        Self {
+            last_used: [0; VMPL_MAX],
+            reserved: [None; VMPL_MAX],
+        }
+    }
+
+    /// Returns the last sequence number used with the VMPCK `idx`.
+    pub fn last_used(&self, idx: usize) -> u64 {
+        self.last_used[idx]
+    }
+
+    /// Reserves the sequence number for the next request protected with
+    /// the VMPCK `idx`.  Returns `None` if a reservation is already
+    /// outstanding, or if the sequence number space cannot hold both the
+    /// request and its response.
+    pub fn reserve(&mut self, idx: usize) -> Option<u64> {
+        if self.reserved[idx].is_some() {
+            return None;
+        }
+        let seqno = self.last_used[idx].checked_add(1)?;
+        seqno.checked_add(1)?;
+        self.reserved[idx] = Some(seqno);
+        Some(seqno)
+    }
+
+    /// Consumes the reserved sequence number and the one of the response
+    /// once the host has accepted the request.  Returns the sequence number
+    /// of the response, or `None` if there is no reservation or the
+    /// sequence number space is exhausted.
+    pub fn commit(&mut self, idx: usize) -> Option<u64> {
+        let seqno = self.reserved[idx].take()?;
+        let response = seqno.checked_add(1)?;
+        self.last_used[idx] = response;
+        Some(response)
+    }
+
+    /// Drops the reservation of a request that the host did not accept,
+    /// so that its sequence number is used by the next request.
+    pub fn release(&mut self, idx: usize) {
+        self.reserved[idx] = None;
+    }
+}
+
+impl Default for SequenceCounters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_counters() {
+        let mut counters = SequenceCounters::new();
+        assert_eq!(counters.reserve(0), Some(1));
+
+        // A reserved number cannot be handed out twice.
+        assert_eq!(counters.reserve(0), None);
+        assert_eq!(counters.commit(0), Some(2));
+        assert_eq!(counters.last_used(0), 2);
+        assert_eq!(counters.reserve(0), Some(3));
+
+        // A released reservation does not consume its number.
+        counters.release(0);
+        assert_eq!(counters.commit(0), None);
+        assert_eq!(counters.reserve(0), Some(3));
+
+        // Each VMPCK has an independent counter.
+        assert_eq!(counters.reserve(1), Some(1));
+    }
+
+    #[test]
+    fn test_sequence_counters_overflow() {
+        let mut counters = SequenceCounters::new();
+
+        // The response number of the last request must fit as well.
+        counters.last_used[2] = u64::MAX - 1;
+        assert_eq!(counters.reserve(2), None);
+        counters.last_used[2] = u64::MAX - 2;
+        assert_eq!(counters.reserve(2), Some(u64::MAX - 1));
+        assert_eq!(counters.commit(2), Some(u64::MAX));
+        assert_eq!(counters.reserve(2), None);
+    }
+}
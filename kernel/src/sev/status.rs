@@ -29,118 +29,51 @@ bitflags! {
     }
 }
 
+/// Display names of the SEV status flags, in bit order.
+const SEV_STATUS_NAMES: [(SEVStatusFlags, &str); 14] = [
+    (SEVStatusFlags::SEV, "SEV"),
+    (SEVStatusFlags::SEV_ES, "SEV-ES"),
+    (SEVStatusFlags::SEV_SNP, "SEV-SNP"),
+    (SEVStatusFlags::VTOM, "VTOM"),
+    (SEVStatusFlags::REFLECT_VC, "REFLECT_VC"),
+    (SEVStatusFlags::REST_INJ, "RESTRICTED_INJECTION"),
+    (SEVStatusFlags::ALT_INJ, "ALTERNATE_INJECTION"),
+    (SEVStatusFlags::DBGSWP, "DEBUG_SWAP"),
+    (SEVStatusFlags::PREV_HOST_IBS, "PREVENT_HOST_IBS"),
+    (SEVStatusFlags::BTB_ISOLATION, "SNP_BTB_ISOLATION"),
+    (SEVStatusFlags::VMPL_SSS, "VMPL_SSS"),
+    (SEVStatusFlags::SECURE_TSC, "SECURE_TSC"),
+    (SEVStatusFlags::VMSA_REG_PROT, "VMSA_REG_PROT"),
+    (SEVStatusFlags::SMT_PROT, "SMT_PROTECTION"),
+];
+
 impl fmt::Display for SEVStatusFlags {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut first = true;
 
-        if self.contains(SEVStatusFlags::SEV) {
-            f.write_str("SEV")?;
-            first = false;
-        }
-
-        if self.contains(SEVStatusFlags::SEV_ES) {
-            if !first {
-                f.write_char(' ')?;
-            }
-            f.write_str("SEV-ES")?;
-            first = false;
-        }
-
-        if self.contains(SEVStatusFlags::SEV_SNP) {
-            if !first {
-                f.write_char(' ')?;
-            }
-            f.write_str("SEV-SNP")?;
-            first = false;
-        }
-
-        if self.contains(SEVStatusFlags::VTOM) {
-            if !first {
-                f.write_char(' ')?;
-            }
-            f.write_str("VTOM")?;
-            first = false;
-        }
-
-        if self.contains(SEVStatusFlags::REFLECT_VC) {
-            if !first {
-                f.write_char(' ')?;
-            }
-            f.write_str("REFLECT_VC")?;
-            first = false;
-        }
-
-        if self.contains(SEVStatusFlags::REST_INJ) {
-            if !first {
-                f.write_char(' ')?;
-            }
-            f.write_str("RESTRICTED_INJECTION")?;
-            first = false;
-        }
-
-        if self.contains(SEVStatusFlags::ALT_INJ) {
-            if !first {
-                f.write_char(' ')?;
-            }
-            f.write_str("ALTERNATE_INJECTION")?;
-            first = false;
-        }
-
-        if self.contains(SEVStatusFlags::DBGSWP) {
-            if !first {
-                f.write_char(' ')?;
-            }
-            f.write_str("DEBUG_SWAP")?;
-            first = false;
-        }
-
-        if self.contains(SEVStatusFlags::PREV_HOST_IBS) {
-            if !first {
-                f.write_char(' ')?;
+        for (flag, name) in SEV_STATUS_NAMES {
+            if !self.contains(flag) {
+                continue;
             }
-            f.write_str("PREVENT_HOST_IBS")?;
-            first = false;
-        }
-
-        if self.contains(SEVStatusFlags::BTB_ISOLATION) {
-            if !first {
-                f.write_char(' ')?;
-            }
-            f.write_str("SNP_BTB_ISOLATION")?;
-            first = false;
-        }
-
-        if self.contains(SEVStatusFlags::SECURE_TSC) {
             if !first {
                 f.write_char(' ')?;
             }
-            f.write_str("SECURE_TSC")?;
+            f.write_str(name)?;
             first = false;
         }
 
-        if self.contains(SEVStatusFlags::VMSA_REG_PROT) {
-            if !first {
-                f.write_char(' ')?;
-            }
-            f.write_str("VMSA_REG_PROT")?;
-        }
-
         Ok(())
     }
 }
 
 static SEV_FLAGS: ImmutAfterInitCell<SEVStatusFlags> = ImmutAfterInitCell::uninit();
 
-fn read_sev_status() -> SEVStatusFlags {
-    SEVStatusFlags::from_bits_truncate(read_msr(SEV_STATUS))
-}
-
 pub fn sev_flags() -> SEVStatusFlags {
     *SEV_FLAGS
 }
 
 pub fn sev_status_init() {
-    let status: SEVStatusFlags = read_sev_status();
+    let status = SEVStatusFlags::read_current();
     SEV_FLAGS
         .init(&status)
         .expect("Already initialized SEV flags");
@@ -186,6 +119,18 @@ pub fn sev_status_verify() {
 }
 
 impl SEVStatusFlags {
+    /// Reads the SEV features active on the current CPU from the
+    /// `SEV_STATUS` MSR. Unknown bits are dropped.
+    pub fn read_current() -> Self {
+        Self::from_bits_truncate(read_msr(SEV_STATUS))
+    }
+
+    /// Returns whether SEV-ES or SEV-SNP is active, in which case
+    /// communication with the hypervisor has to go through a GHCB.
+    pub fn requires_ghcb(&self) -> bool {
+        self.intersects(SEVStatusFlags::SEV_ES | SEVStatusFlags::SEV_SNP)
+    }
+
     pub fn from_sev_features(sev_features: u64) -> Self {
         SEVStatusFlags::from_bits(sev_features << 2).unwrap()
     }
@@ -195,3 +140,39 @@ impl SEVStatusFlags {
         sev_features >> 2
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_display_all_flags() {
+        let rendered = SEVStatusFlags::all().to_string();
+        let names: Vec<&str> = rendered.split(' ').collect();
+
+        assert_eq!(names.len(), SEV_STATUS_NAMES.len());
+        for (_, name) in SEV_STATUS_NAMES {
+            assert_eq!(names.iter().filter(|n| **n == name).count(), 1);
+        }
+        assert_eq!(SEVStatusFlags::empty().to_string(), "");
+    }
+
+    #[test]
+    fn test_display_names_cover_all_flags() {
+        let covered = SEV_STATUS_NAMES
+            .iter()
+            .fold(SEVStatusFlags::empty(), |acc, (flag, _)| acc | *flag);
+        assert!(covered == SEVStatusFlags::all());
+    }
+
+    #[test]
+    fn test_requires_ghcb() {
+        assert!(!SEVStatusFlags::empty().requires_ghcb());
+        assert!(!SEVStatusFlags::SEV.requires_ghcb());
+        assert!((SEVStatusFlags::SEV | SEVStatusFlags::SEV_ES).requires_ghcb());
+        assert!(SEVStatusFlags::SEV_SNP.requires_ghcb());
+    }
+}
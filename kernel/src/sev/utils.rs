@@ -4,7 +4,7 @@
 //
 // Author: Joerg Roedel <jroedel@suse.de>
 
-use crate::address::{Address, VirtAddr};
+use crate::address::{Address, PhysAddr, VirtAddr};
 use crate::error::SvsmError;
 use crate::types::{PageSize, GUEST_VMPL, PAGE_SIZE, PAGE_SIZE_2M};
 use crate::utils::MemoryRegion;
@@ -51,34 +51,63 @@ impl fmt::Display for SevSnpError {
     }
 }
 
-fn pvalidate_range_4k(region: MemoryRegion<VirtAddr>, valid: PvalidateOp) -> Result<(), SvsmError> {
-    for addr in region.iter_pages(PageSize::Regular) {
-        pvalidate(addr, PageSize::Regular, valid)?;
+/// Attaches the failing page and page size to an error returned by
+/// PVALIDATE so that the failure can be located.
+fn validation_error(vaddr: VirtAddr, paddr: PhysAddr, size: PageSize, err: SvsmError) -> SvsmError {
+    match err {
+        SvsmError::SevSnp(error) => SvsmError::Validation {
+            vaddr,
+            paddr,
+            size,
+            error,
+        },
+        _ => err,
     }
-
-    Ok(())
 }
 
+/// Validates or invalidates the pages of `region`, which must map the
+/// physically contiguous range starting at `paddr`.
 pub fn pvalidate_range(
     region: MemoryRegion<VirtAddr>,
+    paddr: PhysAddr,
     valid: PvalidateOp,
 ) -> Result<(), SvsmError> {
+    pvalidate_range_with(region, paddr, valid, pvalidate)
+}
+
+fn pvalidate_range_with<F>(
+    region: MemoryRegion<VirtAddr>,
+    paddr: PhysAddr,
+    valid: PvalidateOp,
+    mut pvalidate_fn: F,
+) -> Result<(), SvsmError>
+where
+    F: FnMut(VirtAddr, PageSize, PvalidateOp) -> Result<(), SvsmError>,
+{
     let mut addr = region.start();
     let end = region.end();
+    let phys = |vaddr: VirtAddr| paddr + (vaddr - region.start());
 
     while addr < end {
         if addr.is_aligned(PAGE_SIZE_2M) && addr + PAGE_SIZE_2M <= end {
             // Try to validate as a huge page.
             // If we fail, try to fall back to regular-sized pages.
-            pvalidate(addr, PageSize::Huge, valid).or_else(|err| match err {
-                SvsmError::SevSnp(SevSnpError::FAIL_SIZEMISMATCH(_)) => {
-                    pvalidate_range_4k(MemoryRegion::new(addr, PAGE_SIZE_2M), valid)
+            match pvalidate_fn(addr, PageSize::Huge, valid) {
+                Ok(()) => {}
+                Err(SvsmError::SevSnp(SevSnpError::FAIL_SIZEMISMATCH(_))) => {
+                    let huge_region = MemoryRegion::new(addr, PAGE_SIZE_2M);
+                    for page in huge_region.iter_pages(PageSize::Regular) {
+                        pvalidate_fn(page, PageSize::Regular, valid).map_err(|err| {
+                            validation_error(page, phys(page), PageSize::Regular, err)
+                        })?;
+                    }
                 }
-                _ => Err(err),
-            })?;
+                Err(err) => return Err(validation_error(addr, phys(addr), PageSize::Huge, err)),
+            }
             addr = addr + PAGE_SIZE_2M;
         } else {
-            pvalidate(addr, PageSize::Regular, valid)?;
+            pvalidate_fn(addr, PageSize::Regular, valid)
+                .map_err(|err| validation_error(addr, phys(addr), PageSize::Regular, err))?;
             addr = addr + PAGE_SIZE;
         }
     }
@@ -255,3 +284,61 @@ pub fn rmp_clear_guest_vmsa(vaddr: VirtAddr) -> Result<(), SvsmError> {
     rmp_revoke_guest_access(vaddr, PageSize::Regular)?;
     rmp_grant_guest_access(vaddr, PageSize::Regular)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pvalidate_range_reports_failing_page() {
+        let start = VirtAddr::from(0x200000u64);
+        let failing = start + PAGE_SIZE_2M + 3 * PAGE_SIZE;
+        let region = MemoryRegion::new(start, 2 * PAGE_SIZE_2M);
+        let paddr = PhysAddr::from(0x8000_0000u64);
+
+        // Huge pages are reported as size mismatches so that the range
+        // falls back to 4K validation, which fails on a specific page.
+        let pvalidate_fn = |addr: VirtAddr, size: PageSize, _: PvalidateOp| match size {
+            PageSize::Huge => Err(SevSnpError::FAIL_SIZEMISMATCH(6).into()),
+            PageSize::Regular if addr == failing => Err(SevSnpError::FAIL_INPUT(1).into()),
+            PageSize::Regular => Ok(()),
+        };
+        let result = pvalidate_range_with(region, paddr, PvalidateOp::Valid, pvalidate_fn);
+
+        match result {
+            Err(SvsmError::Validation {
+                vaddr,
+                paddr,
+                size,
+                error,
+            }) => {
+                assert_eq!(vaddr, failing);
+                assert_eq!(paddr, PhysAddr::from(0x8020_3000u64));
+                assert_eq!(size, PageSize::Regular);
+                assert_eq!(error, SevSnpError::FAIL_INPUT(1));
+            }
+            _ => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_pvalidate_range_reports_failing_huge_page() {
+        let start = VirtAddr::from(0x200000u64);
+        let region = MemoryRegion::new(start, PAGE_SIZE_2M);
+        let paddr = PhysAddr::from(0x8000_0000u64);
+        let result = pvalidate_range_with(region, paddr, PvalidateOp::Invalid, |_, _, _| {
+            Err(SevSnpError::FAIL_INPUT(1).into())
+        });
+
+        match result {
+            Err(SvsmError::Validation {
+                vaddr, paddr, size, ..
+            }) => {
+                assert_eq!(vaddr, start);
+                assert_eq!(paddr, PhysAddr::from(0x8000_0000u64));
+                assert_eq!(size, PageSize::Huge);
+            }
+            _ => panic!("unexpected result {:?}", result),
+        }
+    }
+}
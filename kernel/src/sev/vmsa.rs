@@ -49,6 +49,9 @@ pub fn free_vmsa(vaddr: VirtAddr) {
 pub trait VMSAControl {
     fn enable(&mut self);
     fn disable(&mut self);
+    /// Schedules exception `vector` for injection on the next entry.
+    /// Returns `false` if another event is already pending injection.
+    fn inject_exception(&mut self, vector: u8, error_code: Option<u32>) -> bool;
 }
 
 impl VMSAControl for VMSA {
@@ -59,6 +62,20 @@ impl VMSAControl for VMSA {
     fn disable(&mut self) {
         self.efer &= !(1u64 << 12);
     }
+
+    fn inject_exception(&mut self, vector: u8, error_code: Option<u32>) -> bool {
+        let event_inj = self.event_inj;
+        if event_inj.valid() {
+            return false;
+        }
+        self.event_inj = VmsaEventInject::new()
+            .with_vector(vector)
+            .with_valid(true)
+            .with_event_type(VmsaEventType::Exception)
+            .with_error_code_valid(error_code.is_some())
+            .with_error_code(error_code.unwrap_or(0));
+        true
+    }
 }
 
 impl GuestCpuState for VMSA {
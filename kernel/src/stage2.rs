@@ -139,7 +139,7 @@ fn map_and_validate(
             PageStateChangeOp::Private,
         )?;
     }
-    platform.validate_page_range(vregion)?;
+    platform.validate_page_range(vregion, paddr)?;
     valid_bitmap_set_valid_range(paddr, paddr + vregion.len());
     Ok(())
 }
@@ -214,6 +214,10 @@ fn load_kernel_elf(
     let elf_len = elf_end - elf_start;
     let bytes = unsafe { slice::from_raw_parts(elf_start.bits() as *const u8, elf_len) };
     let elf = elf::Elf64File::read(bytes)?;
+    if elf.machine() != elf::Elf64Machine::X86_64 {
+        log::error!("Kernel ELF is not an x86-64 image");
+        return Err(elf::ElfError::UnsupportedMachine.into());
+    }
 
     let vaddr_alloc_info = elf.image_load_vaddr_alloc_info();
     let vaddr_alloc_base = vaddr_alloc_info.range.vaddr_begin;
@@ -246,11 +250,21 @@ fn load_kernel_elf(
     };
 
     // Apply relocations, if any
-    if let Some(dyn_relocs) =
-        elf.apply_dyn_relas(elf::Elf64X86RelocProcessor::new(), vaddr_alloc_base)?
-    {
+    let reloc_record = elf::Elf64RelocRecord::default();
+    let rela_proc = elf::Elf64RecordingRelocProcessor::new(elf.reloc_processor(), &reloc_record);
+    if let Some(dyn_relocs) = elf.apply_dyn_relas(rela_proc, vaddr_alloc_base)? {
         for reloc in dyn_relocs {
-            let Some(reloc) = reloc? else {
+            let reloc = reloc.inspect_err(|_| {
+                if let Some(r_type) = reloc_record.first_unhandled() {
+                    let name = elf::Elf64X86RelocProcessor::type_name(r_type).unwrap_or("unknown");
+                    log::error!(
+                        "Unsupported relocation type {} ({}) in kernel ELF",
+                        r_type,
+                        name
+                    );
+                }
+            })?;
+            let Some(reloc) = reloc else {
                 continue;
             };
             let dst = unsafe { slice::from_raw_parts_mut(reloc.dst as *mut u8, reloc.value_len) };
@@ -10,6 +10,7 @@
 use svsm::fw_meta::{print_fw_meta, validate_fw_memory, SevFWMetaData};
 
 use bootlib::kernel_launch::KernelLaunchInfo;
+use bootlib::platform::SvsmPlatformType;
 use core::arch::global_asm;
 use core::mem::size_of;
 use core::panic::PanicInfo;
@@ -20,7 +21,7 @@ use svsm::address::{PhysAddr, VirtAddr};
 use svsm::config::SvsmConfig;
 use svsm::console::{init_console, install_console_logger};
 use svsm::cpu::control_regs::{cr0_init, cr4_init};
-use svsm::cpu::cpuid::{dump_cpuid_table, register_cpuid_table};
+use svsm::cpu::cpuid::{dump_cpuid_table, populate_cpuid_table, register_cpuid_table};
 use svsm::cpu::efer::efer_init;
 use svsm::cpu::gdt;
 use svsm::cpu::idt::svsm::{early_idt_init, idt_init};
@@ -51,7 +52,7 @@ use svsm::svsm_paging::{init_page_table, invalidate_early_boot_memory};
 use svsm::task::exec_user;
 use svsm::task::{create_kernel_task, schedule_init};
 use svsm::types::{PageSize, GUEST_VMPL, PAGE_SIZE};
-use svsm::utils::{halt, immut_after_init::ImmutAfterInitCell, zero_mem_region};
+use svsm::utils::{halt, immut_after_init::ImmutAfterInitCell, zero_mem_region, OneShot};
 #[cfg(all(feature = "mstpm", not(test)))]
 use svsm::vtpm::vtpm_init;
 
@@ -99,6 +100,12 @@ global_asm!(
 static CPUID_PAGE: ImmutAfterInitCell<SnpCpuidTable> = ImmutAfterInitCell::uninit();
 static LAUNCH_INFO: ImmutAfterInitCell<KernelLaunchInfo> = ImmutAfterInitCell::uninit();
 
+// One-shot guards that prevent the guest firmware from being prepared or
+// launched more than once, which would re-register the guest VMSA.  A failed
+// attempt can be retried.
+static FW_PREPARED: OneShot = OneShot::new();
+static FW_LAUNCHED: OneShot = OneShot::new();
+
 const _: () = assert!(size_of::<SnpCpuidTable>() <= PAGE_SIZE);
 
 fn copy_cpuid_table_to_fw(fw_addr: PhysAddr) -> Result<(), SvsmError> {
@@ -171,6 +178,8 @@ fn copy_tables_to_fw(fw_meta: &SevFWMetaData) -> Result<(), SvsmError> {
 }
 
 fn prepare_fw_launch(fw_meta: &SevFWMetaData) -> Result<(), SvsmError> {
+    let prepared = FW_PREPARED.begin().ok_or(SvsmError::Firmware)?;
+
     if let Some(caa) = fw_meta.caa_page {
         this_cpu_shared().update_guest_caa(caa);
     }
@@ -178,10 +187,13 @@ fn prepare_fw_launch(fw_meta: &SevFWMetaData) -> Result<(), SvsmError> {
     this_cpu().alloc_guest_vmsa()?;
     update_mappings()?;
 
+    prepared.complete();
     Ok(())
 }
 
 fn launch_fw(config: &SvsmConfig<'_>) -> Result<(), SvsmError> {
+    let launched = FW_LAUNCHED.begin().ok_or(SvsmError::Firmware)?;
+
     let cpu = this_cpu();
     let mut vmsa_ref = cpu.guest_vmsa_ref();
     let vmsa_pa = vmsa_ref.vmsa_phys().unwrap();
@@ -196,12 +208,13 @@ fn launch_fw(config: &SvsmConfig<'_>) -> Result<(), SvsmError> {
     log::info!("Launching Firmware");
     current_ghcb().register_guest_vmsa(vmsa_pa, 0, GUEST_VMPL as u64, sev_features)?;
 
+    launched.complete();
     Ok(())
 }
 
 fn validate_fw(config: &SvsmConfig<'_>, launch_info: &KernelLaunchInfo) -> Result<(), SvsmError> {
     let kernel_region = new_kernel_region(launch_info);
-    let flash_regions = config.get_fw_regions(&kernel_region);
+    let flash_regions = config.get_fw_regions(&kernel_region)?;
 
     for (i, region) in flash_regions.into_iter().enumerate() {
         log::info!(
@@ -258,7 +271,7 @@ fn mapping_info_init(launch_info: &KernelLaunchInfo) {
 /// # Panics
 ///
 /// Panics if the provided address is not aligned to a [`SnpCpuidTable`].
-fn init_cpuid_table(addr: VirtAddr) {
+fn init_cpuid_table(addr: VirtAddr, platform_type: SvsmPlatformType) {
     // SAFETY: this is called from the main function for the SVSM and no other
     // CPUs have been brought up, so the pointer cannot be aliased.
     // `aligned_mut()` will check alignment for us.
@@ -267,10 +280,20 @@ fn init_cpuid_table(addr: VirtAddr) {
             .expect("Misaligned SNP CPUID table address")
     };
 
-    for func in table.func.iter_mut().take(table.count as usize) {
-        if func.eax_in == 0x8000001f {
-            func.eax_out |= 1 << 28;
+    // Only SNP launches with a CPUID page populated by the hypervisor.  On
+    // other platforms the table is synthesized from the native CPUID values.
+    if matches!(platform_type, SvsmPlatformType::Snp) {
+        for func in table.func.iter_mut().take(table.count as usize) {
+            if func.eax_in == 0x8000001f {
+                func.eax_out |= 1 << 28;
+            }
         }
+    } else if let Err(e) = populate_cpuid_table(table) {
+        log::warn!(
+            "CPUID table truncated to {} entries: {:?}",
+            { table.count },
+            e
+        );
     }
 
     CPUID_PAGE
@@ -302,7 +325,7 @@ pub extern "C" fn svsm_start(li: &KernelLaunchInfo, vb_addr: usize) {
     let mut platform_cell = SvsmPlatformCell::new(li.platform_type);
     let platform = platform_cell.as_mut_dyn_ref();
 
-    init_cpuid_table(VirtAddr::from(launch_info.cpuid_page));
+    init_cpuid_table(VirtAddr::from(launch_info.cpuid_page), li.platform_type);
 
     let secrets_page_virt = VirtAddr::from(launch_info.secrets_page);
 
@@ -441,6 +464,10 @@ pub extern "C" fn svsm_main() {
 
     log::info!("{} CPU(s) present", nr_cpus);
 
+    // The BSP is fully set up at this point, so count it among the online
+    // CPUs before bringing up the APs.
+    this_cpu_shared().set_online();
+
     start_secondary_cpus(platform, &cpus, launch_info.vtom);
 
     let fw_metadata = config.get_fw_metadata();
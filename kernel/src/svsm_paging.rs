@@ -110,7 +110,7 @@ fn invalidate_boot_memory_region(
         let guard = PerCPUPageMappingGuard::create_4k(paddr)?;
         let vaddr = guard.virt_addr();
 
-        platform.invalidate_page_range(MemoryRegion::new(vaddr, PAGE_SIZE))?;
+        platform.invalidate_page_range(MemoryRegion::new(vaddr, PAGE_SIZE), paddr)?;
     }
 
     if config.page_state_change_required() && !region.is_empty() {
@@ -11,7 +11,7 @@ use crate::mm::vm::VMFileMappingFlags;
 use crate::mm::USER_MEM_END;
 use crate::task::{create_user_task, current_task, schedule};
 use crate::types::PAGE_SIZE;
-use elf::{Elf64File, Elf64PhdrFlags};
+use elf::{Elf64File, Elf64Machine, Elf64PhdrFlags};
 
 fn convert_elf_phdr_flags(flags: Elf64PhdrFlags) -> VMFileMappingFlags {
     let mut vm_flags = VMFileMappingFlags::Fixed;
@@ -41,6 +41,9 @@ pub fn exec_user(binary: &str) -> Result<(), SvsmError> {
     )?;
     let buf = unsafe { vstart.to_slice::<u8>(file_size) };
     let elf_bin = Elf64File::read(buf).map_err(|_| SvsmError::Mem)?;
+    if elf_bin.machine() != Elf64Machine::X86_64 {
+        return Err(SvsmError::Mem);
+    }
 
     let alloc_info = elf_bin.image_load_vaddr_alloc_info();
     let virt_base = alloc_info.range.vaddr_begin;
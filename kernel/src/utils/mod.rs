@@ -7,9 +7,12 @@
 pub mod bitmap_allocator;
 pub mod immut_after_init;
 pub mod memory_region;
+pub mod one_shot;
 pub mod util;
 
 pub use memory_region::MemoryRegion;
+pub use one_shot::OneShot;
 pub use util::{
     align_down, align_up, halt, is_aligned, overlap, page_align_up, page_offset, zero_mem_region,
+    zero_secret,
 };
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) Microsoft Corporation
+//
+// Author: agent <agent@local>
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const NOT_STARTED: u8 = 0;
+const IN_PROGRESS: u8 = 1;
+const DONE: u8 = 2;
+
+/// Guards an operation that must succeed at most once.  A failed attempt
+/// does not count, so the operation can be retried until one attempt
+/// completes.
+#[derive(Debug)]
+pub struct OneShot {
+    state: AtomicU8,
+}
+
+impl OneShot {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(NOT_STARTED),
+        }
+    }
+
+    /// Claims the operation.  Returns `None` if it has already completed or
+    /// another attempt is in progress.  The attempt counts only once
+    /// [`OneShotGuard::complete()`] is called; dropping the guard without
+    /// completing it allows the operation to be attempted again.
+    pub fn begin(&self) -> Option<OneShotGuard<'_>> {
+        self.state
+            .compare_exchange(
+                NOT_STARTED,
+                IN_PROGRESS,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .ok()
+            .map(|_| OneShotGuard { state: &self.state })
+    }
+
+    /// Returns `true` if an attempt has completed.
+    pub fn is_done(&self) -> bool {
+        self.state.load(Ordering::Acquire) == DONE
+    }
+}
+
+impl Default for OneShot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An attempt at an operation claimed through [`OneShot::begin()`].
+#[derive(Debug)]
+#[must_use = "dropping the guard abandons the attempt"]
+pub struct OneShotGuard<'a> {
+    state: &'a AtomicU8,
+}
+
+impl OneShotGuard<'_> {
+    /// Marks the operation as completed, so that it can never be claimed
+    /// again.
+    pub fn complete(self) {
+        self.state.store(DONE, Ordering::Release);
+        core::mem::forget(self);
+    }
+}
+
+impl Drop for OneShotGuard<'_> {
+    fn drop(&mut self) {
+        self.state.store(NOT_STARTED, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_shot_completes_once() {
+        let once = OneShot::new();
+        once.begin().unwrap().complete();
+        assert!(once.is_done());
+        assert!(once.begin().is_none());
+    }
+
+    #[test]
+    fn test_one_shot_in_progress() {
+        let once = OneShot::new();
+        let guard = once.begin().unwrap();
+        assert!(once.begin().is_none());
+        guard.complete();
+        assert!(once.begin().is_none());
+    }
+
+    #[test]
+    fn test_one_shot_retry_after_failure() {
+        let once = OneShot::new();
+        drop(once.begin().unwrap());
+        assert!(!once.is_done());
+        once.begin().unwrap().complete();
+        assert!(once.is_done());
+    }
+}
@@ -8,6 +8,8 @@ use crate::address::{Address, VirtAddr};
 use crate::types::PAGE_SIZE;
 use core::arch::asm;
 use core::ops::{Add, BitAnd, Not, Sub};
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
 
 pub fn align_up<T>(addr: T, align: T) -> T
 where
@@ -62,6 +64,16 @@ pub fn zero_mem_region(start: VirtAddr, end: VirtAddr) {
     unsafe { start.as_mut_ptr::<u8>().write_bytes(0, size) }
 }
 
+/// Zeroes a buffer holding secret data.  Unlike a plain `fill(0)`, the
+/// stores cannot be elided even if the buffer is never read again.
+pub fn zero_secret(buf: &mut [u8]) {
+    for byte in buf.iter_mut() {
+        // SAFETY: the pointer comes from a valid mutable reference.
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
 /// Obtain bit for a given position
 #[macro_export]
 macro_rules! BIT {
@@ -111,4 +123,11 @@ mod tests {
             assert_eq!(*byte, 0);
         }
     }
+
+    #[test]
+    fn test_zero_secret() {
+        let mut data: [u8; 10] = [0xa5; 10];
+        zero_secret(&mut data);
+        assert_eq!(data, [0; 10]);
+    }
 }